@@ -1,7 +1,12 @@
 fn main() {
+    // without DeepStream the functions are stubs, see `nvds_extern`
+    if std::env::var_os("CARGO_FEATURE_NVDS").is_none() {
+        return;
+    }
     println!("cargo:rustc-link-search=/opt/nvidia/deepstream/deepstream/lib");
     println!("cargo:rustc-link-lib=nvdsgst_helper");
     println!("cargo:rustc-link-lib=nvdsgst_meta");
     println!("cargo:rustc-link-lib=nvds_meta");
+    println!("cargo:rustc-link-lib=nvbufsurface");
     //println!("cargo:rustc-link-lib=nvds_infer");
 }
@@ -5,12 +5,19 @@ use libc::{
 };
 
 use glib_sys::{gpointer, GType};
-use gst_sys::GstMeta;
+use gst_sys::{GstBuffer, GstMeta};
 
 use crate::nvdsmeta;
 
-extern "C" {
+nvds_extern! {
     pub fn nvds_meta_api_get_type() -> GType;
+    pub fn gst_buffer_add_nvds_meta(
+        buffer: *mut GstBuffer,
+        meta_data: gpointer,
+        user_data: gpointer,
+        copy_func: nvdsmeta::NvDsMetaCopyFunc,
+        release_func: nvdsmeta::NvDsMetaReleaseFunc,
+    ) -> *mut NvDsMeta;
 }
 
 pub type GstNvDsMetaType = c_int;
@@ -1,12 +1,53 @@
-use gst_sys::GstEvent;
-use libc::c_int;
+use gst_sys::{
+    GstEvent, GstEventType, GST_EVENT_NUM_SHIFT, GST_EVENT_TYPE_DOWNSTREAM,
+    GST_EVENT_TYPE_SERIALIZED,
+};
+use libc::{c_int, c_uint};
+
+/// Declare the DeepStream functions, linked with the `nvds` feature. Without
+/// it they panic, the buffers of a pipeline without DeepStream elements have
+/// no DeepStream meta to call them on.
+macro_rules! nvds_extern {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        #[cfg(feature = "nvds")]
+        extern "C" {
+            $(pub fn $name($($arg: $ty),*) $(-> $ret)?;)*
+        }
+
+        $(
+            #[cfg(not(feature = "nvds"))]
+            #[allow(unused_variables)]
+            pub unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? {
+                panic!(concat!(stringify!($name), " needs the nvds feature"))
+            }
+        )*
+    };
+}
 
 pub mod gst_nvdsmeta;
+pub mod nvbufsurface;
+pub mod nvds_latency_meta;
 pub mod nvds_roi_meta;
 pub mod nvdsmeta;
 pub mod nvdsmeta_schema;
 pub mod nvll_osd_struct;
 
-extern "C" {
+const fn gst_nvevent_make_type(num: GstEventType) -> GstEventType {
+    (num << GST_EVENT_NUM_SHIFT)
+        | (GST_EVENT_TYPE_DOWNSTREAM | GST_EVENT_TYPE_SERIALIZED) as GstEventType
+}
+
+pub type GstNvEventType = GstEventType;
+pub const GST_NVEVENT_PAD_ADDED: GstNvEventType = gst_nvevent_make_type(400);
+pub const GST_NVEVENT_PAD_DELETED: GstNvEventType = gst_nvevent_make_type(401);
+pub const GST_NVEVENT_STREAM_EOS: GstNvEventType = gst_nvevent_make_type(402);
+pub const GST_NVEVENT_STREAM_SEGMENT: GstNvEventType = gst_nvevent_make_type(403);
+pub const GST_NVEVENT_STREAM_RESET: GstNvEventType = gst_nvevent_make_type(404);
+pub const GST_NVEVENT_STREAM_START: GstNvEventType = gst_nvevent_make_type(405);
+
+nvds_extern! {
     pub fn gst_nvevent_new_stream_reset(source_id: c_int) -> *mut GstEvent;
+    pub fn gst_nvevent_parse_stream_reset(event: *mut GstEvent, source_id: *mut c_uint);
+    pub fn gst_nvevent_parse_stream_eos(event: *mut GstEvent, source_id: *mut c_uint);
+    pub fn gst_nvevent_parse_pad_deleted(event: *mut GstEvent, source_id: *mut c_uint);
 }
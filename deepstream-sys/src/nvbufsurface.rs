@@ -0,0 +1,80 @@
+#[allow(unused_imports)]
+use libc::{c_int, c_uint, c_void};
+
+pub const NVBUF_MAX_PLANES: usize = 4;
+const STRUCTURE_PADDING: usize = 4;
+
+pub type NvBufSurfaceMemType = c_int;
+pub const NVBUF_MEM_DEFAULT: NvBufSurfaceMemType = 0;
+pub const NVBUF_MEM_CUDA_PINNED: NvBufSurfaceMemType = 1;
+pub const NVBUF_MEM_CUDA_DEVICE: NvBufSurfaceMemType = 2;
+pub const NVBUF_MEM_CUDA_UNIFIED: NvBufSurfaceMemType = 3;
+pub const NVBUF_MEM_SURFACE_ARRAY: NvBufSurfaceMemType = 4;
+pub const NVBUF_MEM_HANDLE: NvBufSurfaceMemType = 5;
+pub const NVBUF_MEM_SYSTEM: NvBufSurfaceMemType = 6;
+
+pub type NvBufSurfaceColorFormat = c_int;
+pub type NvBufSurfaceLayout = c_int;
+
+pub type NvBufSurfaceMemMapFlags = c_int;
+pub const NVBUF_MAP_READ: NvBufSurfaceMemMapFlags = 0;
+pub const NVBUF_MAP_WRITE: NvBufSurfaceMemMapFlags = 1;
+pub const NVBUF_MAP_READ_WRITE: NvBufSurfaceMemMapFlags = 2;
+
+/// The camel case fields of the C structs are in snake case.
+#[repr(C)]
+pub struct NvBufSurfacePlaneParams {
+    pub num_planes: c_uint,
+    pub width: [c_uint; NVBUF_MAX_PLANES],
+    pub height: [c_uint; NVBUF_MAX_PLANES],
+    pub pitch: [c_uint; NVBUF_MAX_PLANES],
+    pub offset: [c_uint; NVBUF_MAX_PLANES],
+    pub psize: [c_uint; NVBUF_MAX_PLANES],
+    pub bytes_per_pix: [c_uint; NVBUF_MAX_PLANES],
+    pub _reserved: [*mut c_void; STRUCTURE_PADDING * NVBUF_MAX_PLANES],
+}
+
+#[repr(C)]
+pub struct NvBufSurfaceMappedAddr {
+    pub addr: [*mut c_void; NVBUF_MAX_PLANES],
+    pub egl_image: *mut c_void,
+    pub _reserved: [*mut c_void; STRUCTURE_PADDING],
+}
+
+#[repr(C)]
+pub struct NvBufSurfaceParams {
+    pub width: c_uint,
+    pub height: c_uint,
+    pub pitch: c_uint,
+    pub color_format: NvBufSurfaceColorFormat,
+    pub layout: NvBufSurfaceLayout,
+    pub buffer_desc: u64,
+    pub data_size: c_uint,
+    pub data_ptr: *mut c_void,
+    pub plane_params: NvBufSurfacePlaneParams,
+    pub mapped_addr: NvBufSurfaceMappedAddr,
+    pub paramex: *mut c_void,
+    pub _reserved: [*mut c_void; STRUCTURE_PADDING - 1],
+}
+
+#[repr(C)]
+pub struct NvBufSurface {
+    pub gpu_id: c_uint,
+    pub batch_size: c_uint,
+    pub num_filled: c_uint,
+    pub is_contiguous: bool,
+    pub mem_type: NvBufSurfaceMemType,
+    pub surface_list: *mut NvBufSurfaceParams,
+    pub _reserved: [*mut c_void; STRUCTURE_PADDING],
+}
+
+nvds_extern! {
+    pub fn NvBufSurfaceMap(
+        surf: *mut NvBufSurface,
+        index: c_int,
+        plane: c_int,
+        type_: NvBufSurfaceMemMapFlags,
+    ) -> c_int;
+    pub fn NvBufSurfaceUnMap(surf: *mut NvBufSurface, index: c_int, plane: c_int) -> c_int;
+    pub fn NvBufSurfaceSyncForCpu(surf: *mut NvBufSurface, index: c_int, plane: c_int) -> c_int;
+}
@@ -0,0 +1,34 @@
+#[allow(unused_imports)]
+use libc::{c_char, c_double, c_uint};
+
+use glib_sys::gboolean;
+use gst_sys::GstBuffer;
+
+pub const MAX_COMPONENT_LEN: usize = 64;
+
+#[repr(C)]
+pub struct NvDsMetaCompLatency {
+    pub component_name: [c_char; MAX_COMPONENT_LEN],
+    pub in_system_timestamp: c_double,
+    pub out_system_timestamp: c_double,
+    pub source_id: c_uint,
+    pub frame_num: c_uint,
+    pub pad_index: c_uint,
+}
+
+#[repr(C)]
+pub struct NvDsFrameLatencyInfo {
+    pub source_id: c_uint,
+    pub frame_num: c_uint,
+    pub comp_in_timestamp: c_double,
+    pub latency: c_double,
+}
+
+nvds_extern! {
+    pub fn nvds_measure_buffer_latency(
+        buf: *mut GstBuffer,
+        latency_info: *mut NvDsFrameLatencyInfo,
+    ) -> c_uint;
+    pub fn nvds_get_enable_latency_measurement() -> gboolean;
+    pub fn nvds_get_enable_component_latency_measurement() -> gboolean;
+}
@@ -12,6 +12,7 @@ use crate::nvll_osd_struct;
 const MAX_USER_FIELDS: usize = 4;
 const MAX_RESERVED_FIELDS: usize = 4;
 const MAX_LABEL_SIZE: usize = 128;
+pub const MAX_ELEMENTS_IN_DISPLAY_META: usize = 16;
 
 pub type NvDsFrameMetaList = GList;
 pub type NvDsUserMetaList = GList;
@@ -63,7 +64,7 @@ pub struct NvDsMetaPool {
     pub empty_list: *mut NvDsMetaList,
     pub full_list: *mut NvDsMetaList,
     pub copy_func: NvDsMetaCopyFunc,
-    pub release_func: Option<NvDsMetaReleaseFunc>,
+    pub release_func: NvDsMetaReleaseFunc,
 }
 
 #[repr(C)]
@@ -139,16 +140,86 @@ pub struct NvDsObjectMeta {
     pub reserved: [c_long; MAX_RESERVED_FIELDS],
 }
 
+#[repr(C)]
+pub struct NvDsDisplayMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub num_rects: c_uint,
+    pub num_labels: c_uint,
+    pub num_lines: c_uint,
+    pub num_arrows: c_uint,
+    pub num_circles: c_uint,
+    pub rect_params: [nvds_roi_meta::NvOSD_RectParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub text_params: [nvds_roi_meta::NvOSD_TextParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub line_params: [nvll_osd_struct::NvOSD_LineParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub arrow_params: [nvll_osd_struct::NvOSD_ArrowParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub circle_params: [nvll_osd_struct::NvOSD_CircleParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub misc_osd_data: [c_longlong; MAX_USER_FIELDS],
+    pub reserved: [c_longlong; MAX_RESERVED_FIELDS],
+}
+
+#[repr(C)]
+pub struct NvDsClassifierMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub num_labels: c_uint,
+    pub unique_component_id: c_int,
+    pub label_info_list: *mut NvDsLabelInfoList,
+    pub classifier_type: *const c_char,
+}
+
+#[repr(C)]
+pub struct NvDsLabelInfo {
+    pub base_meta: NvDsBaseMeta,
+    pub num_classes: c_uint,
+    pub result_label: [c_char; MAX_LABEL_SIZE],
+    /// `pResult_label`, set for the labels longer than `MAX_LABEL_SIZE`.
+    pub p_result_label: *mut c_char,
+    pub result_class_id: c_uint,
+    pub label_id: c_uint,
+    pub result_prob: c_float,
+}
+
 #[repr(C)]
 pub struct NvDsUserMeta {
     pub base_meta: NvDsBaseMeta,
     pub user_meta_data: *mut c_void,
 }
 
-extern "C" {
+nvds_extern! {
+    pub fn nvds_create_batch_meta(max_batch_size: c_uint) -> *mut NvDsBatchMeta;
+    pub fn nvds_batch_meta_copy_func(data: gpointer, user_data: gpointer) -> gpointer;
+    pub fn nvds_batch_meta_release_func(data: gpointer, user_data: gpointer);
+    pub fn nvds_acquire_frame_meta_from_pool(batch_meta: *mut NvDsBatchMeta)
+        -> *mut NvDsFrameMeta;
+    pub fn nvds_add_frame_meta_to_batch(
+        batch_meta: *mut NvDsBatchMeta,
+        frame_meta: *mut NvDsFrameMeta,
+    );
     pub fn nvds_add_user_meta_to_frame(
         frame_meta: *mut NvDsFrameMeta,
         user_meta: *mut NvDsUserMeta,
     );
+    pub fn nvds_add_user_meta_to_batch(
+        batch_meta: *mut NvDsBatchMeta,
+        user_meta: *mut NvDsUserMeta,
+    );
+    pub fn nvds_add_user_meta_to_obj(obj_meta: *mut NvDsObjectMeta, user_meta: *mut NvDsUserMeta);
+    pub fn nvds_get_user_meta_type(meta_descriptor: *mut c_char) -> NvDsMetaType;
     pub fn nvds_acquire_user_meta_from_pool(batch_meta: *mut NvDsBatchMeta) -> *mut NvDsUserMeta;
+    pub fn nvds_acquire_obj_meta_from_pool(batch_meta: *mut NvDsBatchMeta) -> *mut NvDsObjectMeta;
+    pub fn nvds_add_obj_meta_to_frame(
+        frame_meta: *mut NvDsFrameMeta,
+        obj_meta: *mut NvDsObjectMeta,
+        obj_parent: *mut NvDsObjectMeta,
+    );
+    pub fn nvds_remove_obj_meta_from_frame(
+        frame_meta: *mut NvDsFrameMeta,
+        obj_meta: *mut NvDsObjectMeta,
+    );
+    pub fn nvds_acquire_display_meta_from_pool(
+        batch_meta: *mut NvDsBatchMeta,
+    ) -> *mut NvDsDisplayMeta;
+    pub fn nvds_add_display_meta_to_frame(
+        frame_meta: *mut NvDsFrameMeta,
+        display_meta: *mut NvDsDisplayMeta,
+    );
 }
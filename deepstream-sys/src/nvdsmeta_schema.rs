@@ -34,6 +34,16 @@ pub struct NvDsRect {
     pub height: c_float,
 }
 
+#[repr(C)]
+pub struct NvDsObjectAttribute {
+    pub classifier_id: c_int,
+    pub classifier_type: *mut c_char,
+    pub class_id: c_int,
+    pub label_id: c_int,
+    pub label: *mut c_char,
+    pub confidence: c_double,
+}
+
 #[repr(C)]
 pub struct NvDsEventMsgMeta {
     pub bbox: NvDsRect,
@@ -44,6 +54,11 @@ pub struct NvDsEventMsgMeta {
     pub confidence: c_double,
     pub tracking_id: c_int,
     pub ts: *mut c_char,
+    pub attributes: *mut NvDsObjectAttribute,
+    pub num_attributes: c_uint,
+    pub event_type: NvDsEventType,
+    pub zone: *mut c_char,
+    pub dwell_time: c_double,
 }
 
 #[repr(C)]
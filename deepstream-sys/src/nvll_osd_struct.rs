@@ -19,3 +19,42 @@ pub struct NvOSD_ColorParams {
     pub blue: c_double,
     pub alpha: c_double,
 }
+
+#[repr(C)]
+pub struct NvOSD_LineParams {
+    pub x1: c_uint,
+    pub y1: c_uint,
+    pub x2: c_uint,
+    pub y2: c_uint,
+    pub line_width: c_uint,
+    pub line_color: NvOSD_ColorParams,
+}
+
+#[allow(non_camel_case_types)]
+pub type NvOSD_Arrow_Head_Direction = c_int;
+pub const START_HEAD: NvOSD_Arrow_Head_Direction = 0;
+pub const END_HEAD: NvOSD_Arrow_Head_Direction = 1;
+pub const BOTH_HEAD: NvOSD_Arrow_Head_Direction = 2;
+
+#[repr(C)]
+pub struct NvOSD_ArrowParams {
+    pub x1: c_uint,
+    pub y1: c_uint,
+    pub x2: c_uint,
+    pub y2: c_uint,
+    pub arrow_width: c_uint,
+    pub arrow_head: NvOSD_Arrow_Head_Direction,
+    pub arrow_color: NvOSD_ColorParams,
+    pub reserved: c_uint,
+}
+
+#[repr(C)]
+pub struct NvOSD_CircleParams {
+    pub xc: c_uint,
+    pub yc: c_uint,
+    pub radius: c_uint,
+    pub circle_color: NvOSD_ColorParams,
+    pub has_bg_color: c_uint,
+    pub bg_color: NvOSD_ColorParams,
+    pub reserved: c_uint,
+}
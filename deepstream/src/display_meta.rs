@@ -0,0 +1,594 @@
+//! Shapes and texts drawn on the frames by the on screen display.
+//!
+//! A [`DisplayMeta`] is built in Rust then attached to a frame with
+//! [`DisplayMeta::attach`], which acquires as many `NvDsDisplayMeta` from the
+//! batch pool as its elements need.
+
+use libc::c_char;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::Mutex;
+
+use deepstream_sys::nvds_roi_meta::{NvOSD_FontParams, NvOSD_RectParams, NvOSD_TextParams};
+use deepstream_sys::nvdsmeta as ffi;
+use deepstream_sys::nvdsmeta::MAX_ELEMENTS_IN_DISPLAY_META;
+use deepstream_sys::nvll_osd_struct as osd;
+
+use crate::meta::{NvDsBatchMeta, NvDsFrameMeta};
+
+/// Font names given to the OSD, never released since it doesn't own them.
+static FONT_NAMES: Lazy<Mutex<HashMap<String, CString>>> = Lazy::new(Default::default);
+
+/// Color with its components between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    pub const RED: Color = Color::rgba(1.0, 0.0, 0.0, 1.0);
+    pub const GREEN: Color = Color::rgba(0.0, 1.0, 0.0, 1.0);
+    pub const BLUE: Color = Color::rgba(0.0, 0.0, 1.0, 1.0);
+    pub const YELLOW: Color = Color::rgba(1.0, 1.0, 0.0, 1.0);
+
+    pub const fn rgba(red: f64, green: f64, blue: f64, alpha: f64) -> Self {
+        Color {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+}
+
+impl From<Color> for osd::NvOSD_ColorParams {
+    fn from(color: Color) -> Self {
+        osd::NvOSD_ColorParams {
+            red: color.red,
+            green: color.green,
+            blue: color.blue,
+            alpha: color.alpha,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub border_width: u32,
+    pub border_color: Color,
+    pub bg_color: Option<Color>,
+}
+
+impl Rect {
+    pub fn new(left: f32, top: f32, width: f32, height: f32) -> Self {
+        Rect {
+            left,
+            top,
+            width,
+            height,
+            border_width: 2,
+            border_color: Color::WHITE,
+            bg_color: None,
+        }
+    }
+
+    pub fn border(mut self, width: u32, color: Color) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+
+    pub fn background(mut self, color: Color) -> Self {
+        self.bg_color = Some(color);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub from: (u32, u32),
+    pub to: (u32, u32),
+    pub width: u32,
+    pub color: Color,
+}
+
+impl Line {
+    pub fn new(from: (u32, u32), to: (u32, u32)) -> Self {
+        Line {
+            from,
+            to,
+            width: 2,
+            color: Color::WHITE,
+        }
+    }
+
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrowHead {
+    Start,
+    End,
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrow {
+    pub from: (u32, u32),
+    pub to: (u32, u32),
+    pub head: ArrowHead,
+    pub width: u32,
+    pub color: Color,
+}
+
+impl Arrow {
+    pub fn new(from: (u32, u32), to: (u32, u32), head: ArrowHead) -> Self {
+        Arrow {
+            from,
+            to,
+            head,
+            width: 2,
+            color: Color::WHITE,
+        }
+    }
+
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circle {
+    pub center: (u32, u32),
+    pub radius: u32,
+    pub color: Color,
+    pub bg_color: Option<Color>,
+}
+
+impl Circle {
+    pub fn new(center: (u32, u32), radius: u32) -> Self {
+        Circle {
+            center,
+            radius,
+            color: Color::WHITE,
+            bg_color: None,
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn background(mut self, color: Color) -> Self {
+        self.bg_color = Some(color);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    pub name: String,
+    pub size: u32,
+    pub color: Color,
+}
+
+impl Font {
+    pub fn new(name: &str, size: u32, color: Color) -> Self {
+        Font {
+            name: name.to_owned(),
+            size,
+            color,
+        }
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::new("Serif", 12, Color::WHITE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub x: u32,
+    pub y: u32,
+    pub font: Font,
+    pub bg_color: Option<Color>,
+}
+
+impl Text {
+    pub fn new(text: &str, x: u32, y: u32) -> Self {
+        Text {
+            text: text.to_owned(),
+            x,
+            y,
+            font: Font::default(),
+            bg_color: None,
+        }
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = font;
+        self
+    }
+
+    pub fn background(mut self, color: Color) -> Self {
+        self.bg_color = Some(color);
+        self
+    }
+}
+
+/// Elements to draw on a frame.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DisplayMeta {
+    rects: Vec<Rect>,
+    lines: Vec<Line>,
+    arrows: Vec<Arrow>,
+    circles: Vec<Circle>,
+    texts: Vec<Text>,
+}
+
+impl DisplayMeta {
+    pub fn builder() -> DisplayMetaBuilder {
+        DisplayMetaBuilder::default()
+    }
+
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn arrows(&self) -> &[Arrow] {
+        &self.arrows
+    }
+
+    pub fn circles(&self) -> &[Circle] {
+        &self.circles
+    }
+
+    pub fn texts(&self) -> &[Text] {
+        &self.texts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_display_metas() == 0
+    }
+
+    /// Number of `NvDsDisplayMeta` needed to hold the elements.
+    pub fn num_display_metas(&self) -> usize {
+        [
+            self.rects.len(),
+            self.lines.len(),
+            self.arrows.len(),
+            self.circles.len(),
+            self.texts.len(),
+        ]
+        .iter()
+        .map(|len| len.div_ceil(MAX_ELEMENTS_IN_DISPLAY_META))
+        .max()
+        .unwrap_or(0)
+    }
+
+    /// Acquire display metas from the pool of `batch_meta`, fill them with the
+    /// elements and add them to `frame_meta`.
+    #[doc(alias = "nvds_acquire_display_meta_from_pool")]
+    #[doc(alias = "nvds_add_display_meta_to_frame")]
+    pub fn attach(&self, batch_meta: &mut NvDsBatchMeta, frame_meta: &mut NvDsFrameMeta) {
+        fn chunk<T>(items: &[T], index: usize) -> &[T] {
+            items
+                .chunks(MAX_ELEMENTS_IN_DISPLAY_META)
+                .nth(index)
+                .unwrap_or(&[])
+        }
+
+        for i in 0..self.num_display_metas() {
+            let display_meta = batch_meta.acquire_display_meta();
+            for rect in chunk(&self.rects, i) {
+                display_meta.push_rect(rect);
+            }
+            for line in chunk(&self.lines, i) {
+                display_meta.push_line(line);
+            }
+            for arrow in chunk(&self.arrows, i) {
+                display_meta.push_arrow(arrow);
+            }
+            for circle in chunk(&self.circles, i) {
+                display_meta.push_circle(circle);
+            }
+            for text in chunk(&self.texts, i) {
+                display_meta.push_text(text);
+            }
+            frame_meta.add_display_meta(display_meta);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DisplayMetaBuilder {
+    meta: DisplayMeta,
+}
+
+impl DisplayMetaBuilder {
+    pub fn rect(mut self, rect: Rect) -> Self {
+        self.meta.rects.push(rect);
+        self
+    }
+
+    pub fn line(mut self, line: Line) -> Self {
+        self.meta.lines.push(line);
+        self
+    }
+
+    /// Closed polygon through `points`, drawn with lines.
+    pub fn polygon(mut self, points: &[(u32, u32)], width: u32, color: Color) -> Self {
+        if points.len() < 2 {
+            return self;
+        }
+        let next = points.iter().cycle().skip(1);
+        for (from, to) in points.iter().zip(next) {
+            self.meta
+                .lines
+                .push(Line::new(*from, *to).width(width).color(color));
+        }
+        self
+    }
+
+    pub fn arrow(mut self, arrow: Arrow) -> Self {
+        self.meta.arrows.push(arrow);
+        self
+    }
+
+    pub fn circle(mut self, circle: Circle) -> Self {
+        self.meta.circles.push(circle);
+        self
+    }
+
+    pub fn text(mut self, text: Text) -> Self {
+        self.meta.texts.push(text);
+        self
+    }
+
+    pub fn build(self) -> DisplayMeta {
+        self.meta
+    }
+}
+
+#[repr(transparent)]
+pub struct NvDsDisplayMeta(ffi::NvDsDisplayMeta);
+
+impl NvDsDisplayMeta {
+    /// # Safety
+    ///
+    /// `ptr` must be a valid display meta acquired from the batch pool, that
+    /// outlives `'a` and has no other live reference.
+    pub unsafe fn from_ptr<'a>(ptr: *mut ffi::NvDsDisplayMeta) -> &'a mut Self {
+        &mut *(ptr as *mut Self)
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut ffi::NvDsDisplayMeta {
+        self as *const Self as *mut ffi::NvDsDisplayMeta
+    }
+
+    pub fn num_rects(&self) -> u32 {
+        self.0.num_rects
+    }
+
+    pub fn num_lines(&self) -> u32 {
+        self.0.num_lines
+    }
+
+    pub fn num_arrows(&self) -> u32 {
+        self.0.num_arrows
+    }
+
+    pub fn num_circles(&self) -> u32 {
+        self.0.num_circles
+    }
+
+    pub fn num_labels(&self) -> u32 {
+        self.0.num_labels
+    }
+
+    /// Add `rect`, returns `false` if the display meta is full.
+    pub fn push_rect(&mut self, rect: &Rect) -> bool {
+        let index = self.0.num_rects as usize;
+        if index >= MAX_ELEMENTS_IN_DISPLAY_META {
+            return false;
+        }
+        self.0.rect_params[index] = NvOSD_RectParams {
+            left: rect.left,
+            top: rect.top,
+            width: rect.width,
+            height: rect.height,
+            border_width: rect.border_width,
+            border_color: rect.border_color.into(),
+            has_bg_color: rect.bg_color.is_some() as u32,
+            reserved: 0,
+            bg_color: rect.bg_color.unwrap_or(Color::BLACK).into(),
+            has_color_info: 0,
+            color_id: 0,
+        };
+        self.0.num_rects += 1;
+        true
+    }
+
+    /// Add `line`, returns `false` if the display meta is full.
+    pub fn push_line(&mut self, line: &Line) -> bool {
+        let index = self.0.num_lines as usize;
+        if index >= MAX_ELEMENTS_IN_DISPLAY_META {
+            return false;
+        }
+        self.0.line_params[index] = osd::NvOSD_LineParams {
+            x1: line.from.0,
+            y1: line.from.1,
+            x2: line.to.0,
+            y2: line.to.1,
+            line_width: line.width,
+            line_color: line.color.into(),
+        };
+        self.0.num_lines += 1;
+        true
+    }
+
+    /// Add `arrow`, returns `false` if the display meta is full.
+    pub fn push_arrow(&mut self, arrow: &Arrow) -> bool {
+        let index = self.0.num_arrows as usize;
+        if index >= MAX_ELEMENTS_IN_DISPLAY_META {
+            return false;
+        }
+        self.0.arrow_params[index] = osd::NvOSD_ArrowParams {
+            x1: arrow.from.0,
+            y1: arrow.from.1,
+            x2: arrow.to.0,
+            y2: arrow.to.1,
+            arrow_width: arrow.width,
+            arrow_head: match arrow.head {
+                ArrowHead::Start => osd::START_HEAD,
+                ArrowHead::End => osd::END_HEAD,
+                ArrowHead::Both => osd::BOTH_HEAD,
+            },
+            arrow_color: arrow.color.into(),
+            reserved: 0,
+        };
+        self.0.num_arrows += 1;
+        true
+    }
+
+    /// Add `circle`, returns `false` if the display meta is full.
+    pub fn push_circle(&mut self, circle: &Circle) -> bool {
+        let index = self.0.num_circles as usize;
+        if index >= MAX_ELEMENTS_IN_DISPLAY_META {
+            return false;
+        }
+        self.0.circle_params[index] = osd::NvOSD_CircleParams {
+            xc: circle.center.0,
+            yc: circle.center.1,
+            radius: circle.radius,
+            circle_color: circle.color.into(),
+            has_bg_color: circle.bg_color.is_some() as u32,
+            bg_color: circle.bg_color.unwrap_or(Color::BLACK).into(),
+            reserved: 0,
+        };
+        self.0.num_circles += 1;
+        true
+    }
+
+    /// Add `text`, returns `false` if the display meta is full.
+    ///
+    /// The text is copied with `g_strdup`, DeepStream frees it when the display
+    /// meta is released.
+    pub fn push_text(&mut self, text: &Text) -> bool {
+        let index = self.0.num_labels as usize;
+        if index >= MAX_ELEMENTS_IN_DISPLAY_META {
+            return false;
+        }
+        self.0.text_params[index] = text_params(text);
+        self.0.num_labels += 1;
+        true
+    }
+}
+
+impl std::fmt::Debug for NvDsDisplayMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NvDsDisplayMeta")
+            .field("num_rects", &self.num_rects())
+            .field("num_lines", &self.num_lines())
+            .field("num_arrows", &self.num_arrows())
+            .field("num_circles", &self.num_circles())
+            .field("num_labels", &self.num_labels())
+            .finish()
+    }
+}
+
+/// Text params of `text`, its `display_text` is a `g_strdup` copy.
+pub(crate) fn text_params(text: &Text) -> NvOSD_TextParams {
+    let display_text = CString::new(text.text.replace('\0', "")).unwrap();
+    NvOSD_TextParams {
+        display_text: unsafe { glib::ffi::g_strdup(display_text.as_ptr()) },
+        x_offset: text.x,
+        y_offset: text.y,
+        font_params: NvOSD_FontParams {
+            font_name: font_name(&text.font.name),
+            font_size: text.font.size as i32,
+            font_color: text.font.color.into(),
+        },
+        set_bg_clr: text.bg_color.is_some() as i32,
+        text_bg_clr: text.bg_color.unwrap_or(Color::BLACK).into(),
+    }
+}
+
+fn font_name(name: &str) -> *mut c_char {
+    let mut names = FONT_NAMES.lock().unwrap();
+    names
+        .entry(name.to_owned())
+        .or_insert_with(|| CString::new(name.replace('\0', "")).unwrap())
+        .as_ptr() as *mut c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_splits_in_display_metas() {
+        let mut builder = DisplayMeta::builder()
+            .polygon(&[(0, 0), (100, 0), (100, 100)], 3, Color::GREEN)
+            .text(Text::new("zone", 0, 0).background(Color::BLACK));
+        for i in 0..20 {
+            builder = builder.rect(Rect::new(i as f32, 0.0, 10.0, 10.0));
+        }
+        let meta = builder.build();
+
+        // closed polygon
+        assert_eq!(meta.lines().len(), 3);
+        assert_eq!(meta.lines()[2].from, (100, 100));
+        assert_eq!(meta.lines()[2].to, (0, 0));
+        // 20 rects need 2 display metas
+        assert_eq!(meta.num_display_metas(), 2);
+        assert!(DisplayMeta::default().is_empty());
+    }
+
+    #[test]
+    fn push_until_full() {
+        let mut raw: ffi::NvDsDisplayMeta = unsafe { std::mem::zeroed() };
+        let display_meta = unsafe { NvDsDisplayMeta::from_ptr(&mut raw) };
+
+        let circle = Circle::new((10, 10), 5).color(Color::RED);
+        for _ in 0..MAX_ELEMENTS_IN_DISPLAY_META {
+            assert!(display_meta.push_circle(&circle));
+        }
+        assert!(!display_meta.push_circle(&circle));
+        assert_eq!(display_meta.num_circles(), 16);
+        assert_eq!(raw.circle_params[15].radius, 5);
+        assert_eq!(raw.circle_params[15].circle_color.red, 1.0);
+    }
+}
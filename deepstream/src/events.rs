@@ -1,10 +1,17 @@
-use gstreamer::Event;
+use gstreamer::{Event, EventRef};
+use libc::c_uint;
+
+use gstreamer::ffi::GstEvent;
 
 /// Creates a "custom reset" event for the specified source.
 /// # Arguments
 ///
 /// * `source_id` - Source ID of the stream for which reset is to be sent; also the pad ID of the sinkpad of the Gst-nvstreammux plugin for which the source is configured.
 pub fn new_stream_reset(source_id: i32) -> Option<Event> {
+    // no Gst-nvstreammux to send it to without DeepStream
+    if !cfg!(feature = "nvds") {
+        return None;
+    }
     unsafe {
         let ptr = deepstream_sys::gst_nvevent_new_stream_reset(source_id);
         if ptr.is_null() {
@@ -14,3 +21,52 @@ pub fn new_stream_reset(source_id: i32) -> Option<Event> {
         }
     }
 }
+
+/// Event of a single source of a batch, sent downstream by Gst-nvstreammux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// The stream of the source ended.
+    #[doc(alias = "GST_NVEVENT_STREAM_EOS")]
+    Eos(u32),
+    /// The source was reset, e.g. after a reconnection.
+    #[doc(alias = "GST_NVEVENT_STREAM_RESET")]
+    Reset(u32),
+    /// The sinkpad of the source was released.
+    #[doc(alias = "GST_NVEVENT_PAD_DELETED")]
+    PadDeleted(u32),
+}
+
+impl StreamEvent {
+    /// The DeepStream event `event` is, `None` for the other events.
+    pub fn parse(event: &EventRef) -> Option<Self> {
+        type Parse = unsafe extern "C" fn(*mut GstEvent, *mut c_uint);
+        type New = fn(u32) -> StreamEvent;
+        let (parse, new): (Parse, New) = match unsafe { (*event.as_ptr()).type_ } {
+            deepstream_sys::GST_NVEVENT_STREAM_EOS => (
+                deepstream_sys::gst_nvevent_parse_stream_eos,
+                StreamEvent::Eos,
+            ),
+            deepstream_sys::GST_NVEVENT_STREAM_RESET => (
+                deepstream_sys::gst_nvevent_parse_stream_reset,
+                StreamEvent::Reset,
+            ),
+            deepstream_sys::GST_NVEVENT_PAD_DELETED => (
+                deepstream_sys::gst_nvevent_parse_pad_deleted,
+                StreamEvent::PadDeleted,
+            ),
+            _ => return None,
+        };
+        let mut source_id = 0;
+        unsafe { parse(event.as_mut_ptr(), &mut source_id) };
+        Some(new(source_id))
+    }
+
+    /// The source the event is about.
+    pub fn source_id(&self) -> u32 {
+        match *self {
+            StreamEvent::Eos(source_id)
+            | StreamEvent::Reset(source_id)
+            | StreamEvent::PadDeleted(source_id) => source_id,
+        }
+    }
+}
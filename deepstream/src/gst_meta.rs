@@ -1,7 +1,10 @@
 use glib::translate::{from_glib, FromGlib, IntoGlib};
-use gstreamer::meta::MetaAPI;
+use gstreamer::meta::{MetaAPI, MetaRefMut, Standalone};
+use gstreamer::BufferRef;
+use libc::c_void;
 
 use deepstream_sys::gst_nvdsmeta as ffi;
+use deepstream_sys::nvdsmeta;
 
 use crate::meta::NvDsBatchMeta;
 
@@ -59,13 +62,52 @@ impl FromGlib<ffi::GstNvDsMetaType> for GstNvDsMetaType {
 pub struct DsMeta(ffi::NvDsMeta);
 
 impl DsMeta {
+    /// Attach a new batch meta of at most `max_frames` frames to `buffer`, as
+    /// `nvstreammux` does.
+    #[doc(alias = "nvds_create_batch_meta")]
+    #[doc(alias = "gst_buffer_add_nvds_meta")]
+    pub fn add(buffer: &mut BufferRef, max_frames: u32) -> MetaRefMut<'_, Self, Standalone> {
+        unsafe {
+            let batch_meta = nvdsmeta::nvds_create_batch_meta(max_frames);
+            (*batch_meta).base_meta.batch_meta = batch_meta;
+            (*batch_meta).base_meta.copy_func = Some(nvdsmeta::nvds_batch_meta_copy_func);
+            (*batch_meta).base_meta.release_func = Some(nvdsmeta::nvds_batch_meta_release_func);
+            (*batch_meta).max_frames_in_batch = max_frames;
+
+            let meta = ffi::gst_buffer_add_nvds_meta(
+                buffer.as_mut_ptr(),
+                batch_meta as glib::ffi::gpointer,
+                std::ptr::null_mut(),
+                Some(nvdsmeta::nvds_batch_meta_copy_func),
+                Some(nvdsmeta::nvds_batch_meta_release_func),
+            );
+            (*meta).meta_type = ffi::NVDS_BATCH_GST_META;
+
+            Self::from_mut_ptr(buffer, meta)
+        }
+    }
+
     pub fn meta_type(&self) -> GstNvDsMetaType {
         unsafe { from_glib(self.0.meta_type) }
     }
 
     pub fn batch_meta(&mut self) -> Option<&mut NvDsBatchMeta> {
+        self.batch_meta_ptr()
+            .map(|ptr| unsafe { NvDsBatchMeta::from_ptr(ptr) })
+    }
+
+    /// The batch meta, read from a buffer that isn't writable.
+    pub fn batch_meta_ref(&self) -> Option<&NvDsBatchMeta> {
+        self.batch_meta_ptr()
+            .map(|ptr| unsafe { &*NvDsBatchMeta::from_ptr(ptr) })
+    }
+
+    /// Pointer to the batch meta, which isn't in the buffer memory: metas can
+    /// be added to it from a buffer that isn't writable, as DeepStream probes
+    /// do with `gst_buffer_get_nvds_batch_meta`.
+    pub fn batch_meta_ptr(&self) -> Option<*mut c_void> {
         if let GstNvDsMetaType::BatchGstMeta = self.meta_type() {
-            unsafe { Some(NvDsBatchMeta::from_ptr(self.0.meta_data)) }
+            Some(self.0.meta_data)
         } else {
             None
         }
@@ -91,3 +133,15 @@ impl std::fmt::Debug for DsMeta {
             .finish()
     }
 }
+
+/// Pointers to the batch metas DeepStream attached to `buffer`, none without
+/// the `nvds` feature since no DeepStream element ran on it.
+pub fn batch_meta_ptrs(buffer: &BufferRef) -> Vec<*mut c_void> {
+    if !cfg!(feature = "nvds") {
+        return Vec::new();
+    }
+    buffer
+        .iter_meta::<DsMeta>()
+        .filter_map(|meta| meta.batch_meta_ptr())
+        .collect()
+}
@@ -0,0 +1,47 @@
+use gstreamer::BufferRef;
+use std::time::Duration;
+
+use deepstream_sys::nvds_latency_meta as ffi;
+
+/// Latency of a frame since its decoder, from the `NVDS_LATENCY_MEASUREMENT_META`
+/// DeepStream attaches when `NVDS_ENABLE_LATENCY_MEASUREMENT=1` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameLatency {
+    pub source_id: u32,
+    pub frame_number: u32,
+    pub latency: Duration,
+}
+
+/// Whether DeepStream attaches the latency measurement meta, never without
+/// the `nvds` feature.
+#[doc(alias = "nvds_get_enable_latency_measurement")]
+pub fn latency_measurement_enabled() -> bool {
+    cfg!(feature = "nvds") && unsafe { ffi::nvds_get_enable_latency_measurement() != 0 }
+}
+
+/// Latency of the frames of a batch `buffer` holding at most `batch_size` frames.
+#[doc(alias = "nvds_measure_buffer_latency")]
+pub fn measure_buffer_latency(buffer: &BufferRef, batch_size: usize) -> Vec<FrameLatency> {
+    let mut infos: Vec<ffi::NvDsFrameLatencyInfo> = (0..batch_size)
+        .map(|_| ffi::NvDsFrameLatencyInfo {
+            source_id: 0,
+            frame_num: 0,
+            comp_in_timestamp: 0.0,
+            latency: 0.0,
+        })
+        .collect();
+    // the buffer is only read, its latency metas are in the batch meta
+    let num_frames =
+        unsafe { ffi::nvds_measure_buffer_latency(buffer.as_ptr() as *mut _, infos.as_mut_ptr()) };
+
+    infos
+        .iter()
+        .take(num_frames as usize)
+        .map(|info| FrameLatency {
+            source_id: info.source_id,
+            frame_number: info.frame_num,
+            // in milliseconds
+            latency: Duration::from_secs_f64(info.latency.max(0.0) / 1000.0),
+        })
+        .collect()
+}
@@ -1,7 +1,13 @@
+pub mod display_meta;
 pub mod events;
 pub mod gst_meta;
+pub mod latency;
 pub mod meta;
+pub mod meta_api;
 pub mod meta_schema;
+pub mod nvbufsurface;
+pub mod owned_meta;
+pub mod user_meta;
 
 #[cfg(test)]
 mod tests {
@@ -1,4 +1,3 @@
-use glib::ffi::gpointer;
 use libc::{c_char, c_void};
 use std::ffi::CStr;
 use std::marker::PhantomData;
@@ -6,7 +5,9 @@ use std::marker::PhantomData;
 use deepstream_sys::nvds_roi_meta::NvOSD_RectParams;
 use deepstream_sys::nvdsmeta as ffi;
 
-use crate::meta_schema::NvDsEventMsgMeta;
+use crate::display_meta::{self, Color, NvDsDisplayMeta, Text};
+use crate::meta_api::Classification;
+use crate::user_meta::{self, AnyUserMetaData, UserMetaData, UserMetaIter};
 
 #[repr(transparent)]
 pub struct NvDsObjectMeta(ffi::NvDsObjectMeta);
@@ -43,6 +44,130 @@ impl NvDsObjectMeta {
     pub fn confidence(&self) -> f32 {
         self.0.confidence
     }
+
+    pub fn set_class_id(&mut self, class_id: i32) {
+        self.0.class_id = class_id;
+    }
+
+    /// Set the label, truncated to `MAX_LABEL_SIZE - 1` bytes on a char
+    /// boundary.
+    pub fn set_obj_label(&mut self, label: &str) {
+        let mut len = label.len().min(self.0.obj_label.len() - 1);
+        while !label.is_char_boundary(len) {
+            len -= 1;
+        }
+        for (dst, src) in self.0.obj_label.iter_mut().zip(&label.as_bytes()[..len]) {
+            *dst = *src as c_char;
+        }
+        self.0.obj_label[len..].fill(0);
+    }
+
+    /// Set the tracker id, `None` for an untracked object.
+    pub fn set_object_id(&mut self, object_id: Option<u64>) {
+        self.0.object_id = object_id.unwrap_or(ffi::UNTRACKED_OBJECT_ID);
+    }
+
+    pub fn set_confidence(&mut self, confidence: f32) {
+        self.0.confidence = confidence;
+    }
+
+    /// `gie-unique-id` of the component that detected the object.
+    pub fn unique_component_id(&self) -> i32 {
+        self.0.unique_component_id
+    }
+
+    pub fn set_unique_component_id(&mut self, unique_component_id: i32) {
+        self.0.unique_component_id = unique_component_id;
+    }
+
+    pub fn rect_params_mut(&mut self) -> &mut NvOSD_RectParams {
+        &mut self.0.rect_params
+    }
+
+    /// Set the bounding box, in pixels of the muxer output.
+    pub fn set_rect(&mut self, left: f32, top: f32, width: f32, height: f32) {
+        let rect = &mut self.0.rect_params;
+        rect.left = left;
+        rect.top = top;
+        rect.width = width;
+        rect.height = height;
+    }
+
+    /// Color and width of the box the osd draws, a width of 0 hides it.
+    pub fn set_border(&mut self, width: u32, color: Color) {
+        self.0.rect_params.border_width = width;
+        self.0.rect_params.border_color = color.into();
+    }
+
+    pub fn set_background(&mut self, color: Option<Color>) {
+        self.0.rect_params.has_bg_color = color.is_some() as u32;
+        self.0.rect_params.bg_color = color.unwrap_or(Color::BLACK).into();
+    }
+
+    /// Text the osd draws over the box.
+    pub fn display_text(&self) -> Option<&str> {
+        if self.0.text_params.display_text.is_null() {
+            return None;
+        }
+        unsafe {
+            CStr::from_ptr(self.0.text_params.display_text)
+                .to_str()
+                .ok()
+        }
+    }
+
+    /// Replace the text the osd draws over the box, the previous one is freed.
+    pub fn set_text(&mut self, text: &Text) {
+        unsafe {
+            glib::ffi::g_free(self.0.text_params.display_text as glib::ffi::gpointer);
+        }
+        self.0.text_params = display_meta::text_params(text);
+    }
+
+    /// Fields free for the application, DeepStream doesn't read them.
+    pub fn misc_obj_info(&self) -> &[i64] {
+        &self.0.misc_obj_info
+    }
+
+    pub fn misc_obj_info_mut(&mut self) -> &mut [i64] {
+        &mut self.0.misc_obj_info
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut ffi::NvDsObjectMeta {
+        self as *const Self as *mut ffi::NvDsObjectMeta
+    }
+
+    #[doc(alias = "nvds_add_user_meta_to_obj")]
+    pub fn add_user_meta<T>(&mut self, user_meta: &NvDsUserMeta<T>) {
+        unsafe {
+            ffi::nvds_add_user_meta_to_obj(self.as_mut_ptr(), user_meta.as_mut_ptr());
+        }
+    }
+
+    /// Data of the object user metas holding a `T`.
+    pub fn iter_user_meta<'a, T: UserMetaData>(&self) -> UserMetaIter<'a, T> {
+        UserMetaIter::new(self.0.obj_user_meta_list)
+    }
+
+    pub fn iter_classifiers<'a>(&self) -> NvDsClassifierMetaIter<'a> {
+        NvDsClassifierMetaIter::new(self.0.classifier_meta_list)
+    }
+
+    /// Labels of the classifiers that ran on the object.
+    pub fn classifications(&self) -> Vec<Classification> {
+        self.iter_classifiers()
+            .flat_map(|classifier| {
+                classifier.iter_labels().map(move |label| Classification {
+                    component_id: classifier.unique_component_id(),
+                    classifier_type: classifier.classifier_type().map(str::to_owned),
+                    class_id: label.result_class_id(),
+                    label_id: label.label_id(),
+                    label: label.result_label().to_owned(),
+                    confidence: label.result_prob(),
+                })
+            })
+            .collect()
+    }
 }
 
 impl std::fmt::Debug for NvDsObjectMeta {
@@ -88,6 +213,167 @@ impl<'a> Iterator for NvDsObjectMetaIter<'a> {
     }
 }
 
+/// Output of a classifier for an object, one label per attribute it infers.
+#[repr(transparent)]
+pub struct NvDsClassifierMeta(ffi::NvDsClassifierMeta);
+
+impl NvDsClassifierMeta {
+    /// # Safety
+    ///
+    /// `ptr` must be a valid classifier meta of an object, that outlives `'a` and has no
+    /// other live reference.
+    pub unsafe fn from_ptr<'a>(ptr: *mut ffi::NvDsClassifierMeta) -> &'a mut Self {
+        &mut *(ptr as *mut Self)
+    }
+
+    pub fn num_labels(&self) -> u32 {
+        self.0.num_labels
+    }
+
+    /// `gie-unique-id` of the classifier.
+    pub fn unique_component_id(&self) -> i32 {
+        self.0.unique_component_id
+    }
+
+    /// `classifier-type` of the classifier config, e.g. `vehiclecolor`.
+    pub fn classifier_type(&self) -> Option<&str> {
+        if self.0.classifier_type.is_null() {
+            return None;
+        }
+        unsafe { CStr::from_ptr(self.0.classifier_type).to_str().ok() }
+    }
+
+    pub fn iter_labels<'a>(&self) -> NvDsLabelInfoIter<'a> {
+        NvDsLabelInfoIter::new(self.0.label_info_list)
+    }
+}
+
+impl std::fmt::Debug for NvDsClassifierMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NvDsClassifierMeta")
+            .field("unique_component_id", &self.unique_component_id())
+            .field("classifier_type", &self.classifier_type())
+            .field("labels", &self.iter_labels().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+pub struct NvDsClassifierMetaIter<'a> {
+    ptr: Option<std::ptr::NonNull<ffi::NvDsClassifierMetaList>>,
+    phantom: PhantomData<&'a NvDsClassifierMeta>,
+}
+
+impl<'a> NvDsClassifierMetaIter<'a> {
+    pub fn new(list: *mut ffi::NvDsClassifierMetaList) -> Self {
+        let ptr = std::ptr::NonNull::new(list);
+        Self {
+            ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for NvDsClassifierMetaIter<'a> {
+    type Item = &'a NvDsClassifierMeta;
+
+    fn next(&mut self) -> Option<&'a NvDsClassifierMeta> {
+        match self.ptr {
+            None => None,
+            Some(cur) => unsafe {
+                self.ptr = std::ptr::NonNull::new(cur.as_ref().next);
+
+                let item = &*(cur.as_ref().data as *const NvDsClassifierMeta);
+
+                Some(item)
+            },
+        }
+    }
+}
+
+/// A label of a [`NvDsClassifierMeta`].
+#[repr(transparent)]
+pub struct NvDsLabelInfo(ffi::NvDsLabelInfo);
+
+impl NvDsLabelInfo {
+    /// # Safety
+    ///
+    /// `ptr` must be a valid label info of a classifier meta, that outlives `'a` and has no
+    /// other live reference.
+    pub unsafe fn from_ptr<'a>(ptr: *mut ffi::NvDsLabelInfo) -> &'a mut Self {
+        &mut *(ptr as *mut Self)
+    }
+
+    pub fn num_classes(&self) -> u32 {
+        self.0.num_classes
+    }
+
+    pub fn result_label(&self) -> &str {
+        let ptr = if self.0.p_result_label.is_null() {
+            &self.0.result_label as *const c_char
+        } else {
+            self.0.p_result_label as *const c_char
+        };
+        unsafe { CStr::from_ptr(ptr).to_str().unwrap_or_default() }
+    }
+
+    pub fn result_class_id(&self) -> u32 {
+        self.0.result_class_id
+    }
+
+    /// Index of the attribute the label is for, among the outputs of the
+    /// classifier.
+    pub fn label_id(&self) -> u32 {
+        self.0.label_id
+    }
+
+    pub fn result_prob(&self) -> f32 {
+        self.0.result_prob
+    }
+}
+
+impl std::fmt::Debug for NvDsLabelInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NvDsLabelInfo")
+            .field("label_id", &self.label_id())
+            .field("result_class_id", &self.result_class_id())
+            .field("result_label", &self.result_label())
+            .field("result_prob", &self.result_prob())
+            .finish()
+    }
+}
+
+pub struct NvDsLabelInfoIter<'a> {
+    ptr: Option<std::ptr::NonNull<ffi::NvDsLabelInfoList>>,
+    phantom: PhantomData<&'a NvDsLabelInfo>,
+}
+
+impl<'a> NvDsLabelInfoIter<'a> {
+    pub fn new(list: *mut ffi::NvDsLabelInfoList) -> Self {
+        let ptr = std::ptr::NonNull::new(list);
+        Self {
+            ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for NvDsLabelInfoIter<'a> {
+    type Item = &'a NvDsLabelInfo;
+
+    fn next(&mut self) -> Option<&'a NvDsLabelInfo> {
+        match self.ptr {
+            None => None,
+            Some(cur) => unsafe {
+                self.ptr = std::ptr::NonNull::new(cur.as_ref().next);
+
+                let item = &*(cur.as_ref().data as *const NvDsLabelInfo);
+
+                Some(item)
+            },
+        }
+    }
+}
+
 #[repr(transparent)]
 pub struct NvDsFrameMeta(ffi::NvDsFrameMeta);
 
@@ -108,10 +394,60 @@ impl NvDsFrameMeta {
         self.0.source_id
     }
 
+    /// Index of the frame in the batch, and in its surface.
+    pub fn batch_id(&self) -> u32 {
+        self.0.batch_id
+    }
+
+    pub fn buf_pts(&self) -> u64 {
+        self.0.buf_pts
+    }
+
     pub fn iter_objects<'a>(&mut self) -> NvDsObjectMetaIter<'a> {
         NvDsObjectMetaIter::new(self.0.obj_meta_list)
     }
 
+    pub(crate) fn obj_meta_list(&self) -> *mut ffi::NvDsObjectMetaList {
+        self.0.obj_meta_list
+    }
+
+    pub fn num_obj_meta(&self) -> u32 {
+        self.0.num_obj_meta
+    }
+
+    /// Add an object acquired with [`NvDsBatchMeta::acquire_obj_meta`], as a
+    /// child of `parent` if any.
+    #[doc(alias = "nvds_add_obj_meta_to_frame")]
+    pub fn add_obj_meta(&mut self, obj_meta: &mut NvDsObjectMeta, parent: Option<&NvDsObjectMeta>) {
+        let parent = parent.map_or(std::ptr::null_mut(), |parent| parent.as_mut_ptr());
+        unsafe {
+            ffi::nvds_add_obj_meta_to_frame(self.as_mut_ptr(), obj_meta.as_mut_ptr(), parent);
+        }
+    }
+
+    /// Remove `obj_meta` from the frame, it goes back to the batch pool.
+    ///
+    /// # Safety
+    ///
+    /// `obj_meta` must be an object of the frame, and no reference to it can
+    /// be used once it's removed.
+    #[doc(alias = "nvds_remove_obj_meta_from_frame")]
+    pub unsafe fn remove_obj_meta(&mut self, obj_meta: *mut ffi::NvDsObjectMeta) {
+        ffi::nvds_remove_obj_meta_from_frame(self.as_mut_ptr(), obj_meta);
+    }
+
+    /// Remove the objects `f` returns `false` for.
+    pub fn retain_objects<F: FnMut(&mut NvDsObjectMeta) -> bool>(&mut self, mut f: F) {
+        // the list can't change while it's walked
+        let removed: Vec<*mut ffi::NvDsObjectMeta> = self
+            .iter_objects()
+            .filter_map(|object| (!f(object)).then(|| object.as_mut_ptr()))
+            .collect();
+        for object in removed {
+            unsafe { self.remove_obj_meta(object) };
+        }
+    }
+
     #[doc(alias = "nvds_add_user_meta_to_frame")]
     pub fn add_user_meta<T>(&mut self, user_meta: &NvDsUserMeta<T>) {
         unsafe {
@@ -120,6 +456,28 @@ impl NvDsFrameMeta {
             ffi::nvds_add_user_meta_to_frame(frame_ptr, user_meta_ptr);
         }
     }
+
+    /// Data of the frame user metas holding a `T`.
+    pub fn iter_user_meta<'a, T: UserMetaData>(&self) -> UserMetaIter<'a, T> {
+        UserMetaIter::new(self.0.frame_user_meta_list)
+    }
+
+    /// Add `data` in a user meta acquired from the pool of the batch of the
+    /// frame.
+    pub fn add_user_data(&mut self, data: Box<dyn AnyUserMetaData>) {
+        unsafe {
+            let user_meta = ffi::nvds_acquire_user_meta_from_pool(self.0.base_meta.batch_meta);
+            data.set_in(&mut *user_meta);
+            ffi::nvds_add_user_meta_to_frame(self.as_mut_ptr(), user_meta);
+        }
+    }
+
+    #[doc(alias = "nvds_add_display_meta_to_frame")]
+    pub fn add_display_meta(&mut self, display_meta: &NvDsDisplayMeta) {
+        unsafe {
+            ffi::nvds_add_display_meta_to_frame(self.as_mut_ptr(), display_meta.as_mut_ptr());
+        }
+    }
 }
 
 impl std::fmt::Debug for NvDsFrameMeta {
@@ -196,28 +554,15 @@ impl<T> NvDsUserMeta<T> {
     }
 }
 
-unsafe extern "C" fn msg_copy_func(data: gpointer, _user_data: gpointer) -> gpointer {
-    println!("--- copy event msg ---");
-
-    let user_meta = data as *mut ffi::NvDsUserMeta;
-    let src_meta = (*user_meta).user_meta_data as *const NvDsEventMsgMeta as *mut NvDsEventMsgMeta;
-    let dst_meta = std::mem::ManuallyDrop::new(src_meta.clone());
-    Box::into_raw(Box::new(dst_meta)) as gpointer
-}
-
-unsafe extern "C" fn msg_release_func(data: gpointer, _user_data: gpointer) {
-    let user_meta = data as *mut ffi::NvDsUserMeta;
-    let src_meta = (*user_meta).user_meta_data as *mut NvDsEventMsgMeta;
-    src_meta.drop_in_place();
-}
+impl<T: UserMetaData> NvDsUserMeta<T> {
+    /// Box `data` in the user meta, it's cloned and dropped with the meta.
+    pub fn set_data(&mut self, data: T) {
+        user_meta::set_data(&mut self.0, data);
+    }
 
-impl NvDsUserMeta<NvDsEventMsgMeta> {
-    pub fn set_data(&mut self, data: NvDsEventMsgMeta) {
-        let user_meta_data = std::mem::ManuallyDrop::new(data);
-        self.0.user_meta_data = Box::into_raw(Box::new(user_meta_data)) as gpointer;
-        self.0.base_meta.meta_type = ffi::NVDS_EVENT_MSG_META;
-        self.0.base_meta.copy_func = Some(msg_copy_func);
-        self.0.base_meta.release_func = Some(msg_release_func);
+    /// The data, `None` if the meta holds an other type.
+    pub fn data(&self) -> Option<&T> {
+        user_meta::data(&self.0)
     }
 }
 
@@ -252,6 +597,25 @@ impl NvDsBatchMeta {
         NvDsFrameMetaIter::new(self.0.frame_meta_list)
     }
 
+    pub(crate) fn frame_meta_list(&self) -> *mut ffi::NvDsFrameMetaList {
+        self.0.frame_meta_list
+    }
+
+    /// Add a frame of `source_id` from the batch pool, after the frames of
+    /// the batch.
+    #[doc(alias = "nvds_acquire_frame_meta_from_pool")]
+    #[doc(alias = "nvds_add_frame_meta_to_batch")]
+    pub fn add_frame_meta(&mut self, source_id: u32) -> &mut NvDsFrameMeta {
+        unsafe {
+            let frame_meta = ffi::nvds_acquire_frame_meta_from_pool(self.as_mut_ptr());
+            (*frame_meta).source_id = source_id;
+            (*frame_meta).pad_index = source_id;
+            (*frame_meta).batch_id = self.0.num_frames_in_batch;
+            ffi::nvds_add_frame_meta_to_batch(self.as_mut_ptr(), frame_meta);
+            NvDsFrameMeta::from_ptr(frame_meta)
+        }
+    }
+
     #[doc(alias = "nvds_acquire_user_meta_from_pool")]
     pub fn acquire_user_meta<T>(&mut self) -> &mut NvDsUserMeta<T> {
         unsafe {
@@ -261,6 +625,38 @@ impl NvDsBatchMeta {
             &mut *user_meta_ptr
         }
     }
+
+    /// Object from the batch pool, untracked, to fill then add to a frame with
+    /// [`NvDsFrameMeta::add_obj_meta`].
+    #[doc(alias = "nvds_acquire_obj_meta_from_pool")]
+    pub fn acquire_obj_meta(&mut self) -> &mut NvDsObjectMeta {
+        unsafe {
+            let obj_meta =
+                NvDsObjectMeta::from_ptr(ffi::nvds_acquire_obj_meta_from_pool(self.as_mut_ptr()));
+            obj_meta.set_object_id(None);
+            obj_meta
+        }
+    }
+
+    #[doc(alias = "nvds_add_user_meta_to_batch")]
+    pub fn add_user_meta<T>(&mut self, user_meta: &NvDsUserMeta<T>) {
+        unsafe {
+            ffi::nvds_add_user_meta_to_batch(self.as_mut_ptr(), user_meta.as_mut_ptr());
+        }
+    }
+
+    /// Data of the batch user metas holding a `T`.
+    pub fn iter_user_meta<'a, T: UserMetaData>(&self) -> UserMetaIter<'a, T> {
+        UserMetaIter::new(self.0.batch_user_meta_list)
+    }
+
+    #[doc(alias = "nvds_acquire_display_meta_from_pool")]
+    pub fn acquire_display_meta(&mut self) -> &mut NvDsDisplayMeta {
+        unsafe {
+            let ptr = ffi::nvds_acquire_display_meta_from_pool(self.as_mut_ptr());
+            NvDsDisplayMeta::from_ptr(ptr)
+        }
+    }
 }
 
 impl std::fmt::Debug for NvDsBatchMeta {
@@ -270,3 +666,95 @@ impl std::fmt::Debug for NvDsBatchMeta {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glib::ffi::{gpointer, GList};
+
+    fn node(data: gpointer) -> GList {
+        GList {
+            data,
+            next: std::ptr::null_mut(),
+            prev: std::ptr::null_mut(),
+        }
+    }
+
+    fn label_info(label: &str, class_id: u32, label_id: u32) -> ffi::NvDsLabelInfo {
+        let mut info: ffi::NvDsLabelInfo = unsafe { std::mem::zeroed() };
+        for (dst, src) in info.result_label.iter_mut().zip(label.bytes()) {
+            *dst = src as c_char;
+        }
+        info.result_class_id = class_id;
+        info.label_id = label_id;
+        info.result_prob = 0.5;
+        info
+    }
+
+    #[test]
+    fn object_classifiers() {
+        let mut color = label_info("red", 3, 0);
+        let mut make = label_info("ford", 1, 1);
+        let mut make_node = node(&mut make as *mut _ as gpointer);
+        let mut color_node = node(&mut color as *mut _ as gpointer);
+        color_node.next = &mut make_node;
+
+        let mut classifier: ffi::NvDsClassifierMeta = unsafe { std::mem::zeroed() };
+        classifier.unique_component_id = 2;
+        classifier.num_labels = 2;
+        classifier.classifier_type = c"vehicle".as_ptr();
+        classifier.label_info_list = &mut color_node;
+        let mut classifier_node = node(&mut classifier as *mut _ as gpointer);
+
+        let mut object: ffi::NvDsObjectMeta = unsafe { std::mem::zeroed() };
+        object.classifier_meta_list = &mut classifier_node;
+        let object = unsafe { NvDsObjectMeta::from_ptr(&mut object) };
+
+        let classifiers: Vec<&NvDsClassifierMeta> = object.iter_classifiers().collect();
+        assert_eq!(classifiers.len(), 1);
+        assert_eq!(classifiers[0].unique_component_id(), 2);
+        assert_eq!(classifiers[0].classifier_type(), Some("vehicle"));
+
+        let labels: Vec<(u32, &str)> = classifiers[0]
+            .iter_labels()
+            .map(|label| (label.label_id(), label.result_label()))
+            .collect();
+        assert_eq!(labels, vec![(0, "red"), (1, "ford")]);
+
+        let classifications = object.classifications();
+        assert_eq!(classifications[1].label, "ford");
+        assert_eq!(classifications[1].class_id, 1);
+        assert_eq!(classifications[1].component_id, 2);
+    }
+
+    #[test]
+    fn edit_object() {
+        let mut object: ffi::NvDsObjectMeta = unsafe { std::mem::zeroed() };
+        let object = unsafe { NvDsObjectMeta::from_ptr(&mut object) };
+
+        object.set_obj_label("person");
+        assert_eq!(object.obj_label(), "person");
+        object.set_obj_label("car");
+        assert_eq!(object.obj_label(), "car");
+        object.set_obj_label(&"x".repeat(200));
+        assert_eq!(object.obj_label().len(), 127);
+        // 2 bytes chars, the 64th doesn't fit
+        object.set_obj_label(&"é".repeat(100));
+        assert_eq!(object.obj_label(), "é".repeat(63));
+
+        object.set_object_id(None);
+        assert_eq!(object.object_id(), None);
+        object.set_object_id(Some(42));
+        assert_eq!(object.object_id(), Some(42));
+
+        object.set_rect(10.0, 20.0, 30.0, 40.0);
+        object.set_border(2, Color::RED);
+        assert_eq!(object.rect_params().width, 30.0);
+        assert_eq!(object.rect_params().border_width, 2);
+        assert_eq!(object.rect_params().border_color.red, 1.0);
+
+        object.misc_obj_info_mut()[1] = 7;
+        assert_eq!(object.misc_obj_info(), &[0, 7, 0, 0]);
+        assert_eq!(object.display_text(), None);
+    }
+}
@@ -0,0 +1,175 @@
+use gstreamer::BufferRef;
+
+use crate::gst_meta::batch_meta_ptrs;
+use crate::meta::{
+    NvDsBatchMeta, NvDsFrameMeta, NvDsFrameMetaIter, NvDsObjectMeta, NvDsObjectMetaIter,
+};
+use crate::owned_meta::BatchGstMeta;
+use crate::user_meta::AnyUserMetaData;
+
+/// Bounding box of an object, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BBox {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Label given to an object by a secondary classifier.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Classification {
+    /// Unique id of the classifier, its `gie-unique-id`.
+    pub component_id: i32,
+    /// What the classifier infers, e.g. `vehiclecolor`.
+    pub classifier_type: Option<String>,
+    pub class_id: u32,
+    /// Index of the attribute among the outputs of the classifier.
+    pub label_id: u32,
+    pub label: String,
+    pub confidence: f32,
+}
+
+/// Read access to an object metadata, implemented by [`NvDsObjectMeta`] and
+/// [`ObjectMeta`](crate::owned_meta::ObjectMeta).
+pub trait ObjectMetaApi {
+    fn class_id(&self) -> i32;
+    fn obj_label(&self) -> &str;
+    /// Tracking id, `None` if the object is untracked.
+    fn object_id(&self) -> Option<u64>;
+    fn bbox(&self) -> BBox;
+    fn confidence(&self) -> f32;
+    /// Labels of the classifiers that ran on the object.
+    fn classifications(&self) -> Vec<Classification>;
+}
+
+/// Read access to a frame metadata, implemented by [`NvDsFrameMeta`] and
+/// [`FrameMeta`](crate::owned_meta::FrameMeta).
+pub trait FrameMetaApi {
+    fn frame_number(&self) -> i32;
+    fn source_id(&self) -> u32;
+    /// Index of the frame in the batch, and in its surface.
+    fn batch_id(&self) -> u32;
+    /// Timestamp of the source buffer the frame comes from, in nanoseconds.
+    fn buf_pts(&self) -> u64;
+    fn objects(&self) -> Box<dyn Iterator<Item = &dyn ObjectMetaApi> + '_>;
+}
+
+/// Read access to a batch metadata, implemented by [`NvDsBatchMeta`] and
+/// [`BatchMeta`](crate::owned_meta::BatchMeta).
+pub trait BatchMetaApi {
+    fn frames(&self) -> Box<dyn Iterator<Item = &dyn FrameMetaApi> + '_>;
+}
+
+/// Write access to a frame metadata, implemented by [`NvDsFrameMeta`] and
+/// [`FrameMeta`](crate::owned_meta::FrameMeta).
+pub trait FrameMetaApiMut: FrameMetaApi {
+    /// Remove the objects `keep` returns `false` for.
+    fn retain_objects(&mut self, keep: &mut dyn FnMut(&dyn ObjectMetaApi) -> bool);
+    /// Attach `data` to the frame as user meta.
+    fn add_user_data(&mut self, data: Box<dyn AnyUserMetaData>);
+}
+
+/// Write access to a batch metadata, implemented by [`NvDsBatchMeta`] and
+/// [`BatchMeta`](crate::owned_meta::BatchMeta).
+pub trait BatchMetaApiMut: BatchMetaApi {
+    fn frames_mut(&mut self) -> Box<dyn Iterator<Item = &mut dyn FrameMetaApiMut> + '_>;
+}
+
+impl ObjectMetaApi for NvDsObjectMeta {
+    fn class_id(&self) -> i32 {
+        NvDsObjectMeta::class_id(self)
+    }
+
+    fn obj_label(&self) -> &str {
+        NvDsObjectMeta::obj_label(self)
+    }
+
+    fn object_id(&self) -> Option<u64> {
+        NvDsObjectMeta::object_id(self)
+    }
+
+    fn bbox(&self) -> BBox {
+        let rect = self.rect_params();
+        BBox {
+            left: rect.left,
+            top: rect.top,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+
+    fn confidence(&self) -> f32 {
+        NvDsObjectMeta::confidence(self)
+    }
+
+    fn classifications(&self) -> Vec<Classification> {
+        NvDsObjectMeta::classifications(self)
+    }
+}
+
+impl FrameMetaApi for NvDsFrameMeta {
+    fn frame_number(&self) -> i32 {
+        NvDsFrameMeta::frame_number(self)
+    }
+
+    fn source_id(&self) -> u32 {
+        NvDsFrameMeta::source_id(self)
+    }
+
+    fn batch_id(&self) -> u32 {
+        NvDsFrameMeta::batch_id(self)
+    }
+
+    fn buf_pts(&self) -> u64 {
+        NvDsFrameMeta::buf_pts(self)
+    }
+
+    fn objects(&self) -> Box<dyn Iterator<Item = &dyn ObjectMetaApi> + '_> {
+        let iter = NvDsObjectMetaIter::new(self.obj_meta_list());
+        Box::new(iter.map(|obj| &*obj as &dyn ObjectMetaApi))
+    }
+}
+
+impl BatchMetaApi for NvDsBatchMeta {
+    fn frames(&self) -> Box<dyn Iterator<Item = &dyn FrameMetaApi> + '_> {
+        let iter = NvDsFrameMetaIter::new(self.frame_meta_list());
+        Box::new(iter.map(|frame| &*frame as &dyn FrameMetaApi))
+    }
+}
+
+impl FrameMetaApiMut for NvDsFrameMeta {
+    fn retain_objects(&mut self, keep: &mut dyn FnMut(&dyn ObjectMetaApi) -> bool) {
+        NvDsFrameMeta::retain_objects(self, |obj| keep(obj))
+    }
+
+    fn add_user_data(&mut self, data: Box<dyn AnyUserMetaData>) {
+        NvDsFrameMeta::add_user_data(self, data)
+    }
+}
+
+impl BatchMetaApiMut for NvDsBatchMeta {
+    fn frames_mut(&mut self) -> Box<dyn Iterator<Item = &mut dyn FrameMetaApiMut> + '_> {
+        Box::new(
+            self.iter_frame()
+                .map(|frame| frame as &mut dyn FrameMetaApiMut),
+        )
+    }
+}
+
+/// Call `f` with every batch metadata of `buffer`, the ones attached by
+/// DeepStream and the ones attached as [`BatchGstMeta`].
+///
+/// The metas are only read, the buffer doesn't have to be writable.
+pub fn for_each_batch_meta<F>(buffer: &BufferRef, mut f: F)
+where
+    F: FnMut(&dyn BatchMetaApi),
+{
+    for ptr in batch_meta_ptrs(buffer) {
+        f(unsafe { &*NvDsBatchMeta::from_ptr(ptr) });
+    }
+
+    for meta in buffer.iter_meta::<BatchGstMeta>() {
+        f(meta.batch());
+    }
+}
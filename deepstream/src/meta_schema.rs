@@ -2,6 +2,14 @@ use std::ffi::{CStr, CString};
 
 use deepstream_sys::nvdsmeta_schema as ffi;
 
+pub use ffi::{
+    NvDsEventType, NVDS_EVENT_CUSTOM, NVDS_EVENT_EMPTY, NVDS_EVENT_ENTRY, NVDS_EVENT_EXIT,
+    NVDS_EVENT_MOVING, NVDS_EVENT_PARKED, NVDS_EVENT_RESET, NVDS_EVENT_STOPPED,
+};
+
+use crate::meta_api::Classification;
+use crate::user_meta::UserMetaData;
+
 #[repr(transparent)]
 pub struct NvDsRect(ffi::NvDsRect);
 
@@ -93,6 +101,11 @@ impl NvDsEventMsgMeta {
             confidence,
             tracking_id,
             ts: ts_c_chars.into_raw(),
+            attributes: std::ptr::null_mut(),
+            num_attributes: 0,
+            event_type: ffi::NVDS_EVENT_MOVING,
+            zone: std::ptr::null_mut(),
+            dwell_time: 0.0,
         })
     }
 
@@ -111,6 +124,11 @@ impl NvDsEventMsgMeta {
         self.0.sensor_id
     }
 
+    /// Tracking id of the object, -1 if it is untracked.
+    pub fn tracking_id(&self) -> i32 {
+        self.0.tracking_id
+    }
+
     pub fn obj_class_label<'a>(&self) -> &'a str {
         unsafe {
             CStr::from_ptr::<'a>(self.0.obj_class_label)
@@ -122,11 +140,135 @@ impl NvDsEventMsgMeta {
     pub fn ts<'a>(&self) -> &'a str {
         unsafe { CStr::from_ptr::<'a>(self.0.ts).to_str().unwrap() }
     }
+
+    /// Type of the event, `NVDS_EVENT_MOVING` unless set.
+    pub fn event_type(&self) -> NvDsEventType {
+        self.0.event_type
+    }
+
+    pub fn set_event_type(&mut self, event_type: NvDsEventType) {
+        self.0.event_type = event_type;
+    }
+
+    /// Name of the zone of the event.
+    pub fn zone<'a>(&self) -> Option<&'a str> {
+        if self.0.zone.is_null() {
+            return None;
+        }
+        unsafe { Some(CStr::from_ptr::<'a>(self.0.zone).to_str().unwrap()) }
+    }
+
+    pub fn set_zone(&mut self, zone: Option<&str>) {
+        self.free_zone();
+        if let Some(zone) = zone {
+            self.0.zone = CString::new(zone).unwrap().into_raw();
+        }
+    }
+
+    /// Time the object stayed in the zone, in seconds.
+    pub fn dwell_time(&self) -> f64 {
+        self.0.dwell_time
+    }
+
+    pub fn set_dwell_time(&mut self, dwell_time: f64) {
+        self.0.dwell_time = dwell_time;
+    }
+
+    fn free_zone(&mut self) {
+        if !self.0.zone.is_null() {
+            unsafe { drop(CString::from_raw(self.0.zone)) };
+            self.0.zone = std::ptr::null_mut();
+        }
+    }
+
+    /// Labels given to the object by the classifiers.
+    pub fn attributes(&self) -> Vec<Classification> {
+        if self.0.attributes.is_null() {
+            return Vec::new();
+        }
+        let attributes = unsafe {
+            std::slice::from_raw_parts(self.0.attributes, self.0.num_attributes as usize)
+        };
+        attributes
+            .iter()
+            .map(|attribute| Classification {
+                component_id: attribute.classifier_id,
+                classifier_type: (!attribute.classifier_type.is_null()).then(|| {
+                    unsafe { CStr::from_ptr(attribute.classifier_type) }
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                class_id: attribute.class_id as u32,
+                label_id: attribute.label_id as u32,
+                label: unsafe { CStr::from_ptr(attribute.label) }
+                    .to_string_lossy()
+                    .into_owned(),
+                confidence: attribute.confidence as f32,
+            })
+            .collect()
+    }
+
+    pub fn set_attributes(&mut self, attributes: &[Classification]) {
+        self.free_attributes();
+        if attributes.is_empty() {
+            return;
+        }
+
+        let attributes: Box<[ffi::NvDsObjectAttribute]> = attributes
+            .iter()
+            .map(|attribute| ffi::NvDsObjectAttribute {
+                classifier_id: attribute.component_id,
+                classifier_type: match &attribute.classifier_type {
+                    Some(classifier_type) => {
+                        CString::new(classifier_type.as_str()).unwrap().into_raw()
+                    }
+                    None => std::ptr::null_mut(),
+                },
+                class_id: attribute.class_id as i32,
+                label_id: attribute.label_id as i32,
+                label: CString::new(attribute.label.as_str()).unwrap().into_raw(),
+                confidence: f64::from(attribute.confidence),
+            })
+            .collect();
+        self.0.num_attributes = attributes.len() as u32;
+        self.0.attributes = Box::into_raw(attributes) as *mut ffi::NvDsObjectAttribute;
+    }
+
+    fn free_attributes(&mut self) {
+        if self.0.attributes.is_null() {
+            return;
+        }
+        unsafe {
+            let attributes = Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                self.0.attributes,
+                self.0.num_attributes as usize,
+            ));
+            for attribute in attributes.iter() {
+                drop(CString::from_raw(attribute.label));
+                if !attribute.classifier_type.is_null() {
+                    drop(CString::from_raw(attribute.classifier_type));
+                }
+            }
+        }
+        self.0.attributes = std::ptr::null_mut();
+        self.0.num_attributes = 0;
+    }
+}
+
+// owns its strings and attributes
+unsafe impl Send for NvDsEventMsgMeta {}
+
+impl UserMetaData for NvDsEventMsgMeta {
+    const DESCRIPTOR: &'static str = "NVIDIA.EVENT_MSG_META";
+
+    fn meta_type() -> deepstream_sys::nvdsmeta::NvDsMetaType {
+        deepstream_sys::nvdsmeta::NVDS_EVENT_MSG_META
+    }
 }
 
 impl Clone for NvDsEventMsgMeta {
     fn clone(&self) -> Self {
-        Self::new(
+        let mut meta = Self::new(
             self.bbox().clone(),
             self.0.obj_class_id,
             &self.obj_class_label().to_owned(),
@@ -135,12 +277,19 @@ impl Clone for NvDsEventMsgMeta {
             self.0.confidence,
             self.0.tracking_id,
             &self.ts().to_owned(),
-        )
+        );
+        meta.set_attributes(&self.attributes());
+        meta.set_event_type(self.event_type());
+        meta.set_zone(self.zone());
+        meta.set_dwell_time(self.dwell_time());
+        meta
     }
 }
 
 impl Drop for NvDsEventMsgMeta {
     fn drop(&mut self) {
+        self.free_attributes();
+        self.free_zone();
         unsafe {
             drop(CString::from_raw(self.0.obj_class_label));
             drop(CString::from_raw(self.0.ts));
@@ -157,6 +306,70 @@ impl std::fmt::Debug for NvDsEventMsgMeta {
             .field("sensor_id", &self.sensor_id())
             .field("frame_id", &self.0.frame_id)
             .field("ts", &self.ts())
+            .field("attributes", &self.attributes())
+            .field("event_type", &self.event_type())
+            .field("zone", &self.zone())
+            .field("dwell_time", &self.dwell_time())
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_msg_attributes() {
+        let color = Classification {
+            component_id: 2,
+            classifier_type: Some("vehiclecolor".to_string()),
+            class_id: 1,
+            label_id: 0,
+            label: "red".to_string(),
+            confidence: 0.5,
+        };
+        let mut meta = NvDsEventMsgMeta::new(
+            NvDsRect::new(1.0, 2.0, 3.0, 4.0),
+            2,
+            "car",
+            0,
+            10,
+            0.9,
+            7,
+            "2021-01-01T00:00:00Z",
+        );
+        assert!(meta.attributes().is_empty());
+
+        meta.set_attributes(std::slice::from_ref(&color));
+        let copy = meta.clone();
+        drop(meta);
+        assert_eq!(copy.attributes(), vec![color]);
+        assert_eq!(copy.obj_class_label(), "car");
+    }
+
+    #[test]
+    fn event_msg_zone() {
+        let mut meta = NvDsEventMsgMeta::new(
+            NvDsRect::new(1.0, 2.0, 3.0, 4.0),
+            0,
+            "person",
+            1,
+            20,
+            0.8,
+            3,
+            "2021-01-01T00:00:00Z",
+        );
+        assert_eq!(meta.event_type(), NVDS_EVENT_MOVING);
+        assert_eq!(meta.zone(), None);
+
+        meta.set_event_type(NVDS_EVENT_STOPPED);
+        meta.set_zone(Some("entrance"));
+        meta.set_dwell_time(12.5);
+        meta.set_zone(Some("exit"));
+        let copy = meta.clone();
+        drop(meta);
+        assert_eq!(copy.event_type(), NVDS_EVENT_STOPPED);
+        assert_eq!(copy.zone(), Some("exit"));
+        assert_eq!(copy.dwell_time(), 12.5);
+    }
+}
@@ -0,0 +1,236 @@
+//! Frames of a batch buffer, the memory of a DeepStream buffer maps to a
+//! `NvBufSurface` with a [`NvBufSurfaceParams`] per frame.
+
+use libc::c_void;
+use std::marker::PhantomData;
+
+use deepstream_sys::nvbufsurface as ffi;
+pub use deepstream_sys::nvbufsurface::{
+    NvBufSurfaceMemMapFlags, NVBUF_MAP_READ, NVBUF_MAP_READ_WRITE, NVBUF_MAP_WRITE,
+};
+
+/// Caps feature of the buffers whose memory is a `NvBufSurface`.
+pub const CAPS_FEATURE_MEMORY_NVMM: &str = "memory:NVMM";
+
+#[repr(transparent)]
+pub struct NvBufSurface(ffi::NvBufSurface);
+
+impl NvBufSurface {
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid `NvBufSurface`, like the mapped memory of
+    /// an NVMM buffer, that outlives `'a` and has no other live reference.
+    pub unsafe fn from_ptr<'a>(ptr: *mut c_void) -> &'a mut Self {
+        &mut *(ptr as *mut Self)
+    }
+
+    /// Surface of a mapped buffer, `None` unless `caps`, the caps of the
+    /// buffer, have the [`CAPS_FEATURE_MEMORY_NVMM`] feature.
+    pub fn from_buffer_map<'a>(
+        map: &'a gstreamer::BufferMap<'_, gstreamer::buffer::Readable>,
+        caps: &gstreamer::CapsRef,
+    ) -> Option<&'a Self> {
+        if !is_surface(map.size(), caps) {
+            return None;
+        }
+        unsafe { Some(&*(map.as_ptr() as *const Self)) }
+    }
+
+    /// Surface of a buffer mapped writable, to map its frames for the CPU.
+    /// `None` unless `caps` have the [`CAPS_FEATURE_MEMORY_NVMM`] feature.
+    pub fn from_buffer_map_mut<'a>(
+        map: &'a mut gstreamer::BufferMap<'_, gstreamer::buffer::Writable>,
+        caps: &gstreamer::CapsRef,
+    ) -> Option<&'a mut Self> {
+        if !is_surface(map.size(), caps) {
+            return None;
+        }
+        unsafe { Some(&mut *(map.as_mut_ptr() as *mut Self)) }
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut ffi::NvBufSurface {
+        self as *const Self as *mut ffi::NvBufSurface
+    }
+
+    pub fn gpu_id(&self) -> u32 {
+        self.0.gpu_id
+    }
+
+    pub fn batch_size(&self) -> u32 {
+        self.0.batch_size
+    }
+
+    /// Number of frames in the batch.
+    pub fn num_filled(&self) -> u32 {
+        self.0.num_filled
+    }
+
+    pub fn mem_type(&self) -> ffi::NvBufSurfaceMemType {
+        self.0.mem_type
+    }
+
+    /// Params of the frame at `index` of the batch, the `batch_id` of its
+    /// frame meta.
+    pub fn surface_params(&self, index: u32) -> Option<&NvBufSurfaceParams> {
+        if index >= self.0.num_filled || self.0.surface_list.is_null() {
+            return None;
+        }
+        unsafe {
+            let params = self.0.surface_list.add(index as usize);
+            Some(&*(params as *const NvBufSurfaceParams))
+        }
+    }
+
+    pub fn iter_surface_params<'a>(&'a self) -> NvBufSurfaceParamsIter<'a> {
+        NvBufSurfaceParamsIter {
+            surface: self,
+            index: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Map all the planes of the frame at `index` for the CPU, their
+    /// addresses are then in [`NvBufSurfaceParams::mapped_addr`]. Only a
+    /// surface of a writable buffer can be mapped, see
+    /// [`NvBufSurface::from_buffer_map_mut`].
+    #[doc(alias = "NvBufSurfaceMap")]
+    pub fn map(&mut self, index: u32, flags: NvBufSurfaceMemMapFlags) -> bool {
+        unsafe { ffi::NvBufSurfaceMap(self.as_mut_ptr(), index as i32, -1, flags) == 0 }
+    }
+
+    /// Make the device writes visible to the CPU, on Jetson.
+    #[doc(alias = "NvBufSurfaceSyncForCpu")]
+    pub fn sync_for_cpu(&mut self, index: u32) -> bool {
+        unsafe { ffi::NvBufSurfaceSyncForCpu(self.as_mut_ptr(), index as i32, -1) == 0 }
+    }
+
+    #[doc(alias = "NvBufSurfaceUnMap")]
+    pub fn unmap(&mut self, index: u32) -> bool {
+        unsafe { ffi::NvBufSurfaceUnMap(self.as_mut_ptr(), index as i32, -1) == 0 }
+    }
+}
+
+/// Whether a mapped memory of `size` bytes of a buffer with `caps` is a
+/// `NvBufSurface`.
+fn is_surface(size: usize, caps: &gstreamer::CapsRef) -> bool {
+    let nvmm = caps
+        .features(0)
+        .is_some_and(|features| features.contains(CAPS_FEATURE_MEMORY_NVMM));
+    nvmm && size >= std::mem::size_of::<ffi::NvBufSurface>()
+}
+
+impl std::fmt::Debug for NvBufSurface {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NvBufSurface")
+            .field("gpu_id", &self.gpu_id())
+            .field("batch_size", &self.batch_size())
+            .field("num_filled", &self.num_filled())
+            .field("mem_type", &self.mem_type())
+            .finish()
+    }
+}
+
+pub struct NvBufSurfaceParamsIter<'a> {
+    surface: &'a NvBufSurface,
+    index: u32,
+    phantom: PhantomData<&'a NvBufSurfaceParams>,
+}
+
+impl<'a> Iterator for NvBufSurfaceParamsIter<'a> {
+    type Item = &'a NvBufSurfaceParams;
+
+    fn next(&mut self) -> Option<&'a NvBufSurfaceParams> {
+        let params = self.surface.surface_params(self.index)?;
+        self.index += 1;
+        Some(params)
+    }
+}
+
+/// A frame of a [`NvBufSurface`].
+#[repr(transparent)]
+pub struct NvBufSurfaceParams(ffi::NvBufSurfaceParams);
+
+impl NvBufSurfaceParams {
+    pub fn width(&self) -> u32 {
+        self.0.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.0.height
+    }
+
+    pub fn pitch(&self) -> u32 {
+        self.0.pitch
+    }
+
+    pub fn color_format(&self) -> ffi::NvBufSurfaceColorFormat {
+        self.0.color_format
+    }
+
+    pub fn data_size(&self) -> u32 {
+        self.0.data_size
+    }
+
+    /// Address of the frame in the memory of `mem_type`, not readable by the
+    /// CPU for the CUDA device memory.
+    pub fn data_ptr(&self) -> *mut c_void {
+        self.0.data_ptr
+    }
+
+    pub fn num_planes(&self) -> u32 {
+        self.0.plane_params.num_planes
+    }
+
+    /// CPU address of `plane`, null until the frame is mapped.
+    pub fn mapped_addr(&self, plane: usize) -> *mut c_void {
+        self.0
+            .mapped_addr
+            .addr
+            .get(plane)
+            .copied()
+            .unwrap_or(std::ptr::null_mut())
+    }
+}
+
+impl std::fmt::Debug for NvBufSurfaceParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NvBufSurfaceParams")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("pitch", &self.pitch())
+            .field("color_format", &self.color_format())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_params_of_the_filled_frames() {
+        let mut frames: [ffi::NvBufSurfaceParams; 2] = unsafe { std::mem::zeroed() };
+        frames[0].width = 1280;
+        frames[1].width = 640;
+        let mut surface: ffi::NvBufSurface = unsafe { std::mem::zeroed() };
+        surface.batch_size = 4;
+        surface.num_filled = 2;
+        surface.surface_list = frames.as_mut_ptr();
+        let surface = unsafe { NvBufSurface::from_ptr(&mut surface as *mut _ as *mut c_void) };
+
+        let widths: Vec<u32> = surface.iter_surface_params().map(|p| p.width()).collect();
+        assert_eq!(widths, vec![1280, 640]);
+        assert!(surface.surface_params(2).is_none());
+        assert!(surface.surface_params(0).unwrap().mapped_addr(0).is_null());
+    }
+
+    #[test]
+    fn system_memory_without_surface() {
+        gstreamer::init().unwrap();
+        let buffer = gstreamer::Buffer::from_slice(vec![0u8; 4096]);
+        let map = buffer.map_readable().unwrap();
+
+        let caps = gstreamer::Caps::new_simple("video/x-raw", &[]);
+        assert!(NvBufSurface::from_buffer_map(&map, &caps).is_none());
+    }
+}
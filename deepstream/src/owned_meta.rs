@@ -0,0 +1,455 @@
+//! Owned, pure Rust batch metadata.
+//!
+//! Mirrors the `NvDsBatchMeta` / `NvDsFrameMeta` / `NvDsObjectMeta` hierarchy
+//! without libnvds_meta, so it can be built in tests or by CPU only pipelines
+//! and attached to a buffer with [`BatchGstMeta`].
+
+use std::fmt;
+
+use gstreamer::meta::{MetaAPI, MetaRefMut, Standalone};
+use gstreamer::BufferRef;
+
+use crate::meta_api::{
+    BBox, BatchMetaApi, BatchMetaApiMut, Classification, FrameMetaApi, FrameMetaApiMut,
+    ObjectMetaApi,
+};
+use crate::user_meta::{AnyUserMetaData, UserMetaData};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ObjectMeta {
+    class_id: i32,
+    label: String,
+    object_id: Option<u64>,
+    bbox: BBox,
+    confidence: f32,
+    classifications: Vec<Classification>,
+}
+
+impl ObjectMeta {
+    pub fn builder() -> ObjectMetaBuilder {
+        ObjectMetaBuilder::default()
+    }
+}
+
+impl ObjectMetaApi for ObjectMeta {
+    fn class_id(&self) -> i32 {
+        self.class_id
+    }
+
+    fn obj_label(&self) -> &str {
+        &self.label
+    }
+
+    fn object_id(&self) -> Option<u64> {
+        self.object_id
+    }
+
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    fn classifications(&self) -> Vec<Classification> {
+        self.classifications.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ObjectMetaBuilder {
+    meta: ObjectMeta,
+}
+
+impl ObjectMetaBuilder {
+    pub fn class_id(mut self, class_id: i32) -> Self {
+        self.meta.class_id = class_id;
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.meta.label = label.to_owned();
+        self
+    }
+
+    pub fn object_id(mut self, object_id: u64) -> Self {
+        self.meta.object_id = Some(object_id);
+        self
+    }
+
+    pub fn bbox(mut self, left: f32, top: f32, width: f32, height: f32) -> Self {
+        self.meta.bbox = BBox {
+            left,
+            top,
+            width,
+            height,
+        };
+        self
+    }
+
+    pub fn confidence(mut self, confidence: f32) -> Self {
+        self.meta.confidence = confidence;
+        self
+    }
+
+    pub fn classification(mut self, classification: Classification) -> Self {
+        self.meta.classifications.push(classification);
+        self
+    }
+
+    pub fn build(self) -> ObjectMeta {
+        self.meta
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FrameMeta {
+    frame_number: i32,
+    source_id: u32,
+    batch_id: u32,
+    buf_pts: u64,
+    objects: Vec<ObjectMeta>,
+    user_data: Vec<Box<dyn AnyUserMetaData>>,
+}
+
+impl FrameMeta {
+    pub fn builder() -> FrameMetaBuilder {
+        FrameMetaBuilder::default()
+    }
+
+    pub fn objects_mut(&mut self) -> &mut Vec<ObjectMeta> {
+        &mut self.objects
+    }
+
+    /// Data attached to the frame holding a `T`.
+    pub fn iter_user_meta<T: UserMetaData>(&self) -> impl Iterator<Item = &T> {
+        self.user_data
+            .iter()
+            .filter_map(|data| data.as_any().downcast_ref())
+    }
+}
+
+impl FrameMetaApi for FrameMeta {
+    fn frame_number(&self) -> i32 {
+        self.frame_number
+    }
+
+    fn source_id(&self) -> u32 {
+        self.source_id
+    }
+
+    fn batch_id(&self) -> u32 {
+        self.batch_id
+    }
+
+    fn buf_pts(&self) -> u64 {
+        self.buf_pts
+    }
+
+    fn objects(&self) -> Box<dyn Iterator<Item = &dyn ObjectMetaApi> + '_> {
+        Box::new(self.objects.iter().map(|obj| obj as &dyn ObjectMetaApi))
+    }
+}
+
+impl FrameMetaApiMut for FrameMeta {
+    fn retain_objects(&mut self, keep: &mut dyn FnMut(&dyn ObjectMetaApi) -> bool) {
+        self.objects.retain(|obj| keep(obj));
+    }
+
+    fn add_user_data(&mut self, data: Box<dyn AnyUserMetaData>) {
+        self.user_data.push(data);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FrameMetaBuilder {
+    meta: FrameMeta,
+}
+
+impl FrameMetaBuilder {
+    pub fn frame_number(mut self, frame_number: i32) -> Self {
+        self.meta.frame_number = frame_number;
+        self
+    }
+
+    pub fn source_id(mut self, source_id: u32) -> Self {
+        self.meta.source_id = source_id;
+        self
+    }
+
+    pub fn batch_id(mut self, batch_id: u32) -> Self {
+        self.meta.batch_id = batch_id;
+        self
+    }
+
+    pub fn buf_pts(mut self, buf_pts: u64) -> Self {
+        self.meta.buf_pts = buf_pts;
+        self
+    }
+
+    pub fn object(mut self, object: ObjectMeta) -> Self {
+        self.meta.objects.push(object);
+        self
+    }
+
+    pub fn build(self) -> FrameMeta {
+        self.meta
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchMeta {
+    frames: Vec<FrameMeta>,
+}
+
+impl BatchMeta {
+    pub fn builder() -> BatchMetaBuilder {
+        BatchMetaBuilder::default()
+    }
+
+    pub fn frames_mut(&mut self) -> &mut Vec<FrameMeta> {
+        &mut self.frames
+    }
+}
+
+impl BatchMetaApi for BatchMeta {
+    fn frames(&self) -> Box<dyn Iterator<Item = &dyn FrameMetaApi> + '_> {
+        Box::new(self.frames.iter().map(|frame| frame as &dyn FrameMetaApi))
+    }
+}
+
+impl BatchMetaApiMut for BatchMeta {
+    fn frames_mut(&mut self) -> Box<dyn Iterator<Item = &mut dyn FrameMetaApiMut> + '_> {
+        Box::new(
+            self.frames
+                .iter_mut()
+                .map(|frame| frame as &mut dyn FrameMetaApiMut),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BatchMetaBuilder {
+    meta: BatchMeta,
+}
+
+impl BatchMetaBuilder {
+    pub fn frame(mut self, frame: FrameMeta) -> Self {
+        self.meta.frames.push(frame);
+        self
+    }
+
+    pub fn build(self) -> BatchMeta {
+        self.meta
+    }
+}
+
+/// Buffer meta holding a [`BatchMeta`].
+#[repr(transparent)]
+pub struct BatchGstMeta(imp::BatchGstMeta);
+
+unsafe impl Send for BatchGstMeta {}
+unsafe impl Sync for BatchGstMeta {}
+
+impl BatchGstMeta {
+    pub fn add(buffer: &mut BufferRef, batch: BatchMeta) -> MetaRefMut<'_, Self, Standalone> {
+        unsafe {
+            let mut params = std::mem::ManuallyDrop::new(imp::BatchGstMetaParams { batch });
+            let meta = gstreamer::ffi::gst_buffer_add_meta(
+                buffer.as_mut_ptr(),
+                imp::batch_gst_meta_get_info(),
+                &mut *params as *mut imp::BatchGstMetaParams as glib::ffi::gpointer,
+            ) as *mut imp::BatchGstMeta;
+
+            Self::from_mut_ptr(buffer, meta)
+        }
+    }
+
+    pub fn batch(&self) -> &BatchMeta {
+        &self.0.batch
+    }
+
+    pub fn batch_mut(&mut self) -> &mut BatchMeta {
+        &mut self.0.batch
+    }
+}
+
+unsafe impl MetaAPI for BatchGstMeta {
+    type GstType = imp::BatchGstMeta;
+
+    fn meta_api() -> glib::Type {
+        imp::batch_gst_meta_api_get_type()
+    }
+}
+
+impl fmt::Debug for BatchGstMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BatchGstMeta")
+            .field("batch", self.batch())
+            .finish()
+    }
+}
+
+mod imp {
+    use glib::translate::{from_glib, IntoGlib};
+    use gstreamer::ffi as gst_ffi;
+    use once_cell::sync::Lazy;
+    use std::ptr;
+
+    use super::BatchMeta;
+
+    pub(super) struct BatchGstMetaParams {
+        pub batch: BatchMeta,
+    }
+
+    #[repr(C)]
+    pub struct BatchGstMeta {
+        parent: gst_ffi::GstMeta,
+        pub(super) batch: BatchMeta,
+    }
+
+    pub(super) fn batch_gst_meta_api_get_type() -> glib::Type {
+        static TYPE: Lazy<glib::Type> = Lazy::new(|| unsafe {
+            let t = from_glib(gst_ffi::gst_meta_api_type_register(
+                c"DsRsBatchMetaAPI".as_ptr(),
+                [ptr::null::<std::os::raw::c_char>()].as_ptr() as *mut *const _,
+            ));
+
+            assert_ne!(t, glib::Type::INVALID);
+
+            t
+        });
+
+        *TYPE
+    }
+
+    unsafe extern "C" fn batch_gst_meta_init(
+        meta: *mut gst_ffi::GstMeta,
+        params: glib::ffi::gpointer,
+        _buffer: *mut gst_ffi::GstBuffer,
+    ) -> glib::ffi::gboolean {
+        assert!(!params.is_null());
+
+        let meta = &mut *(meta as *mut BatchGstMeta);
+        let params = ptr::read(params as *const BatchGstMetaParams);
+        ptr::write(&mut meta.batch, params.batch);
+
+        true.into_glib()
+    }
+
+    unsafe extern "C" fn batch_gst_meta_free(
+        meta: *mut gst_ffi::GstMeta,
+        _buffer: *mut gst_ffi::GstBuffer,
+    ) {
+        let meta = &mut *(meta as *mut BatchGstMeta);
+        ptr::drop_in_place(&mut meta.batch);
+    }
+
+    unsafe extern "C" fn batch_gst_meta_transform(
+        dest: *mut gst_ffi::GstBuffer,
+        meta: *mut gst_ffi::GstMeta,
+        _buffer: *mut gst_ffi::GstBuffer,
+        _type: glib::ffi::GQuark,
+        _data: glib::ffi::gpointer,
+    ) -> glib::ffi::gboolean {
+        let meta = &*(meta as *mut BatchGstMeta);
+
+        super::BatchGstMeta::add(gstreamer::BufferRef::from_mut_ptr(dest), meta.batch.clone());
+
+        true.into_glib()
+    }
+
+    pub(super) fn batch_gst_meta_get_info() -> *const gst_ffi::GstMetaInfo {
+        struct MetaInfo(ptr::NonNull<gst_ffi::GstMetaInfo>);
+        unsafe impl Send for MetaInfo {}
+        unsafe impl Sync for MetaInfo {}
+
+        static META_INFO: Lazy<MetaInfo> = Lazy::new(|| unsafe {
+            MetaInfo(
+                ptr::NonNull::new(gst_ffi::gst_meta_register(
+                    batch_gst_meta_api_get_type().into_glib(),
+                    c"DsRsBatchMeta".as_ptr(),
+                    std::mem::size_of::<BatchGstMeta>(),
+                    Some(batch_gst_meta_init),
+                    Some(batch_gst_meta_free),
+                    Some(batch_gst_meta_transform),
+                ) as *mut gst_ffi::GstMetaInfo)
+                .expect("Failed to register batch meta API"),
+            )
+        });
+
+        META_INFO.0.as_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_api::for_each_batch_meta;
+
+    fn batch() -> BatchMeta {
+        BatchMeta::builder()
+            .frame(
+                FrameMeta::builder()
+                    .source_id(1)
+                    .buf_pts(40_000_000)
+                    .frame_number(10)
+                    .object(
+                        ObjectMeta::builder()
+                            .class_id(2)
+                            .label("car")
+                            .object_id(7)
+                            .bbox(1.0, 2.0, 30.0, 40.0)
+                            .confidence(0.9)
+                            .classification(Classification {
+                                component_id: 2,
+                                classifier_type: Some("vehiclecolor".to_string()),
+                                class_id: 3,
+                                label_id: 0,
+                                label: "red".to_string(),
+                                confidence: 0.8,
+                            })
+                            .build(),
+                    )
+                    .object(ObjectMeta::builder().class_id(0).label("person").build())
+                    .build(),
+            )
+            .frame(FrameMeta::builder().source_id(2).build())
+            .build()
+    }
+
+    #[test]
+    fn builders_through_api() {
+        let batch = batch();
+        let frames: Vec<&dyn FrameMetaApi> = batch.frames().collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].source_id(), 1);
+        assert_eq!(frames[0].frame_number(), 10);
+        assert_eq!(frames[0].buf_pts(), 40_000_000);
+
+        let objects: Vec<&dyn ObjectMetaApi> = frames[0].objects().collect();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].obj_label(), "car");
+        assert_eq!(objects[0].object_id(), Some(7));
+        assert_eq!(objects[0].bbox().height, 40.0);
+        assert_eq!(objects[0].classifications()[0].label, "red");
+        assert_eq!(objects[1].object_id(), None);
+        assert!(objects[1].classifications().is_empty());
+        assert_eq!(frames[1].objects().count(), 0);
+    }
+
+    #[test]
+    fn attach_to_buffer() {
+        gstreamer::init().unwrap();
+
+        let mut buffer = gstreamer::Buffer::new();
+        BatchGstMeta::add(buffer.get_mut().unwrap(), batch());
+
+        // metas are copied with the buffer
+        let copy = buffer.copy();
+        let mut num_frames = 0;
+        for_each_batch_meta(&copy, |batch| num_frames += batch.frames().count());
+        assert_eq!(num_frames, 2);
+    }
+}
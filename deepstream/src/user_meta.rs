@@ -0,0 +1,219 @@
+//! Rust data attached to the batch, frame or object user meta lists.
+//!
+//! A type implementing [`UserMetaData`] is boxed in the `user_meta_data` of a
+//! `NvDsUserMeta`, with copy and release callbacks cloning and dropping it, so
+//! it follows the buffers through the pipeline like the DeepStream metas.
+
+use glib::ffi::gpointer;
+use once_cell::sync::Lazy;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use deepstream_sys::nvdsmeta as ffi;
+
+/// Meta types registered by descriptor.
+static META_TYPES: Lazy<Mutex<HashMap<&'static str, ffi::NvDsMetaType>>> =
+    Lazy::new(Default::default);
+
+/// Data that can be attached as user meta.
+pub trait UserMetaData: Clone + Send + 'static {
+    /// Unique name of the data, e.g. `"DSRS.ANALYTICS.EVENTS"`, registered as
+    /// a custom meta type.
+    const DESCRIPTOR: &'static str;
+
+    /// Meta type of the user metas holding the data.
+    fn meta_type() -> ffi::NvDsMetaType {
+        user_meta_type(Self::DESCRIPTOR)
+    }
+}
+
+/// A [`UserMetaData`] of any type, attached through
+/// [`FrameMetaApiMut`](crate::meta_api::FrameMetaApiMut).
+pub trait AnyUserMetaData: Send {
+    fn descriptor(&self) -> &'static str;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn clone_box(&self) -> Box<dyn AnyUserMetaData>;
+
+    /// Box the data in `user_meta` and set the callbacks of its type.
+    fn set_in(self: Box<Self>, user_meta: &mut ffi::NvDsUserMeta);
+}
+
+impl<T: UserMetaData> AnyUserMetaData for T {
+    fn descriptor(&self) -> &'static str {
+        T::DESCRIPTOR
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyUserMetaData> {
+        Box::new(self.clone())
+    }
+
+    fn set_in(self: Box<Self>, user_meta: &mut ffi::NvDsUserMeta) {
+        set_data(user_meta, *self);
+    }
+}
+
+impl Clone for Box<dyn AnyUserMetaData> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl std::fmt::Debug for dyn AnyUserMetaData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("AnyUserMetaData")
+            .field(&self.descriptor())
+            .finish()
+    }
+}
+
+/// Custom meta type of `descriptor`, registered on the first call.
+#[doc(alias = "nvds_get_user_meta_type")]
+pub fn user_meta_type(descriptor: &'static str) -> ffi::NvDsMetaType {
+    *META_TYPES
+        .lock()
+        .unwrap()
+        .entry(descriptor)
+        .or_insert_with(|| {
+            let descriptor = CString::new(descriptor).unwrap();
+            unsafe { ffi::nvds_get_user_meta_type(descriptor.as_ptr() as *mut _) }
+        })
+}
+
+/// Box `data` in `user_meta` and set the callbacks of its type.
+pub(crate) fn set_data<T: UserMetaData>(user_meta: &mut ffi::NvDsUserMeta, data: T) {
+    user_meta.user_meta_data = Box::into_raw(Box::new(data)) as gpointer;
+    user_meta.base_meta.meta_type = T::meta_type();
+    user_meta.base_meta.copy_func = Some(copy_func::<T>);
+    user_meta.base_meta.release_func = Some(release_func::<T>);
+}
+
+/// Data of `user_meta` if it holds a `T`.
+pub(crate) fn data<T: UserMetaData>(user_meta: &ffi::NvDsUserMeta) -> Option<&T> {
+    if user_meta.base_meta.meta_type != T::meta_type() || user_meta.user_meta_data.is_null() {
+        return None;
+    }
+    unsafe { Some(&*(user_meta.user_meta_data as *const T)) }
+}
+
+unsafe extern "C" fn copy_func<T: UserMetaData>(data: gpointer, _user_data: gpointer) -> gpointer {
+    let user_meta = &*(data as *const ffi::NvDsUserMeta);
+    let src = &*(user_meta.user_meta_data as *const T);
+    Box::into_raw(Box::new(src.clone())) as gpointer
+}
+
+unsafe extern "C" fn release_func<T: UserMetaData>(data: gpointer, _user_data: gpointer) {
+    let user_meta = &mut *(data as *mut ffi::NvDsUserMeta);
+    if !user_meta.user_meta_data.is_null() {
+        drop(Box::from_raw(user_meta.user_meta_data as *mut T));
+        user_meta.user_meta_data = std::ptr::null_mut();
+    }
+}
+
+/// Data of the user metas of a list holding a `T`, the others are skipped.
+pub struct UserMetaIter<'a, T> {
+    ptr: Option<std::ptr::NonNull<ffi::NvDsUserMetaList>>,
+    phantom: PhantomData<&'a T>,
+}
+
+impl<'a, T> UserMetaIter<'a, T> {
+    pub fn new(list: *mut ffi::NvDsUserMetaList) -> Self {
+        let ptr = std::ptr::NonNull::new(list);
+        Self {
+            ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: UserMetaData> Iterator for UserMetaIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while let Some(cur) = self.ptr {
+            unsafe {
+                self.ptr = std::ptr::NonNull::new(cur.as_ref().next);
+
+                let user_meta = &*(cur.as_ref().data as *const ffi::NvDsUserMeta);
+                if let Some(data) = data::<T>(user_meta) {
+                    return Some(data);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glib::ffi::GList;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl UserMetaData for Counted {
+        const DESCRIPTOR: &'static str = "DSRS.TEST.COUNTED";
+
+        // no libnvds_meta in the tests
+        fn meta_type() -> ffi::NvDsMetaType {
+            ffi::NVDS_START_USER_META + 1
+        }
+    }
+
+    #[test]
+    fn copy_release_and_iterate() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut user_meta: ffi::NvDsUserMeta = unsafe { std::mem::zeroed() };
+        set_data(&mut user_meta, Counted(drops.clone()));
+        assert_eq!(user_meta.base_meta.meta_type, Counted::meta_type());
+
+        // the copy is a new box the release drops
+        let user_meta_ptr = &mut user_meta as *mut _ as gpointer;
+        let copy = unsafe { (user_meta.base_meta.copy_func.unwrap())(user_meta_ptr, ptr()) };
+        let mut copy_meta: ffi::NvDsUserMeta = unsafe { std::mem::zeroed() };
+        copy_meta.user_meta_data = copy;
+        unsafe { release_func::<Counted>(&mut copy_meta as *mut _ as gpointer, ptr()) };
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // other meta types are skipped
+        let mut other: ffi::NvDsUserMeta = unsafe { std::mem::zeroed() };
+        other.base_meta.meta_type = ffi::NVDS_EVENT_MSG_META;
+        let mut second = GList {
+            data: user_meta_ptr,
+            next: std::ptr::null_mut(),
+            prev: std::ptr::null_mut(),
+        };
+        let mut first = GList {
+            data: &mut other as *mut _ as gpointer,
+            next: &mut second,
+            prev: std::ptr::null_mut(),
+        };
+        assert_eq!(UserMetaIter::<Counted>::new(&mut first).count(), 1);
+
+        unsafe { (user_meta.base_meta.release_func.unwrap())(user_meta_ptr, ptr()) };
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        assert!(user_meta.user_meta_data.is_null());
+    }
+
+    fn ptr() -> gpointer {
+        std::ptr::null_mut()
+    }
+}
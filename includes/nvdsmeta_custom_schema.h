@@ -52,6 +52,25 @@ extern "C"
         float height; /**< Holds the rectangle's height in pixels. */
     } NvDsRect;
 
+    /**
+ * Holds a label given to an object by a secondary classifier.
+ */
+    typedef struct NvDsObjectAttribute
+    {
+        /** Holds the unique ID of the classifier. */
+        gint classifierId;
+        /** Holds a pointer to a string containing the classifier type, or NULL. */
+        gchar *classifierType;
+        /** Holds the class ID of the label. */
+        gint classId;
+        /** Holds the index of the attribute among the classifier outputs. */
+        gint labelId;
+        /** Holds a pointer to a string containing the label. */
+        gchar *label;
+        /** Holds the confidence level of the label. */
+        gdouble confidence;
+    } NvDsObjectAttribute;
+
     typedef struct NvDsEventMsgMeta
     {
         /** Holds the object's bounding box. */
@@ -70,6 +89,17 @@ extern "C"
         gint trackingId;
         /** Holds a pointer to the generated event's timestamp. */
         gchar *ts;
+        /** Holds the labels given to the object by the classifiers. */
+        NvDsObjectAttribute *attributes;
+        /** Holds the number of attributes. */
+        guint numAttributes;
+        /** Holds the type of the event. */
+        NvDsEventType type;
+        /** Holds a pointer to a string containing the name of the zone of the
+   event, or NULL. */
+        gchar *zone;
+        /** Holds how long the object stayed in the zone, in seconds. */
+        gdouble dwellTime;
     } NvDsEventMsgMeta;
 
     /**
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use deepstream::meta_api::ObjectMetaApi;
+
+/// Objects and frames sent to the message converter, an empty list allows
+/// everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub class_ids: Vec<i32>,
+    pub deny_class_ids: Vec<i32>,
+    pub labels: Vec<String>,
+    pub deny_labels: Vec<String>,
+    pub min_confidence: f32,
+    /// Size of the box, in pixels.
+    pub min_width: u32,
+    pub min_height: u32,
+    pub source_ids: Vec<u32>,
+    /// Send one frame out of `frame_interval` of every source.
+    pub frame_interval: u32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            class_ids: Vec::new(),
+            deny_class_ids: Vec::new(),
+            labels: Vec::new(),
+            deny_labels: Vec::new(),
+            min_confidence: 0.0,
+            min_width: 0,
+            min_height: 0,
+            source_ids: Vec::new(),
+            frame_interval: 1,
+        }
+    }
+}
+
+impl Filter {
+    pub fn keeps_source(&self, source_id: u32) -> bool {
+        self.source_ids.is_empty() || self.source_ids.contains(&source_id)
+    }
+
+    /// Whether the frame of a source numbered `index`, from 0, is sent.
+    pub fn keeps_frame(&self, index: u64) -> bool {
+        index.is_multiple_of(u64::from(self.frame_interval.max(1)))
+    }
+
+    pub fn keeps_object(&self, obj: &dyn ObjectMetaApi) -> bool {
+        let label = obj.obj_label();
+        let bbox = obj.bbox();
+        (self.class_ids.is_empty() || self.class_ids.contains(&obj.class_id()))
+            && !self.deny_class_ids.contains(&obj.class_id())
+            && (self.labels.is_empty() || self.labels.iter().any(|l| l == label))
+            && !self.deny_labels.iter().any(|l| l == label)
+            && obj.confidence() >= self.min_confidence
+            && bbox.width >= self.min_width as f32
+            && bbox.height >= self.min_height as f32
+    }
+}
+
+/// Parse a list of values separated by `;`, like the `nvinfer` properties.
+pub fn parse_list<T: FromStr>(list: &str) -> Result<Vec<T>, T::Err> {
+    list.split(';')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+pub fn format_list<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deepstream::owned_meta::ObjectMeta;
+
+    #[test]
+    fn filter_objects() {
+        let filter = Filter {
+            deny_class_ids: parse_list("2").unwrap(),
+            labels: parse_list("car; person;").unwrap(),
+            min_confidence: 0.5,
+            min_width: 10,
+            ..Filter::default()
+        };
+        assert_eq!(format_list(&filter.labels), "car;person");
+        assert!(parse_list::<i32>("1;two").is_err());
+
+        let object = |class_id, label, confidence, width| {
+            ObjectMeta::builder()
+                .class_id(class_id)
+                .label(label)
+                .confidence(confidence)
+                .bbox(0.0, 0.0, width, 20.0)
+                .build()
+        };
+        assert!(filter.keeps_object(&object(0, "car", 0.9, 20.0)));
+        assert!(!filter.keeps_object(&object(2, "car", 0.9, 20.0)));
+        assert!(!filter.keeps_object(&object(0, "bicycle", 0.9, 20.0)));
+        assert!(!filter.keeps_object(&object(0, "car", 0.4, 20.0)));
+        assert!(!filter.keeps_object(&object(0, "car", 0.9, 5.0)));
+
+        let filter = Filter {
+            source_ids: vec![1],
+            frame_interval: 3,
+            ..Filter::default()
+        };
+        assert!(filter.keeps_source(1));
+        assert!(!filter.keeps_source(2));
+        let kept: Vec<u64> = (0..7).filter(|i| filter.keeps_frame(*i)).collect();
+        assert_eq!(kept, vec![0, 3, 6]);
+    }
+}
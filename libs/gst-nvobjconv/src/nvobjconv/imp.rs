@@ -1,17 +1,183 @@
 use chrono::prelude::{DateTime, Utc};
 use gst::glib;
+use gst::prelude::*;
 use gst::subclass::prelude::ObjectSubclass;
-use gst_base::subclass::prelude::{BaseTransformImpl, ElementImpl, ObjectImpl};
+use gst_base::subclass::prelude::{
+    BaseTransformImpl, BaseTransformImplExt, ElementImpl, ObjectImpl,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use once_cell::sync::Lazy;
 
-use deepstream::gst_meta::{DsMeta, GstNvDsMetaType};
+use deepstream::events::StreamEvent;
+use deepstream::gst_meta::DsMeta;
+use deepstream::meta_api::{BatchMetaApiMut, FrameMetaApi, ObjectMetaApi};
 use deepstream::meta_schema::{NvDsEventMsgMeta, NvDsRect};
+use deepstream::owned_meta::{BatchGstMeta, BatchMeta, FrameMeta};
+
+use super::filter::{format_list, parse_list, Filter};
+use super::lifecycle::{Lifecycles, Mode, Settings};
+
+static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
+    gst::DebugCategory::new(
+        "nvobjconv",
+        gst::DebugColorFlags::empty(),
+        Some("NVObjects Converter"),
+    )
+});
+
+/// Tracks and frame counts of the sources.
+#[derive(Default)]
+struct State {
+    lifecycles: Lifecycles,
+    frames: HashMap<u32, u64>,
+    /// Whether the last batch was a DeepStream one, the exits are sent in the
+    /// same kind of batch.
+    nvds: bool,
+}
 
 #[derive(Default)]
-pub struct NVObjconv {}
+pub struct NVObjconv {
+    settings: Mutex<Settings>,
+    filter: Mutex<Filter>,
+    state: Mutex<State>,
+}
+
+impl NVObjconv {
+    /// Create the event message of an object detected in `frame`.
+    fn event_msg_meta(
+        frame: &dyn FrameMetaApi,
+        obj: &dyn ObjectMetaApi,
+        ts: &str,
+    ) -> NvDsEventMsgMeta {
+        let tracking_id = match obj.object_id() {
+            Some(id) => id.try_into().expect(&format!("Invalid object id: {}", id)),
+            None => -1,
+        };
+        let bbox = obj.bbox();
+
+        let mut msg_meta = NvDsEventMsgMeta::new(
+            NvDsRect::new(bbox.top, bbox.left, bbox.width, bbox.height),
+            obj.class_id(),
+            obj.obj_label(),
+            frame.source_id().try_into().unwrap(),
+            frame.frame_number(),
+            f64::from(obj.confidence()),
+            tracking_id,
+            ts,
+        );
+        msg_meta.set_attributes(&obj.classifications());
+
+        msg_meta
+    }
 
-impl NVObjconv {}
+    /// Attach the messages of the objects `filter` keeps to the frames of
+    /// `batch`.
+    fn process_batch(
+        settings: &Settings,
+        filter: &Filter,
+        state: &mut State,
+        batch: &mut dyn BatchMetaApiMut,
+    ) {
+        let ts = Utc::now().to_rfc3339();
+
+        for frame in batch.frames_mut() {
+            let source_id = frame.source_id();
+            if !filter.keeps_source(source_id) {
+                continue;
+            }
+            let index = state.frames.entry(source_id).or_default();
+            *index += 1;
+            if !filter.keeps_frame(*index - 1) {
+                continue;
+            }
+
+            let objects = frame
+                .objects()
+                .filter_map(|obj| {
+                    filter
+                        .keeps_object(obj)
+                        .then(|| (obj.object_id(), Self::event_msg_meta(&*frame, obj, &ts)))
+                })
+                .collect();
+            let messages = state
+                .lifecycles
+                .frame(settings, source_id, frame.buf_pts(), objects);
+            for msg_meta in messages {
+                frame.add_user_data(Box::new(msg_meta));
+            }
+        }
+    }
+
+    /// End the tracks of `sources`, returning their exit messages by source.
+    fn end_sources(state: &mut State, sources: Vec<u32>) -> Vec<(u32, Vec<NvDsEventMsgMeta>)> {
+        sources
+            .into_iter()
+            .map(|source_id| (source_id, state.lifecycles.end(source_id)))
+            .filter(|(_, exits)| !exits.is_empty())
+            .collect()
+    }
+
+    /// Buffer without data holding a batch of a frame per source of `exits`,
+    /// carrying their exit messages, a DeepStream batch if `nvds`.
+    fn exits_buffer(exits: Vec<(u32, Vec<NvDsEventMsgMeta>)>, nvds: bool) -> gst::Buffer {
+        let mut buffer = gst::Buffer::new();
+        let buffer_mut = buffer.get_mut().unwrap();
+        if nvds {
+            let mut meta = DsMeta::add(buffer_mut, exits.len() as u32);
+            let batch = meta.batch_meta().unwrap();
+            for (source_id, _) in &exits {
+                batch.add_frame_meta(*source_id);
+            }
+            Self::add_exits(batch, exits);
+        } else {
+            let mut batch = BatchMeta::builder();
+            for (batch_id, (source_id, _)) in exits.iter().enumerate() {
+                let frame = FrameMeta::builder()
+                    .source_id(*source_id)
+                    .batch_id(batch_id as u32)
+                    .build();
+                batch = batch.frame(frame);
+            }
+            let mut meta = BatchGstMeta::add(buffer_mut, batch.build());
+            Self::add_exits(meta.batch_mut(), exits);
+        }
+        buffer
+    }
+
+    /// Attach the exit messages of every source to its frame of `batch`.
+    fn add_exits(batch: &mut dyn BatchMetaApiMut, exits: Vec<(u32, Vec<NvDsEventMsgMeta>)>) {
+        let mut exits: HashMap<u32, Vec<NvDsEventMsgMeta>> = exits.into_iter().collect();
+        for frame in batch.frames_mut() {
+            for msg_meta in exits.remove(&frame.source_id()).unwrap_or_default() {
+                frame.add_user_data(Box::new(msg_meta));
+            }
+        }
+    }
+
+    /// Set a list property from its string, keeping the previous list if it
+    /// isn't valid.
+    fn set_list<T: FromStr>(
+        obj: &super::NVObjconv,
+        pspec: &glib::ParamSpec,
+        value: &glib::Value,
+        list: &mut Vec<T>,
+    ) where
+        T::Err: Display,
+    {
+        let value: Option<String> = value.get().expect("type checked upstream");
+        match parse_list(value.as_deref().unwrap_or("")) {
+            Ok(values) => *list = values,
+            Err(err) => {
+                gst::gst_error!(CAT, obj: obj, "Invalid {}: {}", pspec.name(), err);
+            }
+        }
+    }
+}
 
 #[glib::object_subclass]
 impl ObjectSubclass for NVObjconv {
@@ -20,7 +186,170 @@ impl ObjectSubclass for NVObjconv {
     type ParentType = gst_base::BaseTransform;
 }
 
-impl ObjectImpl for NVObjconv {}
+impl ObjectImpl for NVObjconv {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            let settings = Settings::default();
+            let filter = Filter::default();
+            let list = |name, nick, blurb| {
+                glib::ParamSpec::new_string(name, nick, blurb, None, glib::ParamFlags::READWRITE)
+            };
+            vec![
+                glib::ParamSpec::new_enum(
+                    "mode",
+                    "Mode",
+                    "Which objects are sent to the message converter",
+                    Mode::static_type(),
+                    settings.mode as i32,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpec::new_uint(
+                    "throttle-interval",
+                    "Throttle interval",
+                    "Minimum time between two moving events of a track, in milliseconds",
+                    0,
+                    u32::MAX,
+                    settings.throttle_interval.as_millis() as u32,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpec::new_uint(
+                    "max-missing-frames",
+                    "Max missing frames",
+                    "Frames of its source a track can be missing from before its exit",
+                    0,
+                    u32::MAX,
+                    settings.max_missing_frames as u32,
+                    glib::ParamFlags::READWRITE,
+                ),
+                list(
+                    "class-ids",
+                    "Class ids",
+                    "Classes of the objects sent, separated by ;, all of them if empty",
+                ),
+                list(
+                    "deny-class-ids",
+                    "Deny class ids",
+                    "Classes of the objects not sent, separated by ;",
+                ),
+                list(
+                    "labels",
+                    "Labels",
+                    "Labels of the objects sent, separated by ;, all of them if empty",
+                ),
+                list(
+                    "deny-labels",
+                    "Deny labels",
+                    "Labels of the objects not sent, separated by ;",
+                ),
+                glib::ParamSpec::new_float(
+                    "min-confidence",
+                    "Min confidence",
+                    "Minimum confidence of the objects sent",
+                    0.0,
+                    1.0,
+                    filter.min_confidence,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpec::new_uint(
+                    "min-width",
+                    "Min width",
+                    "Minimum width of the boxes of the objects sent, in pixels",
+                    0,
+                    u32::MAX,
+                    filter.min_width,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpec::new_uint(
+                    "min-height",
+                    "Min height",
+                    "Minimum height of the boxes of the objects sent, in pixels",
+                    0,
+                    u32::MAX,
+                    filter.min_height,
+                    glib::ParamFlags::READWRITE,
+                ),
+                list(
+                    "source-ids",
+                    "Source ids",
+                    "Sources of the objects sent, separated by ;, all of them if empty",
+                ),
+                glib::ParamSpec::new_uint(
+                    "frame-interval",
+                    "Frame interval",
+                    "Send one frame out of frame-interval of every source",
+                    1,
+                    u32::MAX,
+                    filter.frame_interval,
+                    glib::ParamFlags::READWRITE,
+                ),
+            ]
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(
+        &self,
+        obj: &Self::Type,
+        _id: usize,
+        value: &glib::Value,
+        pspec: &glib::ParamSpec,
+    ) {
+        let mut settings = self.settings.lock().unwrap();
+        let mut filter = self.filter.lock().unwrap();
+        match pspec.name() {
+            "mode" => {
+                settings.mode = value.get().expect("type checked upstream");
+            }
+            "throttle-interval" => {
+                let millis: u32 = value.get().expect("type checked upstream");
+                settings.throttle_interval = Duration::from_millis(millis.into());
+            }
+            "max-missing-frames" => {
+                let frames: u32 = value.get().expect("type checked upstream");
+                settings.max_missing_frames = frames.into();
+            }
+            "class-ids" => Self::set_list(obj, pspec, value, &mut filter.class_ids),
+            "deny-class-ids" => Self::set_list(obj, pspec, value, &mut filter.deny_class_ids),
+            "labels" => Self::set_list(obj, pspec, value, &mut filter.labels),
+            "deny-labels" => Self::set_list(obj, pspec, value, &mut filter.deny_labels),
+            "min-confidence" => {
+                filter.min_confidence = value.get().expect("type checked upstream");
+            }
+            "min-width" => {
+                filter.min_width = value.get().expect("type checked upstream");
+            }
+            "min-height" => {
+                filter.min_height = value.get().expect("type checked upstream");
+            }
+            "source-ids" => Self::set_list(obj, pspec, value, &mut filter.source_ids),
+            "frame-interval" => {
+                filter.frame_interval = value.get().expect("type checked upstream");
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn property(&self, _obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        let settings = self.settings.lock().unwrap();
+        let filter = self.filter.lock().unwrap();
+        match pspec.name() {
+            "mode" => settings.mode.to_value(),
+            "throttle-interval" => (settings.throttle_interval.as_millis() as u32).to_value(),
+            "max-missing-frames" => (settings.max_missing_frames as u32).to_value(),
+            "class-ids" => format_list(&filter.class_ids).to_value(),
+            "deny-class-ids" => format_list(&filter.deny_class_ids).to_value(),
+            "labels" => format_list(&filter.labels).to_value(),
+            "deny-labels" => format_list(&filter.deny_labels).to_value(),
+            "min-confidence" => filter.min_confidence.to_value(),
+            "min-width" => filter.min_width.to_value(),
+            "min-height" => filter.min_height.to_value(),
+            "source-ids" => format_list(&filter.source_ids).to_value(),
+            "frame-interval" => filter.frame_interval.to_value(),
+            _ => unreachable!(),
+        }
+    }
+}
 
 impl ElementImpl for NVObjconv {
     fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
@@ -68,47 +397,174 @@ impl BaseTransformImpl for NVObjconv {
     const PASSTHROUGH_ON_SAME_CAPS: bool = false;
     const TRANSFORM_IP_ON_PASSTHROUGH: bool = false;
 
+    fn stop(&self, _element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        let mut state = self.state.lock().unwrap();
+        state.lifecycles.clear();
+        state.frames.clear();
+        Ok(())
+    }
+
+    fn sink_event(&self, element: &Self::Type, event: gst::Event) -> bool {
+        // the exits are sent before the event, there are no more frames of
+        // the ended sources to attach them to
+        let exits = {
+            let mut state = self.state.lock().unwrap();
+            let sources = if let Some(stream_event) = StreamEvent::parse(&event) {
+                let source_id = stream_event.source_id();
+                state.frames.remove(&source_id);
+                vec![source_id]
+            } else if event.type_() == gst::EventType::Eos {
+                state.lifecycles.sources()
+            } else {
+                Vec::new()
+            };
+            let exits = Self::end_sources(&mut state, sources);
+            (!exits.is_empty()).then(|| Self::exits_buffer(exits, state.nvds))
+        };
+        if let Some(buffer) = exits {
+            let srcpad = element.static_pad("src").expect("Failed to get srcpad");
+            if let Err(err) = srcpad.push(buffer) {
+                gst::gst_warning!(CAT, obj: element, "Failed to send the exits: {:?}", err);
+            }
+        }
+        self.parent_sink_event(element, event)
+    }
+
     fn transform_ip(
         &self,
         _element: &Self::Type,
         buf: &mut gst::BufferRef,
     ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let settings = *self.settings.lock().unwrap();
+        let filter = self.filter.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
         for mut meta in buf.iter_meta_mut::<DsMeta>() {
-            if let GstNvDsMetaType::BatchGstMeta = meta.meta_type() {
-                let ts = Utc::now().to_rfc3339();
-
-                let mut batch_meta = meta.batch_meta().unwrap();
-                for mut frame in batch_meta.iter_frame() {
-                    for mut obj in frame.iter_objects() {
-                        let tracking_id = match obj.object_id() {
-                            Some(id) => id.try_into().expect(&format!("Invalid object id: {}", id)),
-                            None => -1,
-                        };
-                        let msg_meta = NvDsEventMsgMeta::new(
-                            NvDsRect::new(
-                                obj.rect_params().top,
-                                obj.rect_params().left,
-                                obj.rect_params().width,
-                                obj.rect_params().height,
-                            ),
-                            obj.class_id(),
-                            obj.obj_label(),
-                            frame.source_id().try_into().unwrap(),
-                            frame.frame_number(),
-                            f64::from(obj.confidence()),
-                            tracking_id,
-                            &ts,
-                        );
-
-                        let mut user_meta = batch_meta.acquire_user_meta::<NvDsEventMsgMeta>();
-
-                        user_meta.set_data(msg_meta);
-
-                        frame.add_user_meta(user_meta);
-                    }
-                }
+            if let Some(batch_meta) = meta.batch_meta() {
+                state.nvds = true;
+                Self::process_batch(&settings, &filter, &mut state, batch_meta);
             }
         }
+        for mut meta in buf.iter_meta_mut::<BatchGstMeta>() {
+            state.nvds = false;
+            Self::process_batch(&settings, &filter, &mut state, meta.batch_mut());
+        }
         Ok(gst::FlowSuccess::Ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deepstream::meta_schema::NVDS_EVENT_EXIT;
+    use deepstream::owned_meta::ObjectMeta;
+
+    fn object(label: &str, object_id: u64) -> ObjectMeta {
+        ObjectMeta::builder()
+            .label(label)
+            .object_id(object_id)
+            .bbox(0.0, 0.0, 100.0, 100.0)
+            .confidence(0.9)
+            .build()
+    }
+
+    /// Labels and tracking ids of the messages of every frame of `batch`.
+    fn messages(batch: &mut BatchMeta) -> Vec<Vec<(String, i32)>> {
+        batch
+            .frames_mut()
+            .iter()
+            .map(|frame| {
+                frame
+                    .iter_user_meta::<NvDsEventMsgMeta>()
+                    .map(|msg| (msg.obj_class_label().to_owned(), msg.tracking_id()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn owned_batch() {
+        let filter = Filter {
+            deny_labels: vec!["bicycle".to_string()],
+            ..Filter::default()
+        };
+        let mut state = State::default();
+        let mut batch = BatchMeta::builder()
+            .frame(
+                FrameMeta::builder()
+                    .source_id(0)
+                    .object(object("car", 1))
+                    .object(object("bicycle", 2))
+                    .build(),
+            )
+            .frame(
+                FrameMeta::builder()
+                    .source_id(1)
+                    .object(object("person", 3))
+                    .build(),
+            )
+            .build();
+
+        NVObjconv::process_batch(&Settings::default(), &filter, &mut state, &mut batch);
+
+        assert_eq!(
+            messages(&mut batch),
+            vec![
+                vec![("car".to_string(), 1)],
+                vec![("person".to_string(), 3)],
+            ]
+        );
+        assert_eq!(state.frames[&0], 1);
+    }
+
+    #[test]
+    fn ended_sources_exits() {
+        gst::init().unwrap();
+
+        let mut state = State::default();
+        let mut batch = BatchMeta::builder()
+            .frame(
+                FrameMeta::builder()
+                    .source_id(0)
+                    .object(object("car", 1))
+                    .build(),
+            )
+            .frame(
+                FrameMeta::builder()
+                    .source_id(1)
+                    .object(object("person", 3))
+                    .build(),
+            )
+            .build();
+        NVObjconv::process_batch(
+            &Settings::default(),
+            &Filter::default(),
+            &mut state,
+            &mut batch,
+        );
+
+        let sources = state.lifecycles.sources();
+        assert_eq!(sources, vec![0, 1]);
+        let exits = NVObjconv::end_sources(&mut state, sources);
+        assert!(state.lifecycles.sources().is_empty());
+
+        // a frame per source, without waiting for a next batch
+        let buffer = NVObjconv::exits_buffer(exits, false);
+        let mut batch = buffer.meta::<BatchGstMeta>().unwrap().batch().clone();
+        let source_ids: Vec<u32> = batch
+            .frames_mut()
+            .iter()
+            .map(|frame| frame.source_id())
+            .collect();
+        assert_eq!(source_ids, vec![0, 1]);
+        assert!(batch.frames_mut().iter().all(|frame| frame
+            .iter_user_meta::<NvDsEventMsgMeta>()
+            .all(|msg| msg.event_type() == NVDS_EVENT_EXIT)));
+        assert_eq!(
+            messages(&mut batch),
+            vec![
+                vec![("car".to_string(), 1)],
+                vec![("person".to_string(), 3)],
+            ]
+        );
+    }
+}
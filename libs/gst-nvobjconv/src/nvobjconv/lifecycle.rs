@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use gst::glib;
+
+use deepstream::meta_schema::{
+    NvDsEventMsgMeta, NVDS_EVENT_ENTRY, NVDS_EVENT_EXIT, NVDS_EVENT_MOVING,
+};
+
+/// Which objects are sent, the untracked objects are only sent per frame.
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Copy, glib::GEnum)]
+#[repr(u32)]
+#[genum(type_name = "GstNVObjconvMode")]
+pub enum Mode {
+    #[default]
+    #[genum(name = "Every object of every frame", nick = "per-frame")]
+    PerFrame = 0,
+    #[genum(
+        name = "Entry and exit of the tracks, moving at most once per throttle interval",
+        nick = "per-track"
+    )]
+    PerTrack = 1,
+    #[genum(
+        name = "Entry and exit of the tracks, moving when their labels change",
+        nick = "on-change"
+    )]
+    OnChange = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub mode: Mode,
+    /// Minimum time between two moving events of a track.
+    pub throttle_interval: Duration,
+    /// Frames of its source a track can be missing from before it exits.
+    pub max_missing_frames: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mode: Mode::default(),
+            throttle_interval: Duration::from_secs(1),
+            max_missing_frames: 30,
+        }
+    }
+}
+
+/// Class and labels of an object, without the confidences that change every
+/// frame.
+#[derive(Debug, PartialEq)]
+struct Labels {
+    class_id: i32,
+    label: String,
+    attributes: Vec<(i32, u32, String)>,
+}
+
+impl Labels {
+    fn new(msg_meta: &NvDsEventMsgMeta) -> Self {
+        Labels {
+            class_id: msg_meta.obj_class_id(),
+            label: msg_meta.obj_class_label().to_string(),
+            attributes: msg_meta
+                .attributes()
+                .into_iter()
+                .map(|a| (a.component_id, a.class_id, a.label))
+                .collect(),
+        }
+    }
+}
+
+struct Track {
+    last_seen: u64,
+    /// Timestamp and labels of the last event sent.
+    sent_at: u64,
+    sent_labels: Labels,
+    /// Message of the last frame the object was in, sent again on exit.
+    last: NvDsEventMsgMeta,
+}
+
+/// Tracks of the objects of every source, deciding which objects are sent
+/// and with what event type.
+#[derive(Default)]
+pub struct Lifecycles {
+    frames: HashMap<u32, u64>,
+    tracks: HashMap<(u32, u64), Track>,
+}
+
+impl Lifecycles {
+    /// Messages to send for a frame of `source_id` at `pts` nanoseconds, given
+    /// the tracking id and message of its objects.
+    ///
+    /// Each track is sent as `NVDS_EVENT_ENTRY` on its first frame, then as
+    /// `NVDS_EVENT_MOVING` depending on the mode, and as `NVDS_EVENT_EXIT`
+    /// with its last message once it's missing for too long.
+    pub fn frame(
+        &mut self,
+        settings: &Settings,
+        source_id: u32,
+        pts: u64,
+        objects: Vec<(Option<u64>, NvDsEventMsgMeta)>,
+    ) -> Vec<NvDsEventMsgMeta> {
+        let frame = self.frames.entry(source_id).or_default();
+        *frame += 1;
+        let frame = *frame;
+
+        let mut messages = Vec::new();
+        for (object_id, mut msg_meta) in objects {
+            let object_id = match object_id {
+                Some(object_id) => object_id,
+                None => {
+                    if settings.mode == Mode::PerFrame {
+                        msg_meta.set_event_type(NVDS_EVENT_MOVING);
+                        messages.push(msg_meta);
+                    }
+                    continue;
+                }
+            };
+
+            let event_type = match self.tracks.get_mut(&(source_id, object_id)) {
+                None => {
+                    self.tracks.insert(
+                        (source_id, object_id),
+                        Track {
+                            last_seen: frame,
+                            sent_at: pts,
+                            sent_labels: Labels::new(&msg_meta),
+                            last: msg_meta.clone(),
+                        },
+                    );
+                    Some(NVDS_EVENT_ENTRY)
+                }
+                Some(track) => {
+                    track.last_seen = frame;
+                    track.last = msg_meta.clone();
+                    if Self::sends_moving(settings, track, pts, &msg_meta) {
+                        track.sent_at = pts;
+                        track.sent_labels = Labels::new(&msg_meta);
+                        Some(NVDS_EVENT_MOVING)
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let Some(event_type) = event_type {
+                msg_meta.set_event_type(event_type);
+                messages.push(msg_meta);
+            }
+        }
+
+        messages.extend(self.exit(|(id, _), track| {
+            id == source_id && frame - track.last_seen > settings.max_missing_frames
+        }));
+
+        messages
+    }
+
+    /// Exit messages of all the tracks of a source whose stream ended or was
+    /// reset, its next frame starts new tracks.
+    pub fn end(&mut self, source_id: u32) -> Vec<NvDsEventMsgMeta> {
+        self.frames.remove(&source_id);
+        self.exit(|(id, _), _| id == source_id)
+    }
+
+    /// Sources with tracks, sorted.
+    pub fn sources(&self) -> Vec<u32> {
+        let mut sources: Vec<u32> = self.tracks.keys().map(|(id, _)| *id).collect();
+        sources.sort_unstable();
+        sources.dedup();
+        sources
+    }
+
+    /// Remove the tracks `lost` returns `true` for, returning their exit
+    /// messages sorted by source and tracking id.
+    fn exit<F>(&mut self, lost: F) -> Vec<NvDsEventMsgMeta>
+    where
+        F: Fn((u32, u64), &Track) -> bool,
+    {
+        let mut keys: Vec<(u32, u64)> = self
+            .tracks
+            .iter()
+            .filter(|(key, track)| lost(**key, track))
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort_unstable();
+        keys.into_iter()
+            .map(|key| {
+                let mut msg_meta = self.tracks.remove(&key).unwrap().last;
+                msg_meta.set_event_type(NVDS_EVENT_EXIT);
+                msg_meta
+            })
+            .collect()
+    }
+
+    fn sends_moving(
+        settings: &Settings,
+        track: &Track,
+        pts: u64,
+        msg_meta: &NvDsEventMsgMeta,
+    ) -> bool {
+        // the timestamps go back when a source restarts
+        let throttled = pts >= track.sent_at
+            && pts - track.sent_at < settings.throttle_interval.as_nanos() as u64;
+        match settings.mode {
+            Mode::PerFrame => true,
+            Mode::PerTrack => !throttled,
+            Mode::OnChange => !throttled && Labels::new(msg_meta) != track.sent_labels,
+        }
+    }
+
+    /// Forget all the tracks, without sending their exit.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.tracks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deepstream::meta_schema::{NvDsEventType, NvDsRect};
+
+    /// Event types of `messages`, with their tracking ids.
+    fn events(messages: &[NvDsEventMsgMeta]) -> Vec<(i32, NvDsEventType)> {
+        messages
+            .iter()
+            .map(|m| (m.tracking_id(), m.event_type()))
+            .collect()
+    }
+
+    fn object(object_id: Option<u64>, label: &str) -> (Option<u64>, NvDsEventMsgMeta) {
+        let tracking_id = object_id.map_or(-1, |id| id as i32);
+        let msg_meta = NvDsEventMsgMeta::new(
+            NvDsRect::new(0.0, 0.0, 10.0, 10.0),
+            0,
+            label,
+            1,
+            0,
+            0.9,
+            tracking_id,
+            "2021-01-01T00:00:00Z",
+        );
+        (object_id, msg_meta)
+    }
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn per_track_lifecycle() {
+        let settings = Settings {
+            mode: Mode::PerTrack,
+            throttle_interval: Duration::from_secs(1),
+            max_missing_frames: 1,
+        };
+        let mut lifecycles = Lifecycles::default();
+        let mut frame = |pts, objects| events(&lifecycles.frame(&settings, 1, pts, objects));
+
+        assert_eq!(
+            frame(0, vec![object(Some(7), "car"), object(None, "car")]),
+            vec![(7, NVDS_EVENT_ENTRY)]
+        );
+        assert_eq!(frame(SECOND / 2, vec![object(Some(7), "car")]), vec![]);
+        assert_eq!(
+            frame(SECOND, vec![object(Some(7), "car")]),
+            vec![(7, NVDS_EVENT_MOVING)]
+        );
+        assert_eq!(frame(2 * SECOND, vec![]), vec![]);
+        assert_eq!(frame(3 * SECOND, vec![]), vec![(7, NVDS_EVENT_EXIT)]);
+    }
+
+    #[test]
+    fn on_change_lifecycle() {
+        let settings = Settings {
+            mode: Mode::OnChange,
+            throttle_interval: Duration::ZERO,
+            max_missing_frames: 30,
+        };
+        let mut lifecycles = Lifecycles::default();
+        let mut frame = |pts, objects| events(&lifecycles.frame(&settings, 1, pts, objects));
+
+        assert_eq!(
+            frame(0, vec![object(Some(7), "car")]),
+            vec![(7, NVDS_EVENT_ENTRY)]
+        );
+        assert_eq!(frame(SECOND, vec![object(Some(7), "car")]), vec![]);
+        assert_eq!(
+            frame(2 * SECOND, vec![object(Some(7), "truck")]),
+            vec![(7, NVDS_EVENT_MOVING)]
+        );
+    }
+
+    #[test]
+    fn ended_source_tracks_exit() {
+        let settings = Settings::default();
+        let mut lifecycles = Lifecycles::default();
+        lifecycles.frame(
+            &settings,
+            1,
+            0,
+            vec![object(Some(8), "car"), object(Some(7), "car")],
+        );
+        lifecycles.frame(&settings, 2, 0, vec![object(Some(9), "car")]);
+
+        // without waiting for another frame of the source
+        assert_eq!(
+            events(&lifecycles.end(1)),
+            vec![(7, NVDS_EVENT_EXIT), (8, NVDS_EVENT_EXIT)]
+        );
+        assert!(lifecycles.end(1).is_empty());
+        assert_eq!(
+            events(&lifecycles.frame(&settings, 1, 0, vec![object(Some(7), "car")])),
+            vec![(7, NVDS_EVENT_ENTRY)]
+        );
+        assert_eq!(
+            events(&lifecycles.frame(&settings, 2, SECOND, vec![object(Some(9), "car")])),
+            vec![(9, NVDS_EVENT_MOVING)]
+        );
+    }
+}
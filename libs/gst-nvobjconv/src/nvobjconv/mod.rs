@@ -1,7 +1,9 @@
 use gst::glib;
 use gst::prelude::*;
 
+mod filter;
 mod imp;
+mod lifecycle;
 
 glib::wrapper! {
     pub struct NVObjconv(ObjectSubclass<imp::NVObjconv>) @extends gst_base::BaseTransform, gst::Element, gst::Object;
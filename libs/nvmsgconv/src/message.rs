@@ -2,13 +2,24 @@ use serde::{Deserialize, Serialize};
 use std::ffi::CStr;
 use std::str;
 
-use deepstream_sys::nvdsmeta_schema::NvDsEvent;
+use deepstream_sys::nvdsmeta_schema::{self as ffi, NvDsEvent, NvDsEventType};
 
 #[derive(Serialize, Deserialize)]
 struct Camera {
     id: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct Attribute {
+    classifier_id: i32,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    classifier_type: Option<String>,
+    label_id: i32,
+    class_id: i32,
+    label: String,
+    confidence: f64,
+}
+
 #[derive(Serialize, Deserialize)]
 struct Object {
     id: i32,
@@ -17,6 +28,14 @@ struct Object {
     width: u32,
     height: u32,
     label: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute>,
+    event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zone: Option<String>,
+    /// Seconds the object stayed in the zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dwell_time: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -27,6 +46,19 @@ struct Message {
     objects: Vec<Object>,
 }
 
+fn event_name(event_type: NvDsEventType) -> &'static str {
+    match event_type {
+        ffi::NVDS_EVENT_ENTRY => "entry",
+        ffi::NVDS_EVENT_EXIT => "exit",
+        ffi::NVDS_EVENT_MOVING => "moving",
+        ffi::NVDS_EVENT_STOPPED => "stopped",
+        ffi::NVDS_EVENT_EMPTY => "empty",
+        ffi::NVDS_EVENT_PARKED => "parked",
+        ffi::NVDS_EVENT_RESET => "reset",
+        _ => "custom",
+    }
+}
+
 pub fn generate_message(events: &[NvDsEvent]) -> String {
     let mut objects = Vec::new();
 
@@ -37,6 +69,28 @@ pub fn generate_message(events: &[NvDsEvent]) -> String {
         let c_str: &CStr = unsafe { CStr::from_ptr(meta.obj_class_label) };
         let str_slice: &str = c_str.to_str().unwrap();
         let label: String = str_slice.to_owned();
+        let attributes = if meta.attributes.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(meta.attributes, meta.num_attributes as usize) }
+        };
+        let attributes = attributes
+            .iter()
+            .map(|attribute| Attribute {
+                classifier_id: attribute.classifier_id,
+                classifier_type: (!attribute.classifier_type.is_null()).then(|| {
+                    unsafe { CStr::from_ptr(attribute.classifier_type) }
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                label_id: attribute.label_id,
+                class_id: attribute.class_id,
+                label: unsafe { CStr::from_ptr(attribute.label) }
+                    .to_string_lossy()
+                    .into_owned(),
+                confidence: attribute.confidence,
+            })
+            .collect();
         let obj = Object {
             id: meta.tracking_id,
             x: meta.bbox.left as u32,
@@ -44,6 +98,14 @@ pub fn generate_message(events: &[NvDsEvent]) -> String {
             width: meta.bbox.width as u32,
             height: meta.bbox.height as u32,
             label,
+            attributes,
+            event: event_name(meta.event_type).to_string(),
+            zone: (!meta.zone.is_null()).then(|| {
+                unsafe { CStr::from_ptr(meta.zone) }
+                    .to_string_lossy()
+                    .into_owned()
+            }),
+            dwell_time: (!meta.zone.is_null()).then_some(meta.dwell_time),
         };
 
         objects.push(obj);
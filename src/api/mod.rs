@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Error};
+use log::{error, info};
+use serde_json::json;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::common::SourceId;
+use crate::pipeline::config::{ApiConfig, SourceConfig};
+use crate::pipeline_manager::{Command, CommandError, CommandResult, Event, Reply};
+
+/// Time to wait for the manager to handle a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq)]
+pub enum Body {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+/// Status code and body of a response.
+pub type ApiResponse = (u16, Body);
+
+/// Start the HTTP control api on its own thread, forwarding the requests to
+/// the manager through `events`.
+pub fn start(config: &ApiConfig, events: mpsc::Sender<Event>) -> Result<(), Error> {
+    let address = format!("{}:{}", config.host, config.port);
+    let server = Server::http(&address).map_err(|e| anyhow!("Cant start api server: {}", e))?;
+    info!("Control api listening at http://{}", address);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle_request(request, &events);
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_request(mut request: Request, events: &mpsc::Sender<Event>) {
+    let (status, body) = route(&mut request, events);
+
+    let (content_type, body) = match body {
+        Body::Json(value) => ("application/json", value.to_string()),
+        Body::Text(text) => ("text/plain; version=0.0.4", text),
+    };
+    let header = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap();
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        error!("Cant send api response: {}", e);
+    }
+}
+
+/// Response to `request`, from the manager listening to `events` if it's a
+/// command.
+pub fn route(request: &mut Request, events: &mpsc::Sender<Event>) -> ApiResponse {
+    let url = request.url().to_string();
+    let segments: Vec<&str> = url
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["status"]) => send(events, Command::Status),
+        (Method::Get, ["metrics"]) => send(events, Command::Metrics),
+        (Method::Get, ["sources"]) => send(events, Command::ListSources),
+        (Method::Post, ["sources"]) => match read_json::<SourceConfig>(request) {
+            Ok(config) => send(events, Command::AddOrUpdateSource(config)),
+            Err(e) => error_response(400, &e.to_string()),
+        },
+        (Method::Get, ["sources", id]) => match parse_id(id) {
+            Ok(id) => send(events, Command::GetSource(id)),
+            Err(response) => response,
+        },
+        (Method::Delete, ["sources", id]) => match parse_id(id) {
+            Ok(id) => send(events, Command::RemoveSource(id)),
+            Err(response) => response,
+        },
+        _ => error_response(404, &format!("No route for {} {}", request.method(), url)),
+    }
+}
+
+fn parse_id(id: &str) -> Result<SourceId, ApiResponse> {
+    id.parse()
+        .map_err(|_| error_response(400, &format!("Invalid source id {}", id)))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(request: &mut Request) -> Result<T, Error> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body)?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Send `command` to the manager and wait for its result.
+fn send(events: &mpsc::Sender<Event>, command: Command) -> ApiResponse {
+    let (reply_sender, reply) = mpsc::channel();
+    if events.send(Event::Command(command, reply_sender)).is_err() {
+        return error_response(503, "Pipeline manager not running");
+    }
+
+    match reply.recv_timeout(COMMAND_TIMEOUT) {
+        Ok(result) => command_response(result),
+        Err(_) => error_response(504, "Pipeline manager did not reply"),
+    }
+}
+
+pub fn command_response(result: CommandResult) -> ApiResponse {
+    match result {
+        Ok(Reply::Done) => (200, Body::Json(json!({ "ok": true }))),
+        Ok(Reply::Sources(sources)) => (200, Body::Json(json!(sources))),
+        Ok(Reply::Source(source)) => (200, Body::Json(json!(source))),
+        Ok(Reply::Status(status)) => (200, Body::Json(json!(status))),
+        Ok(Reply::Metrics(metrics)) => (200, Body::Text(metrics)),
+        Err(e @ CommandError::SourceNotFound(_)) => error_response(404, &e.to_string()),
+        Err(e @ CommandError::InvalidConfig(_)) => error_response(400, &e.to_string()),
+        Err(e @ CommandError::Failed(_)) => error_response(500, &e.to_string()),
+    }
+}
+
+fn error_response(status: u16, message: &str) -> ApiResponse {
+    (status, Body::Json(json!({ "error": message })))
+}
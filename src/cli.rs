@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Error};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::process::ExitCode;
+
+use crate::common::SourceId;
+use crate::logging;
+use crate::pipeline::config::{HashableF64, PipelineConfig, Secret, SourceConfig, SourceKind};
+use crate::pipeline::description;
+use crate::pipeline_manager::PipelineManager;
+
+const DEFAULT_CONFIG: &str = "config/pipeline_config.yml";
+
+#[derive(Parser)]
+#[command(version, about = "DeepStream pipeline manager")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the pipeline, reloading the sources when the config changes
+    Run {
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+    },
+    /// Parse and validate a config, without starting the pipeline
+    Validate { config: String },
+    /// Print the gst-launch style description of the pipeline a config builds
+    PrintPipeline { config: String },
+    /// Manage the sources of a running pipeline through its control api
+    Sources {
+        /// Control api url
+        #[arg(long, default_value = "http://localhost:8000")]
+        url: String,
+        #[command(subcommand)]
+        command: SourcesCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum SourcesCommand {
+    /// List the sources with their config and FPS
+    List,
+    /// Add a source, or update it if the id already exists
+    Add {
+        id: SourceId,
+        #[arg(value_enum)]
+        kind: SourceType,
+        /// Required by the uri and rtsp sources
+        #[arg(long)]
+        uri: Option<String>,
+        #[arg(long)]
+        username: Option<String>,
+        #[arg(long)]
+        password: Option<String>,
+        /// Required by the file sources
+        #[arg(long)]
+        path: Option<String>,
+        /// Loop the file source
+        #[arg(long = "loop")]
+        looping: bool,
+        /// Seconds
+        #[arg(long)]
+        start_offset: Option<f64>,
+        /// Seconds
+        #[arg(long)]
+        end_offset: Option<f64>,
+        #[arg(long)]
+        playback_rate: Option<f64>,
+    },
+    /// Remove a source
+    Remove { id: SourceId },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SourceType {
+    Test,
+    Uri,
+    Rtsp,
+    File,
+}
+
+impl Cli {
+    /// Run the command, returning the process exit code.
+    pub fn run(self) -> ExitCode {
+        let command = self.command.unwrap_or(Command::Run {
+            config: DEFAULT_CONFIG.to_string(),
+        });
+
+        let result = match command {
+            Command::Run { config } => run(&config),
+            Command::Validate { config } => validate(&config),
+            Command::PrintPipeline { config } => print_pipeline(&config),
+            Command::Sources { url, command } => sources(url.trim_end_matches('/'), command),
+        };
+
+        match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error! {}", e);
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+fn run(config: &str) -> Result<(), Error> {
+    logging::init();
+
+    let mut manager = PipelineManager::new(config)
+        .map_err(|e| anyhow!("Error instancing pipeline manager. {}", e))?;
+
+    manager.run()
+}
+
+fn load_config(config: &str) -> Result<PipelineConfig, Error> {
+    PipelineConfig::from_file(config).map_err(|e| anyhow!("{}: {}", config, e))
+}
+
+fn validate(config: &str) -> Result<(), Error> {
+    load_config(config)?;
+    println!("{}: valid", config);
+
+    Ok(())
+}
+
+fn print_pipeline(config: &str) -> Result<(), Error> {
+    let config = load_config(config)?;
+    println!("{}", description::describe(&config));
+
+    Ok(())
+}
+
+/// Json of a source for the control api.
+///
+/// The secrets are redacted when serialized, so the credentials given to the
+/// cli are put back, the running instance takes them literally.
+pub fn source_body(config: &SourceConfig) -> Result<serde_json::Value, Error> {
+    let mut body = serde_json::to_value(config)?;
+    if let SourceKind::Uri {
+        username, password, ..
+    }
+    | SourceKind::Rtsp {
+        username, password, ..
+    } = &config.kind
+    {
+        for (field, secret) in &[("username", username), ("password", password)] {
+            if let Some(secret) = secret {
+                body["kind"][*field] = json!(secret.expose());
+            }
+        }
+    }
+
+    Ok(body)
+}
+
+fn sources(url: &str, command: SourcesCommand) -> Result<(), Error> {
+    let response = match command {
+        SourcesCommand::List => ureq::get(&format!("{}/sources", url)).call(),
+        SourcesCommand::Add {
+            id,
+            kind,
+            uri,
+            username,
+            password,
+            path,
+            looping,
+            start_offset,
+            end_offset,
+            playback_rate,
+        } => {
+            let uri = || uri.clone().ok_or_else(|| anyhow!("--uri is required"));
+            let username = username.map(Secret::new);
+            let password = password.map(Secret::new);
+            let kind = match kind {
+                SourceType::Test => SourceKind::Test,
+                SourceType::Uri => SourceKind::Uri {
+                    uri: uri()?,
+                    username,
+                    password,
+                },
+                SourceType::Rtsp => SourceKind::Rtsp {
+                    uri: uri()?,
+                    username,
+                    password,
+                    reconnect: Default::default(),
+                },
+                SourceType::File => SourceKind::File {
+                    path: path.ok_or_else(|| anyhow!("--path is required"))?,
+                    looping,
+                    start_offset: start_offset.map(HashableF64),
+                    end_offset: end_offset.map(HashableF64),
+                    playback_rate: playback_rate.map(HashableF64),
+                },
+            };
+            // validated by the server, the file of a source is on its side
+            let config = SourceConfig { id, kind };
+            ureq::post(&format!("{}/sources", url))
+                .set("Content-Type", "application/json")
+                .send_string(&source_body(&config)?.to_string())
+        }
+        SourcesCommand::Remove { id } => ureq::delete(&format!("{}/sources/{}", url, id)).call(),
+    };
+
+    match response {
+        Ok(response) => {
+            println!("{}", response.into_string()?);
+            Ok(())
+        }
+        Err(ureq::Error::Status(status, response)) => Err(anyhow!(
+            "{} {}",
+            status,
+            response.into_string().unwrap_or_default()
+        )),
+        Err(e) => Err(anyhow!("Cant reach the control api at {}: {}", url, e)),
+    }
+}
@@ -1,3 +1,8 @@
+use clap::Parser;
+use std::process::ExitCode;
+
+mod api;
+mod cli;
 mod common;
 mod logging;
 mod pipeline;
@@ -6,16 +11,7 @@ mod pipeline_manager;
 #[cfg(test)]
 mod test;
 
-fn main() {
-    // Init logging
-    logging::init();
-    let mut manger = match pipeline_manager::PipelineManager::new("config/pipeline_config.yml") {
-        Ok(manger) => manger,
-        Err(e) => panic!("Error instancing pipeline manager {}", e),
-    };
-
-    match manger.run() {
-        Ok(r) => r,
-        Err(e) => eprintln!("Error! {}", e),
-    }
+fn main() -> ExitCode {
+    pipeline::filters::processor::register_builtin_processors();
+    cli::Cli::parse().run()
 }
@@ -0,0 +1,77 @@
+//! Geometry of the lines and zones, in pixels of the streammux frames with
+//! the y axis going down.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+}
+
+impl From<(u32, u32)> for Point {
+    fn from((x, y): (u32, u32)) -> Self {
+        Point::new(x as f32, y as f32)
+    }
+}
+
+/// Side a line is crossed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From the left to the right of the line, looking from its start to its end.
+    In,
+    Out,
+}
+
+/// Which side of the line from `a` to `b` `p` is on, positive on the right,
+/// negative on the left and 0 on the line.
+fn side(a: Point, b: Point, p: Point) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Direction of the move from `prev` to `cur` across the segment from `a`
+/// to `b`, `None` if it doesn't cross it.
+///
+/// A point on the line counts as on its right side, so a move ending on the
+/// line and the next one leaving it are a single crossing.
+pub fn crossing(a: Point, b: Point, prev: Point, cur: Point) -> Option<Direction> {
+    let prev_right = side(a, b, prev) >= 0.0;
+    let cur_right = side(a, b, cur) >= 0.0;
+    if prev_right == cur_right {
+        return None;
+    }
+
+    // the move must also cross the line within the segment
+    let a_side = side(prev, cur, a);
+    let b_side = side(prev, cur, b);
+    if a_side != 0.0 && b_side != 0.0 && (a_side > 0.0) == (b_side > 0.0) {
+        return None;
+    }
+
+    match cur_right {
+        true => Some(Direction::In),
+        false => Some(Direction::Out),
+    }
+}
+
+/// Whether `p` is inside `polygon`, by ray casting.
+pub fn contains(polygon: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, pi) in polygon.iter().enumerate() {
+        let pj = polygon[j];
+        if (pi.y > p.y) != (pj.y > p.y) && p.x < (pj.x - pi.x) * (p.y - pi.y) / (pj.y - pi.y) + pi.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
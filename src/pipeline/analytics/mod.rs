@@ -0,0 +1,162 @@
+//! Line crossing and zone analytics, from the tracker ids of the objects.
+//!
+//! The geometry and the tracking of the objects don't depend on DeepStream,
+//! the [`Analytics`] processor feeds them the objects of the frames and
+//! attaches the events and counts to the frames as [`AnalyticsFrameMeta`].
+//!
+//! The objects loitering in a zone, and leaving it after, are also sent to the
+//! message broker as `NvDsEventMsgMeta`s of the frames.
+
+use chrono::Utc;
+use ds::meta_schema::{
+    NvDsEventMsgMeta, NvDsEventType, NvDsRect, NVDS_EVENT_EXIT, NVDS_EVENT_STOPPED,
+};
+use ds::user_meta::UserMetaData;
+use log::warn;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use super::config::AnalyticsConfig;
+use super::filters::processor::{Frame, FrameProcessor};
+use super::metrics::{AnalyticsCounters, SourceAnalyticsCounts};
+
+pub mod geometry;
+pub mod tracker;
+
+pub use geometry::{Direction, Point};
+pub use tracker::{AnalyticsEvent, EventKind, ObjectPosition, SourceAnalytics};
+
+/// Events of a frame and the counts of its source, attached to the frame user
+/// metas.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AnalyticsFrameMeta {
+    pub events: Vec<AnalyticsEvent>,
+    pub counts: SourceAnalyticsCounts,
+}
+
+impl UserMetaData for AnalyticsFrameMeta {
+    const DESCRIPTOR: &'static str = "DSRS.ANALYTICS.FRAME";
+}
+
+/// Runs the analytics of the sources of its config, the other sources are
+/// left as they are.
+pub struct Analytics {
+    config: AnalyticsConfig,
+    counters: AnalyticsCounters,
+}
+
+impl Analytics {
+    pub fn new(config: AnalyticsConfig, counters: AnalyticsCounters) -> Self {
+        Analytics { config, counters }
+    }
+}
+
+impl FrameProcessor for Analytics {
+    type State = ();
+
+    fn process_frame(&mut self, frame: &mut Frame, _state: &mut ()) {
+        let source_id = frame.source_id();
+        let config = match self
+            .config
+            .sources
+            .iter()
+            .find(|source| source.source_id == source_id)
+        {
+            Some(config) => config,
+            None => return,
+        };
+
+        // untracked objects can't be followed
+        let objects: Vec<ObjectPosition> = frame
+            .frame_meta
+            .objects()
+            .filter_map(|object| {
+                let bbox = object.bbox();
+                Some(ObjectPosition {
+                    object_id: object.object_id()?,
+                    class_id: object.class_id(),
+                    position: Point::new(bbox.left + bbox.width / 2.0, bbox.top + bbox.height),
+                })
+            })
+            .collect();
+        let timestamp = Duration::from_nanos(frame.frame_meta.buf_pts());
+        let (events, counts) = self.counters.update(
+            source_id,
+            config,
+            self.config.max_missing_frames,
+            timestamp,
+            &objects,
+        );
+
+        let ts = Utc::now().to_rfc3339();
+        for event in &events {
+            let (event_type, dwell) = match event.kind {
+                EventKind::Loitering { dwell } => (NVDS_EVENT_STOPPED, dwell),
+                EventKind::ZoneExit {
+                    dwell,
+                    loitering: true,
+                } => (NVDS_EVENT_EXIT, dwell),
+                _ => continue,
+            };
+            if let Some(msg_meta) = loitering_msg_meta(frame, event, event_type, dwell, &ts) {
+                frame.attach_user_meta(msg_meta);
+            }
+        }
+
+        frame.attach_user_meta(AnalyticsFrameMeta { events, counts });
+    }
+}
+
+/// Message of a loitering `event`, the box and label are left empty when the
+/// object is no longer in the frame. `None` if the object id doesn't fit the
+/// tracking id of the message.
+fn loitering_msg_meta(
+    frame: &Frame,
+    event: &AnalyticsEvent,
+    event_type: NvDsEventType,
+    dwell: f64,
+    ts: &str,
+) -> Option<NvDsEventMsgMeta> {
+    let tracking_id = match i32::try_from(event.object_id) {
+        Ok(tracking_id) => tracking_id,
+        Err(_) => {
+            warn!(
+                "Loitering of object {} of source {} not sent, its id doesn't fit a tracking id",
+                event.object_id,
+                frame.source_id()
+            );
+            return None;
+        }
+    };
+    let object = frame
+        .frame_meta
+        .objects()
+        .find(|object| object.object_id() == Some(event.object_id));
+    let (bbox, label, confidence) = match object {
+        Some(object) => {
+            let bbox = object.bbox();
+            (
+                NvDsRect::new(bbox.top, bbox.left, bbox.width, bbox.height),
+                object.obj_label().to_string(),
+                object.confidence(),
+            )
+        }
+        None => (NvDsRect::new(0.0, 0.0, 0.0, 0.0), String::new(), 0.0),
+    };
+
+    let mut msg_meta = NvDsEventMsgMeta::new(
+        bbox,
+        event.class_id,
+        &label,
+        frame.source_id() as i32,
+        frame.frame_meta.frame_number(),
+        f64::from(confidence),
+        tracking_id,
+        ts,
+    );
+    msg_meta.set_event_type(event_type);
+    msg_meta.set_zone(Some(&event.name));
+    msg_meta.set_dwell_time(dwell);
+    Some(msg_meta)
+}
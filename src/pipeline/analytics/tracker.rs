@@ -0,0 +1,228 @@
+use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+use super::geometry::{contains, crossing, Direction, Point};
+use crate::pipeline::config::{LineDirection, SourceAnalyticsConfig};
+
+/// Kind of an event, the dwell times are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    LineCrossing {
+        direction: Direction,
+    },
+    ZoneEntry,
+    /// The object stayed in the zone for its loitering threshold.
+    Loitering {
+        dwell: f64,
+    },
+    /// End of the dwell of the object in the zone, `loitering` if it reached
+    /// the loitering threshold.
+    ZoneExit {
+        dwell: f64,
+        loitering: bool,
+    },
+}
+
+/// An object crossing a line, or entering or leaving a zone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalyticsEvent {
+    #[serde(flatten)]
+    pub kind: EventKind,
+    /// Name of the line or zone.
+    pub name: String,
+    pub object_id: u64,
+    pub class_id: i32,
+}
+
+/// A tracked object of a frame, at the bottom center of its box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectPosition {
+    pub object_id: u64,
+    pub class_id: i32,
+    pub position: Point,
+}
+
+struct ZoneStay {
+    entered: Duration,
+    loitering: bool,
+}
+
+impl ZoneStay {
+    fn dwell(&self, now: Duration) -> Duration {
+        // the timestamps go back when a source restarts
+        now.saturating_sub(self.entered)
+    }
+}
+
+struct ObjectState {
+    class_id: i32,
+    position: Point,
+    /// Stays in the zones the object is in, by zone index.
+    zones: BTreeMap<usize, ZoneStay>,
+    last_seen: u64,
+    last_seen_at: Duration,
+}
+
+/// Follows the objects of a source from frame to frame, to find when they
+/// cross its lines and enter or leave its zones.
+pub struct SourceAnalytics {
+    config: SourceAnalyticsConfig,
+    zones: Vec<Vec<Point>>,
+    max_missing_frames: u64,
+    frame: u64,
+    objects: HashMap<u64, ObjectState>,
+}
+
+impl SourceAnalytics {
+    pub fn new(config: SourceAnalyticsConfig, max_missing_frames: u32) -> Self {
+        let zones = config
+            .zones
+            .iter()
+            .map(|zone| zone.points.iter().map(|p| Point::from(*p)).collect())
+            .collect();
+
+        SourceAnalytics {
+            config,
+            zones,
+            max_missing_frames: max_missing_frames as u64,
+            frame: 0,
+            objects: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &SourceAnalyticsConfig {
+        &self.config
+    }
+
+    pub fn max_missing_frames(&self) -> u32 {
+        self.max_missing_frames as u32
+    }
+
+    /// Events of the objects of the next frame of the source, at `timestamp`,
+    /// the objects missing for too long leave their zones.
+    pub fn update(
+        &mut self,
+        timestamp: Duration,
+        objects: &[ObjectPosition],
+    ) -> Vec<AnalyticsEvent> {
+        self.frame += 1;
+        let mut events = Vec::new();
+
+        let class_ids = &self.config.class_ids;
+        for object in objects
+            .iter()
+            .filter(|o| class_ids.is_empty() || class_ids.contains(&o.class_id))
+        {
+            let event = |kind, name: &str| AnalyticsEvent {
+                kind,
+                name: name.to_string(),
+                object_id: object.object_id,
+                class_id: object.class_id,
+            };
+            let zones: BTreeSet<usize> = self
+                .zones
+                .iter()
+                .enumerate()
+                .filter(|(_, zone)| contains(zone, object.position))
+                .map(|(i, _)| i)
+                .collect();
+            let state = self
+                .objects
+                .entry(object.object_id)
+                .or_insert_with(|| ObjectState {
+                    class_id: object.class_id,
+                    position: object.position,
+                    zones: BTreeMap::new(),
+                    last_seen: 0,
+                    last_seen_at: timestamp,
+                });
+
+            for line in &self.config.lines {
+                let counted = |direction: &Direction| match line.direction {
+                    LineDirection::Both => true,
+                    LineDirection::In => *direction == Direction::In,
+                    LineDirection::Out => *direction == Direction::Out,
+                };
+                if let Some(direction) = crossing(
+                    line.from.into(),
+                    line.to.into(),
+                    state.position,
+                    object.position,
+                )
+                .filter(counted)
+                {
+                    events.push(event(EventKind::LineCrossing { direction }, &line.name));
+                }
+            }
+            for i in &zones {
+                let zone = &self.config.zones[*i];
+                match state.zones.entry(*i) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(ZoneStay {
+                            entered: timestamp,
+                            loitering: false,
+                        });
+                        events.push(event(EventKind::ZoneEntry, &zone.name));
+                    }
+                    Entry::Occupied(mut entry) => {
+                        let stay = entry.get_mut();
+                        let dwell = stay.dwell(timestamp);
+                        let threshold = zone.loitering_threshold.map(u64::from);
+                        if !stay.loitering && threshold.is_some_and(|t| dwell.as_secs() >= t) {
+                            stay.loitering = true;
+                            let dwell = dwell.as_secs_f64();
+                            events.push(event(EventKind::Loitering { dwell }, &zone.name));
+                        }
+                    }
+                }
+            }
+            let left: Vec<usize> = state
+                .zones
+                .keys()
+                .filter(|i| !zones.contains(i))
+                .copied()
+                .collect();
+            for i in left {
+                let stay = state.zones.remove(&i).unwrap();
+                let kind = EventKind::ZoneExit {
+                    dwell: stay.dwell(timestamp).as_secs_f64(),
+                    loitering: stay.loitering,
+                };
+                events.push(event(kind, &self.config.zones[i].name));
+            }
+
+            state.class_id = object.class_id;
+            state.position = object.position;
+            state.last_seen = self.frame;
+            state.last_seen_at = timestamp;
+        }
+
+        let mut lost: Vec<u64> = self
+            .objects
+            .iter()
+            .filter(|(_, state)| self.frame - state.last_seen > self.max_missing_frames)
+            .map(|(id, _)| *id)
+            .collect();
+        lost.sort_unstable();
+        for object_id in lost {
+            let state = self.objects.remove(&object_id).unwrap();
+            // the dwell ends when the object was last seen
+            for (i, stay) in state.zones {
+                events.push(AnalyticsEvent {
+                    kind: EventKind::ZoneExit {
+                        dwell: stay.dwell(state.last_seen_at).as_secs_f64(),
+                        loitering: stay.loitering,
+                    },
+                    name: self.config.zones[i].name.clone(),
+                    object_id,
+                    class_id: state.class_id,
+                });
+            }
+        }
+
+        events
+    }
+}
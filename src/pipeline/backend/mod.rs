@@ -0,0 +1,80 @@
+use anyhow::Error;
+use std::sync::Arc;
+
+use super::common::MissingElement;
+use super::config::{Backend, InferConfig, MsgBrokerSinkConfig, ObjconvConfig, StreamMuxConfig};
+
+mod nvidia;
+mod software;
+
+pub use nvidia::NvidiaFactory;
+pub use software::SoftwareFactory;
+
+/// Creates the backend specific elements of the pipeline.
+///
+/// Every method returns an element already configured, so the callers only
+/// have to add it to a bin and link it.
+pub trait ElementFactory: Send + Sync {
+    /// Element that batches the sources, with a `sink_%u` request pad per source.
+    fn streammux(&self, config: &StreamMuxConfig) -> Result<gst::Element, Error>;
+
+    /// Inference stage, primary or secondary.
+    fn inference(&self, config: &InferConfig) -> Result<gst::Element, Error>;
+
+    /// Element forwarding the buffers, a frame processor runs on its src pad.
+    fn frame_processor(&self) -> Result<gst::Element, Error>;
+
+    /// Object tracker stage.
+    fn tracker(
+        &self,
+        lib_path: Option<&str>,
+        config_path: Option<&str>,
+    ) -> Result<gst::Element, Error>;
+
+    fn video_convert(&self) -> Result<gst::Element, Error>;
+
+    /// On screen display, draws the objects metadata on the frames.
+    fn osd(&self) -> Result<gst::Element, Error>;
+
+    /// Composes the batch frames in a single frame.
+    fn tiler(&self) -> Result<gst::Element, Error>;
+
+    fn video_sink(&self) -> Result<gst::Element, Error>;
+
+    /// Element that splits the batch back by source, with a `src_%u` request
+    /// pad per source.
+    fn stream_demux(&self) -> Result<gst::Element, Error>;
+
+    fn h264_encoder(&self) -> Result<gst::Element, Error>;
+
+    /// Source bin that decodes `uri`, exposing the video pads with `pad-added`.
+    fn uri_decoder(&self, uri: &str) -> Result<gst::Element, Error>;
+
+    /// Converts the objects metadata kept by `config` to event messages
+    /// metadata.
+    fn object_converter(&self, config: &ObjconvConfig) -> Result<gst::Element, Error>;
+
+    /// Converts the event messages metadata to payloads.
+    fn message_converter(&self) -> Result<gst::Element, Error>;
+
+    /// Sends the payloads to the message broker.
+    fn message_broker(&self, config: &MsgBrokerSinkConfig) -> Result<gst::Element, Error>;
+
+    /// Caps feature of the decoded video buffers, if any.
+    fn memory_feature(&self) -> Option<&'static str>;
+}
+
+/// Create the element factory of `backend`.
+pub fn create_factory(backend: Backend) -> Arc<dyn ElementFactory> {
+    match backend {
+        Backend::Nvidia => Arc::new(NvidiaFactory),
+        Backend::Software => Arc::new(SoftwareFactory),
+    }
+}
+
+fn make_element(factory_name: &'static str) -> Result<gst::Element, Error> {
+    let elem =
+        gst::ElementFactory::make(factory_name, None).map_err(|_| MissingElement(factory_name))?;
+
+    Ok(elem)
+}
@@ -0,0 +1,145 @@
+use anyhow::Error;
+use gst::prelude::*;
+
+use super::super::config::{InferConfig, MsgBrokerSinkConfig, ObjconvConfig, StreamMuxConfig};
+use super::{make_element, ElementFactory};
+
+/// DeepStream elements, requires a NVIDIA GPU.
+pub struct NvidiaFactory;
+
+impl ElementFactory for NvidiaFactory {
+    fn streammux(&self, config: &StreamMuxConfig) -> Result<gst::Element, Error> {
+        let streammux = make_element("nvstreammux")?;
+
+        // Set propertys
+        streammux.set_property("batch-size", config.batch_size)?;
+        streammux.set_property("enable-padding", config.enable_padding)?;
+        streammux.set_property("live-source", true)?;
+        streammux.set_property("width", config.width)?;
+        streammux.set_property("height", config.height)?;
+
+        Ok(streammux)
+    }
+
+    fn inference(&self, config: &InferConfig) -> Result<gst::Element, Error> {
+        let gie = make_element("nvinfer")?;
+
+        gie.set_property("config-file-path", config.config_path.as_str())?;
+        if let Some(unique_id) = config.gie_unique_id {
+            gie.set_property("unique-id", unique_id)?;
+        }
+        if let Some(gie_id) = config.operate_on_gie_id {
+            gie.set_property_from_str("process-mode", "secondary");
+            gie.set_property("infer-on-gie-id", gie_id as i32)?;
+        }
+        if !config.operate_on_class_ids.is_empty() {
+            let class_ids: Vec<String> = config
+                .operate_on_class_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect();
+            gie.set_property("infer-on-class-ids", class_ids.join(":"))?;
+        }
+        if let Some(interval) = config.interval {
+            gie.set_property("interval", interval)?;
+        }
+        if let Some(batch_size) = config.batch_size {
+            gie.set_property("batch-size", batch_size)?;
+        }
+
+        Ok(gie)
+    }
+
+    fn frame_processor(&self) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn tracker(
+        &self,
+        lib_path: Option<&str>,
+        config_path: Option<&str>,
+    ) -> Result<gst::Element, Error> {
+        let tracker = make_element("nvtracker")?;
+
+        tracker.set_property("tracker-width", 640u32)?;
+        tracker.set_property("tracker-height", 384u32)?;
+
+        tracker.set_property(
+            "ll-lib-file",
+            lib_path
+                .unwrap_or("/opt/nvidia/deepstream/deepstream/lib/libnvds_nvmultiobjecttracker.so"),
+        )?;
+        tracker.set_property(
+            "ll-config-file",
+            config_path.unwrap_or("config/filters/config_tracker_NvDCF_perf.yml"),
+        )?;
+
+        Ok(tracker)
+    }
+
+    fn video_convert(&self) -> Result<gst::Element, Error> {
+        make_element("nvvideoconvert")
+    }
+
+    fn osd(&self) -> Result<gst::Element, Error> {
+        make_element("nvdsosd")
+    }
+
+    fn tiler(&self) -> Result<gst::Element, Error> {
+        make_element("nvmultistreamtiler")
+    }
+
+    fn video_sink(&self) -> Result<gst::Element, Error> {
+        make_element("nveglglessink")
+    }
+
+    fn stream_demux(&self) -> Result<gst::Element, Error> {
+        make_element("nvstreamdemux")
+    }
+
+    fn h264_encoder(&self) -> Result<gst::Element, Error> {
+        make_element("nvv4l2h264enc")
+    }
+
+    fn uri_decoder(&self, uri: &str) -> Result<gst::Element, Error> {
+        let urisrc = make_element("nvurisrcbin")?;
+
+        urisrc.set_property("uri", uri)?;
+        urisrc.set_property("rtsp-reconnect-interval", 10_u32)?;
+
+        Ok(urisrc)
+    }
+
+    fn object_converter(&self, config: &ObjconvConfig) -> Result<gst::Element, Error> {
+        let objconv = make_element("nvobjconv")?;
+
+        for (name, value) in config.properties() {
+            objconv.set_property_from_str(name, &value);
+        }
+
+        Ok(objconv)
+    }
+
+    fn message_converter(&self) -> Result<gst::Element, Error> {
+        make_element("nvmsgconv")
+    }
+
+    fn message_broker(&self, config: &MsgBrokerSinkConfig) -> Result<gst::Element, Error> {
+        let sink = make_element("nvmsgbroker")?;
+
+        sink.set_property(
+            "proto-lib",
+            "/opt/nvidia/deepstream/deepstream/lib/libnvds_kafka_proto.so",
+        )?;
+        sink.set_property("conn-str", format!("{};{}", config.server.expose(), config.port))?;
+        sink.set_property("topic", &config.topic)?;
+        sink.set_property("config", "config/filters/msgbroker_config.txt")?;
+        sink.set_property("sync", false)?;
+
+        Ok(sink)
+    }
+
+    fn memory_feature(&self) -> Option<&'static str> {
+        Some("memory:NVMM")
+    }
+}
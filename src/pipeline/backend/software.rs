@@ -0,0 +1,98 @@
+use anyhow::Error;
+use gst::prelude::*;
+
+use super::super::config::{InferConfig, MsgBrokerSinkConfig, ObjconvConfig, StreamMuxConfig};
+use super::{make_element, ElementFactory};
+
+/// Stock GStreamer elements, runs on any machine without a GPU.
+///
+/// Inference, tracking and the message broker are no-op stages, so no objects
+/// metadata is produced.
+pub struct SoftwareFactory;
+
+impl ElementFactory for SoftwareFactory {
+    fn streammux(&self, _config: &StreamMuxConfig) -> Result<gst::Element, Error> {
+        let compositor = make_element("compositor")?;
+
+        compositor.set_property_from_str("background", "black");
+
+        Ok(compositor)
+    }
+
+    fn inference(&self, _config: &InferConfig) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn frame_processor(&self) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn tracker(
+        &self,
+        _lib_path: Option<&str>,
+        _config_path: Option<&str>,
+    ) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn video_convert(&self) -> Result<gst::Element, Error> {
+        make_element("videoconvert")
+    }
+
+    fn osd(&self) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn tiler(&self) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn video_sink(&self) -> Result<gst::Element, Error> {
+        let sink = make_element("fakesink")?;
+
+        sink.set_property("sync", false)?;
+
+        Ok(sink)
+    }
+
+    fn stream_demux(&self) -> Result<gst::Element, Error> {
+        // the compositor output can't be split back, every source gets the full frame
+        make_element("tee")
+    }
+
+    fn h264_encoder(&self) -> Result<gst::Element, Error> {
+        let encoder = make_element("x264enc")?;
+
+        encoder.set_property_from_str("tune", "zerolatency");
+
+        Ok(encoder)
+    }
+
+    fn uri_decoder(&self, uri: &str) -> Result<gst::Element, Error> {
+        let urisrc = make_element("uridecodebin")?;
+
+        urisrc.set_property("uri", uri)?;
+
+        Ok(urisrc)
+    }
+
+    fn object_converter(&self, _config: &ObjconvConfig) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn message_converter(&self) -> Result<gst::Element, Error> {
+        make_element("identity")
+    }
+
+    fn message_broker(&self, _config: &MsgBrokerSinkConfig) -> Result<gst::Element, Error> {
+        let sink = make_element("fakesink")?;
+
+        sink.set_property("sync", false)?;
+
+        Ok(sink)
+    }
+
+    fn memory_feature(&self) -> Option<&'static str> {
+        None
+    }
+}
@@ -38,11 +38,11 @@ pub fn add_bin_ghost_pad(bin: &gst::Bin, elem: &gst::Element, pad_name: &str) ->
     add_bin_ghost_pad_named(bin, elem, pad_name, pad_name)
 }
 
-/// Link sink pad of a element to source pad of tee.
+/// Link sink pad of a element to source pad of tee, returning the tee pad.
 pub fn link_element_to_tee_src_pad<P: IsA<gst::Element>>(
     tee: &gst::Element,
     elem: &P,
-) -> Result<(), Error> {
+) -> Result<gst::Pad, Error> {
     let pad_template = tee
         .pad_template("src_%u")
         .expect("Cant get tee src pad template");
@@ -54,5 +54,5 @@ pub fn link_element_to_tee_src_pad<P: IsA<gst::Element>>(
 
     tee_src_pad.link(&sink_pad)?;
 
-    Ok(())
+    Ok(tee_src_pad)
 }
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Error};
+use std::env;
+use std::fs;
+
+/// Prefix of the values read from a file, e.g. `file:/run/secrets/rtsp_password`.
+const FILE_PREFIX: &str = "file:";
+
+/// Resolve the references of a config value.
+///
+/// A value starting with `file:` is replaced by the content of the file, without
+/// the trailing new line. Otherwise `${VAR}` and `${VAR:-default}` are replaced
+/// by the value of the environment variable `VAR`.
+pub fn resolve(value: &str) -> Result<String, Error> {
+    if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+        let content =
+            fs::read_to_string(path).map_err(|e| anyhow!("Cant read file {}: {}", path, e))?;
+        return Ok(content.trim_end_matches(&['\r', '\n'][..]).to_string());
+    }
+
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+        // the value may be a secret, only where the reference starts is reported
+        let offset = value.len() - rest.len() + start;
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed variable reference at offset {}", offset))?;
+        let reference = &rest[start + 2..start + end];
+
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        match (env::var(name), default) {
+            (Ok(var), _) => resolved.push_str(&var),
+            (Err(_), Some(default)) => resolved.push_str(default),
+            (Err(_), None) => return Err(anyhow!("Environment variable {} not set", name)),
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// Position in the YAML source, 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
+}
+
+/// Location of every node of a YAML document, by path, e.g. `sources[1].kind.uri`.
+#[derive(Debug, Default)]
+pub struct Locations(HashMap<String, Location>);
+
+impl Locations {
+    pub fn from_yaml(content: &str) -> Self {
+        let mut receiver = Receiver::default();
+        // invalid documents fail to deserialize before being validated
+        let _ = Parser::new(content.chars()).load(&mut receiver, false);
+
+        Locations(receiver.locations)
+    }
+
+    /// Location of `path`, or of its closest parent found.
+    pub fn get(&self, path: &str) -> Option<Location> {
+        let mut path = path;
+        loop {
+            if let Some(location) = self.0.get(path) {
+                return Some(*location);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+enum Node {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+#[derive(Default)]
+struct Receiver {
+    stack: Vec<Node>,
+    locations: HashMap<String, Location>,
+}
+
+impl Receiver {
+    /// Path of the next value of the current node, `None` if the next event is
+    /// a mapping key.
+    fn next_path(&mut self, scalar: Option<&str>, mark: Marker) -> Option<String> {
+        let path = match self.stack.last_mut()? {
+            Node::Mapping { path, key } => match key.take() {
+                Some(key) => join(path, &key),
+                None => {
+                    let key_name = scalar.unwrap_or_default().to_string();
+                    self.locations
+                        .entry(join(path, &key_name))
+                        .or_insert_with(|| location(mark));
+                    *key = Some(key_name);
+                    return None;
+                }
+            },
+            Node::Sequence { path, index } => {
+                *index += 1;
+                format!("{}[{}]", path, *index - 1)
+            }
+        };
+        self.locations
+            .entry(path.clone())
+            .or_insert_with(|| location(mark));
+
+        Some(path)
+    }
+}
+
+impl MarkedEventReceiver for Receiver {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                self.next_path(Some(&value), mark);
+            }
+            Event::Alias(_) => {
+                self.next_path(None, mark);
+            }
+            Event::MappingStart(_) => {
+                let path = self.next_path(None, mark).unwrap_or_default();
+                self.stack.push(Node::Mapping { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.next_path(None, mark).unwrap_or_default();
+                self.stack.push(Node::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn location(mark: Marker) -> Location {
+    Location {
+        line: mark.line(),
+        column: mark.col() + 1,
+    }
+}
@@ -0,0 +1,473 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::common::SourceId;
+
+mod interpolate;
+mod location;
+mod secret;
+mod validation;
+
+pub use secret::Secret;
+pub use validation::{validate_source, ConfigIssue};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "type")]
+pub enum SourceKind {
+    Test,
+    Uri {
+        uri: String,
+        username: Option<Secret>,
+        password: Option<Secret>,
+    },
+    Rtsp {
+        uri: String,
+        username: Option<Secret>,
+        password: Option<Secret>,
+        #[serde(default)]
+        reconnect: ReconnectConfig,
+    },
+    /// Local video file, looped and seeked without stopping the pipeline.
+    File {
+        path: String,
+        /// Seek back to `start_offset` at the end instead of sending EOS
+        #[serde(default, rename = "loop")]
+        looping: bool,
+        /// Seconds
+        start_offset: Option<HashableF64>,
+        /// Seconds
+        end_offset: Option<HashableF64>,
+        playback_rate: Option<HashableF64>,
+    },
+}
+
+/// Reconnection of a source that stops sending data, durations in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Hash)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    /// Interval between the checks of the data flow
+    pub watch_interval: HashableF64,
+    /// Time without data before reconnecting
+    pub data_timeout: HashableF64,
+    /// Time for an attempt to get data again before it's considered failed
+    pub connect_timeout: HashableF64,
+    /// Delay after the first failed attempt, multiplied by `backoff_multiplier`
+    /// after every failed attempt up to `max_backoff`
+    pub initial_backoff: HashableF64,
+    pub max_backoff: HashableF64,
+    pub backoff_multiplier: HashableF64,
+    /// Random fraction of the backoff added or removed, from 0 to 1
+    pub jitter: HashableF64,
+    /// Unlimited if not set
+    pub max_attempts: Option<u32>,
+    pub give_up: GiveUp,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            watch_interval: HashableF64(1.0),
+            data_timeout: HashableF64(10.0),
+            connect_timeout: HashableF64(30.0),
+            initial_backoff: HashableF64(1.0),
+            max_backoff: HashableF64(60.0),
+            backoff_multiplier: HashableF64(2.0),
+            jitter: HashableF64(0.1),
+            max_attempts: None,
+            give_up: GiveUp::Stop,
+        }
+    }
+}
+
+/// What to do with a source once `max_attempts` reconnections failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum GiveUp {
+    /// Keep the source stopped, marked as failed.
+    #[default]
+    Stop,
+    /// Remove the source from the pipeline.
+    Remove,
+}
+
+/// `f64` config value that can be part of the config hash.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HashableF64(pub f64);
+
+impl Hash for HashableF64 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Backend {
+    /// DeepStream elements, requires a NVIDIA GPU.
+    #[default]
+    Nvidia,
+    /// Stock GStreamer elements, without inference.
+    Software,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+pub struct SourceConfig {
+    pub id: SourceId,
+    pub kind: SourceKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamMuxConfig {
+    pub batch_size: u32,
+    pub enable_padding: bool,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An inference stage, a primary detector on the full frames unless
+/// `operate_on_gie_id` is set. The values not set are the ones of `config_path`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InferConfig {
+    pub config_path: String,
+    pub gie_unique_id: Option<u32>,
+    /// Makes the stage secondary, inferring on the objects of this stage.
+    pub operate_on_gie_id: Option<u32>,
+    /// Classes of the objects to infer on, all of them when empty.
+    #[serde(default)]
+    pub operate_on_class_ids: Vec<u32>,
+    /// Batches skipped between two inferences.
+    pub interval: Option<u32>,
+    pub batch_size: Option<u32>,
+}
+
+/// Lines and zones the objects of the sources are counted on, from their
+/// tracker ids.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalyticsConfig {
+    pub sources: Vec<SourceAnalyticsConfig>,
+    /// Frames an object can be missing from before it's forgotten, leaving
+    /// the zones it was in.
+    #[serde(default = "default_max_missing_frames")]
+    pub max_missing_frames: u32,
+}
+
+fn default_max_missing_frames() -> u32 {
+    30
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceAnalyticsConfig {
+    pub source_id: SourceId,
+    /// Classes counted, all of them when empty.
+    #[serde(default)]
+    pub class_ids: Vec<i32>,
+    #[serde(default)]
+    pub lines: Vec<LineConfig>,
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+}
+
+/// A line crossed by the bottom center of the objects boxes, in pixels of the
+/// streammux frames.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineConfig {
+    pub name: String,
+    pub from: (u32, u32),
+    pub to: (u32, u32),
+    #[serde(default)]
+    pub direction: LineDirection,
+}
+
+/// Crossings counted, `in` is from the left to the right of the line looking
+/// from `from` to `to`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineDirection {
+    #[default]
+    Both,
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneConfig {
+    pub name: String,
+    /// Polygon of the zone, in pixels of the streammux frames.
+    pub points: Vec<(u32, u32)>,
+    /// Seconds an object stays in the zone before it is loitering, not
+    /// detected when not set.
+    #[serde(default)]
+    pub loitering_threshold: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterConfig {
+    NvInfer(InferConfig),
+    Tracker {
+        lib_path: Option<String>,
+        config_path: Option<String>,
+    },
+    Analytics(AnalyticsConfig),
+    /// A frame processor registered as `name`, given its `config`.
+    Processor {
+        name: String,
+        #[serde(default)]
+        config: serde_yaml::Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MsgBrokerSinkConfig {
+    pub topic: String,
+    pub server: Secret,
+    pub port: u32,
+    #[serde(default)]
+    pub objconv: ObjconvConfig,
+}
+
+/// Objects the `nvobjconv` element sends to the broker, and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjconvConfig {
+    pub mode: ObjconvMode,
+    /// Minimum time between two moving events of a track, in milliseconds.
+    pub throttle_interval: u32,
+    /// Frames of its source a track can be missing from before its exit.
+    pub max_missing_frames: u32,
+    /// Classes sent, all of them when empty.
+    pub class_ids: Vec<i32>,
+    pub deny_class_ids: Vec<i32>,
+    /// Labels sent, all of them when empty.
+    pub labels: Vec<String>,
+    pub deny_labels: Vec<String>,
+    pub min_confidence: f32,
+    /// Size of the box, in pixels of the streammux frames.
+    pub min_width: u32,
+    pub min_height: u32,
+    /// Sources sent, all of them when empty.
+    pub source_ids: Vec<SourceId>,
+    /// Send one frame out of `frame_interval` of every source.
+    pub frame_interval: u32,
+}
+
+impl Default for ObjconvConfig {
+    fn default() -> Self {
+        ObjconvConfig {
+            mode: ObjconvMode::default(),
+            throttle_interval: 1000,
+            max_missing_frames: 30,
+            class_ids: Vec::new(),
+            deny_class_ids: Vec::new(),
+            labels: Vec::new(),
+            deny_labels: Vec::new(),
+            min_confidence: 0.0,
+            min_width: 0,
+            min_height: 0,
+            source_ids: Vec::new(),
+            frame_interval: 1,
+        }
+    }
+}
+
+impl ObjconvConfig {
+    /// Properties of the `nvobjconv` element, as strings, the lists are
+    /// separated by `;`.
+    pub fn properties(&self) -> Vec<(&'static str, String)> {
+        fn list<T: ToString>(values: &[T]) -> String {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(";")
+        }
+
+        vec![
+            ("mode", self.mode.nick().to_string()),
+            ("throttle-interval", self.throttle_interval.to_string()),
+            ("max-missing-frames", self.max_missing_frames.to_string()),
+            ("class-ids", list(&self.class_ids)),
+            ("deny-class-ids", list(&self.deny_class_ids)),
+            ("labels", list(&self.labels)),
+            ("deny-labels", list(&self.deny_labels)),
+            ("min-confidence", self.min_confidence.to_string()),
+            ("min-width", self.min_width.to_string()),
+            ("min-height", self.min_height.to_string()),
+            ("source-ids", list(&self.source_ids)),
+            ("frame-interval", self.frame_interval.to_string()),
+        ]
+    }
+}
+
+/// Objects sent, the `mode` of the `nvobjconv` element.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ObjconvMode {
+    /// Every object of every frame.
+    #[default]
+    PerFrame,
+    /// Entry and exit of the tracks, and their moves at most once per
+    /// throttle interval.
+    PerTrack,
+    /// Entry and exit of the tracks, and their class or labels changes at
+    /// most once per throttle interval.
+    OnChange,
+}
+
+impl ObjconvMode {
+    /// Nick of the mode in the element enum.
+    pub fn nick(self) -> &'static str {
+        match self {
+            ObjconvMode::PerFrame => "per-frame",
+            ObjconvMode::PerTrack => "per-track",
+            ObjconvMode::OnChange => "on-change",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SinksConfig {
+    pub display: bool,
+    pub rtsp: bool,
+    pub msg_broker: Option<MsgBrokerSinkConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiConfig {
+    /// Only local clients by default, the api has no authentication.
+    #[serde(default = "default_api_host")]
+    pub host: String,
+    pub port: u16,
+}
+
+fn default_api_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// FPS measurement of the sources, in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Length of the sliding window the FPS is computed over
+    pub fps_window: f64,
+    /// Time without frames after which a source has no FPS, its window is
+    /// restarted by the next frame
+    pub stall_timeout: f64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            fps_window: 5.0,
+            stall_timeout: 60.0,
+        }
+    }
+}
+
+/// Shapes and counters drawn on the frames by the osd.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OverlayConfig {
+    #[serde(default)]
+    pub zones: Vec<OverlayZoneConfig>,
+    /// Draw the fps and the objects in the frame of every source.
+    #[serde(default)]
+    pub counters: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayZoneConfig {
+    pub source_id: SourceId,
+    pub name: String,
+    /// Polygon of the zone, in pixels of the streammux frames.
+    pub points: Vec<(u32, u32)>,
+    /// Red, green, blue and alpha, between 0 and 1.
+    #[serde(default = "default_zone_color")]
+    pub color: [f64; 4],
+}
+
+fn default_zone_color() -> [f64; 4] {
+    [0.0, 1.0, 0.0, 1.0]
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    #[serde(default)]
+    pub backend: Backend,
+    pub sources: Vec<SourceConfig>,
+    pub streammux: StreamMuxConfig,
+    pub filters: Vec<FilterConfig>,
+    pub sinks: SinksConfig,
+    #[serde(default)]
+    pub api: Option<ApiConfig>,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub overlay: OverlayConfig,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(serde_yaml::Error),
+    Invalid(Vec<ConfigIssue>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Cant read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "Cant parse config: {}", e),
+            ConfigError::Invalid(issues) => {
+                write!(f, "Invalid config, {} issues:", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub fn from_file(filename: &str) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(filename).map_err(ConfigError::Io)?;
+
+        Self::from_yaml(&content)
+    }
+
+    /// Parse a YAML config, resolve its secrets and validate it.
+    pub fn from_yaml(content: &str) -> Result<Self, ConfigError> {
+        let mut config: PipelineConfig =
+            serde_yaml::from_str(content).map_err(ConfigError::Parse)?;
+
+        let locations = location::Locations::from_yaml(content);
+        let mut issues = validation::resolve_secrets(&mut config, &locations);
+        issues.extend(validation::validate(&config, &locations));
+        if !issues.is_empty() {
+            return Err(ConfigError::Invalid(issues));
+        }
+
+        Ok(config)
+    }
+}
+
+impl SourceConfig {
+    pub fn get_hash(&self) -> u64 {
+        let mut s = DefaultHasher::new();
+        self.hash(&mut s);
+        s.finish()
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use super::interpolate;
+
+const REDACTED: &str = "***";
+
+/// Config value that must not be logged, like a password.
+///
+/// It's deserialized as it is, the references to environment variables and
+/// files are only resolved for the config file, not for the sources posted to
+/// the api. `Debug`, `Display` and `Serialize` hide the value, while the `Hash`
+/// is computed from it, so a changed secret is detected on reload.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Replace the references of the value by what they point to.
+    pub(super) fn resolve(&mut self) -> Result<(), Error> {
+        self.0 = interpolate::resolve(&self.0)?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::pipeline::filters::processor;
+
+use super::location::{Location, Locations};
+use super::{
+    AnalyticsConfig, Backend, FilterConfig, InferConfig, PipelineConfig, ReconnectConfig, Secret,
+    SourceConfig, SourceKind,
+};
+
+const URI_SCHEMES: &[&str] = &["file", "http", "https", "rtsp", "rtsps", "rtmp", "udp"];
+const RTSP_SCHEMES: &[&str] = &["rtsp", "rtsps", "rtspt", "rtspu"];
+
+/// A problem found validating a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Path of the invalid value, e.g. `sources[1].kind.uri`.
+    pub path: String,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} at {}: {}", self.path, location, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Check the values the deserialization can't, returning all the problems found.
+pub fn validate(config: &PipelineConfig, locations: &Locations) -> Vec<ConfigIssue> {
+    let mut issues = Issues {
+        locations,
+        issues: Vec::new(),
+    };
+
+    let mut ids = HashMap::new();
+    for (i, source) in config.sources.iter().enumerate() {
+        let path = format!("sources[{}]", i);
+        if let Some(first) = ids.insert(source.id, i) {
+            issues.push(
+                &format!("{}.id", path),
+                format!(
+                    "duplicate source id {}, first used by sources[{}]",
+                    source.id, first
+                ),
+            );
+        }
+        check_source(&mut issues, &path, source);
+    }
+
+    let streammux = &config.streammux;
+    if streammux.batch_size == 0 {
+        issues.push("streammux.batch_size", "must be greater than 0".to_string());
+    } else if config.sources.len() > streammux.batch_size as usize {
+        issues.push(
+            "streammux.batch_size",
+            format!(
+                "{} is lower than the number of sources, {}",
+                streammux.batch_size,
+                config.sources.len()
+            ),
+        );
+    }
+    if streammux.width == 0 {
+        issues.push("streammux.width", "must be greater than 0".to_string());
+    }
+    if streammux.height == 0 {
+        issues.push("streammux.height", "must be greater than 0".to_string());
+    }
+
+    // the software backend doesn't use the filters files
+    if config.backend == Backend::Nvidia {
+        for (i, filter) in config.filters.iter().enumerate() {
+            match filter {
+                FilterConfig::NvInfer(infer) => {
+                    let path = format!("filters[{}].NvInfer.config_path", i);
+                    check_file(&mut issues, &path, &infer.config_path);
+                }
+                FilterConfig::Tracker {
+                    lib_path,
+                    config_path,
+                } => {
+                    if let Some(lib_path) = lib_path {
+                        let path = format!("filters[{}].Tracker.lib_path", i);
+                        check_file(&mut issues, &path, lib_path);
+                    }
+                    if let Some(config_path) = config_path {
+                        let path = format!("filters[{}].Tracker.config_path", i);
+                        check_file(&mut issues, &path, config_path);
+                    }
+                }
+                FilterConfig::Analytics(_) | FilterConfig::Processor { .. } => {}
+            }
+        }
+    }
+
+    // unique ids of the inference stages before the current one
+    let mut gie_ids = HashMap::new();
+    for (i, filter) in config.filters.iter().enumerate() {
+        if let FilterConfig::NvInfer(infer) = filter {
+            let path = format!("filters[{}].NvInfer", i);
+            check_infer(&mut issues, &path, infer, &gie_ids);
+            if let Some(gie_id) = infer.gie_unique_id {
+                gie_ids.entry(gie_id).or_insert(i);
+            }
+        }
+    }
+
+    for (i, filter) in config.filters.iter().enumerate() {
+        match filter {
+            FilterConfig::Analytics(analytics) => {
+                let path = format!("filters[{}].Analytics", i);
+                check_analytics(&mut issues, &path, analytics);
+            }
+            FilterConfig::Processor { name, config } => {
+                let path = format!("filters[{}].Processor", i);
+                check_processor(&mut issues, &path, name, config);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(msg_broker) = &config.sinks.msg_broker {
+        if !(1..=65535).contains(&msg_broker.port) {
+            issues.push(
+                "sinks.msg_broker.port",
+                format!("{} is not a valid port", msg_broker.port),
+            );
+        }
+        if msg_broker.server.expose().is_empty() {
+            issues.push("sinks.msg_broker.server", "must not be empty".to_string());
+        }
+
+        let objconv = &msg_broker.objconv;
+        if !(0.0..=1.0).contains(&objconv.min_confidence) {
+            issues.push(
+                "sinks.msg_broker.objconv.min_confidence",
+                "must be between 0 and 1".to_string(),
+            );
+        }
+        if objconv.frame_interval == 0 {
+            issues.push(
+                "sinks.msg_broker.objconv.frame_interval",
+                "must be greater than 0".to_string(),
+            );
+        }
+        // the lists are given to the element separated by `;`
+        let labels = [
+            ("labels", &objconv.labels),
+            ("deny_labels", &objconv.deny_labels),
+        ];
+        for (name, labels) in labels {
+            for (i, label) in labels.iter().enumerate() {
+                if label.is_empty() || label.contains(';') {
+                    issues.push(
+                        &format!("sinks.msg_broker.objconv.{}[{}]", name, i),
+                        "must be a non empty label without ;".to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    if !is_positive(config.metrics.fps_window) {
+        issues.push("metrics.fps_window", POSITIVE.to_string());
+    }
+    if !is_positive(config.metrics.stall_timeout) {
+        issues.push("metrics.stall_timeout", POSITIVE.to_string());
+    }
+
+    for (i, zone) in config.overlay.zones.iter().enumerate() {
+        let path = format!("overlay.zones[{}]", i);
+        if zone.points.len() < 3 {
+            issues.push(
+                &format!("{}.points", path),
+                "a zone needs at least 3 points".to_string(),
+            );
+        }
+        if zone.color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            issues.push(
+                &format!("{}.color", path),
+                "components must be between 0 and 1".to_string(),
+            );
+        }
+    }
+
+    issues.issues
+}
+
+/// Validate a single source, e.g. one added through the api.
+pub fn validate_source(config: &SourceConfig) -> Vec<ConfigIssue> {
+    let locations = Locations::default();
+    let mut issues = Issues {
+        locations: &locations,
+        issues: Vec::new(),
+    };
+    check_source(&mut issues, "source", config);
+
+    issues.issues
+}
+
+/// Resolve the references of the secrets of a config file, returning the ones
+/// that can't be resolved.
+pub fn resolve_secrets(config: &mut PipelineConfig, locations: &Locations) -> Vec<ConfigIssue> {
+    let mut issues = Issues {
+        locations,
+        issues: Vec::new(),
+    };
+    let mut resolve = |path: &str, secret: &mut Secret| {
+        if let Err(e) = secret.resolve() {
+            issues.push(path, e.to_string());
+        }
+    };
+
+    for (i, source) in config.sources.iter_mut().enumerate() {
+        if let SourceKind::Uri {
+            username, password, ..
+        }
+        | SourceKind::Rtsp {
+            username, password, ..
+        } = &mut source.kind
+        {
+            for (field, secret) in [("username", username), ("password", password)] {
+                if let Some(secret) = secret {
+                    resolve(&format!("sources[{}].kind.{}", i, field), secret);
+                }
+            }
+        }
+    }
+    if let Some(msg_broker) = &mut config.sinks.msg_broker {
+        resolve("sinks.msg_broker.server", &mut msg_broker.server);
+    }
+
+    issues.issues
+}
+
+struct Issues<'a> {
+    locations: &'a Locations,
+    issues: Vec<ConfigIssue>,
+}
+
+impl<'a> Issues<'a> {
+    fn push(&mut self, path: &str, message: String) {
+        self.issues.push(ConfigIssue {
+            path: path.to_string(),
+            location: self.locations.get(path),
+            message,
+        });
+    }
+}
+
+fn check_source(issues: &mut Issues, path: &str, source: &SourceConfig) {
+    let (uri, schemes) = match &source.kind {
+        SourceKind::Test => return,
+        SourceKind::File {
+            path: file,
+            start_offset,
+            end_offset,
+            playback_rate,
+            ..
+        } => {
+            check_file(issues, &format!("{}.kind.path", path), file);
+            for (name, offset) in [("start_offset", start_offset), ("end_offset", end_offset)] {
+                if matches!(offset, Some(offset) if !is_non_negative(offset.0)) {
+                    issues.push(&format!("{}.kind.{}", path, name), NON_NEGATIVE.to_string());
+                }
+            }
+            if let (Some(start), Some(end)) = (start_offset, end_offset) {
+                if end.0 <= start.0 {
+                    issues.push(
+                        &format!("{}.kind.end_offset", path),
+                        format!("must be greater than start_offset, {}", start.0),
+                    );
+                }
+            }
+            if matches!(playback_rate, Some(rate) if !is_positive(rate.0)) {
+                issues.push(
+                    &format!("{}.kind.playback_rate", path),
+                    POSITIVE.to_string(),
+                );
+            }
+            return;
+        }
+        SourceKind::Uri { uri, .. } => (uri, URI_SCHEMES),
+        SourceKind::Rtsp { uri, reconnect, .. } => {
+            check_reconnect(issues, &format!("{}.kind.reconnect", path), reconnect);
+            (uri, RTSP_SCHEMES)
+        }
+    };
+
+    let path = format!("{}.kind.uri", path);
+    match uri.split_once("://") {
+        Some((scheme, rest)) if schemes.contains(&scheme) && !rest.is_empty() => {}
+        Some((scheme, _)) if !schemes.contains(&scheme) => issues.push(
+            &path,
+            format!(
+                "unsupported scheme {}, expected one of {}",
+                scheme,
+                schemes.join(", ")
+            ),
+        ),
+        _ => issues.push(&path, format!("{} is not a valid uri", uri)),
+    }
+}
+
+fn check_analytics(issues: &mut Issues, path: &str, config: &AnalyticsConfig) {
+    let mut sources = HashMap::new();
+    for (i, source) in config.sources.iter().enumerate() {
+        let path = format!("{}.sources[{}]", path, i);
+        if let Some(first) = sources.insert(source.source_id, i) {
+            issues.push(
+                &format!("{}.source_id", path),
+                format!(
+                    "duplicate source id {}, first used by sources[{}]",
+                    source.source_id, first
+                ),
+            );
+        }
+
+        let mut names = HashSet::new();
+        for (j, line) in source.lines.iter().enumerate() {
+            let path = format!("{}.lines[{}]", path, j);
+            if !names.insert(&line.name) {
+                issues.push(
+                    &format!("{}.name", path),
+                    format!("duplicate line or zone name {}", line.name),
+                );
+            }
+            if line.from == line.to {
+                issues.push(
+                    &format!("{}.to", path),
+                    "must be a different point than from".to_string(),
+                );
+            }
+        }
+        for (j, zone) in source.zones.iter().enumerate() {
+            let path = format!("{}.zones[{}]", path, j);
+            if !names.insert(&zone.name) {
+                issues.push(
+                    &format!("{}.name", path),
+                    format!("duplicate line or zone name {}", zone.name),
+                );
+            }
+            if zone.points.len() < 3 {
+                issues.push(
+                    &format!("{}.points", path),
+                    "a zone needs at least 3 points".to_string(),
+                );
+            }
+            if zone.loitering_threshold == Some(0) {
+                issues.push(
+                    &format!("{}.loitering_threshold", path),
+                    "must be at least 1 second".to_string(),
+                );
+            }
+        }
+    }
+}
+
+fn check_processor(issues: &mut Issues, path: &str, name: &str, config: &serde_yaml::Value) {
+    let names = processor::processor_names();
+    if !names.iter().any(|n| n == name) {
+        issues.push(
+            &format!("{}.name", path),
+            format!(
+                "no processor registered as {}, expected one of: {}",
+                name,
+                names.join(", ")
+            ),
+        );
+    } else if let Err(e) = processor::create_processor(name, config) {
+        issues.push(&format!("{}.config", path), e.to_string());
+    }
+}
+
+fn check_infer(
+    issues: &mut Issues,
+    path: &str,
+    config: &InferConfig,
+    gie_ids: &HashMap<u32, usize>,
+) {
+    if let Some(gie_id) = config.gie_unique_id {
+        if gie_id == 0 {
+            issues.push(
+                &format!("{}.gie_unique_id", path),
+                "must be greater than 0".to_string(),
+            );
+        } else if let Some(first) = gie_ids.get(&gie_id) {
+            issues.push(
+                &format!("{}.gie_unique_id", path),
+                format!("duplicate id {}, first used by filters[{}]", gie_id, first),
+            );
+        }
+    }
+    match config.operate_on_gie_id {
+        Some(gie_id) if !gie_ids.contains_key(&gie_id) => issues.push(
+            &format!("{}.operate_on_gie_id", path),
+            format!(
+                "{} is not the gie_unique_id of a previous NvInfer filter",
+                gie_id
+            ),
+        ),
+        None if !config.operate_on_class_ids.is_empty() => issues.push(
+            &format!("{}.operate_on_class_ids", path),
+            "requires operate_on_gie_id".to_string(),
+        ),
+        _ => {}
+    }
+    if config.batch_size == Some(0) {
+        issues.push(
+            &format!("{}.batch_size", path),
+            "must be greater than 0".to_string(),
+        );
+    }
+}
+
+fn check_reconnect(issues: &mut Issues, path: &str, config: &ReconnectConfig) {
+    for (name, value) in [
+        ("watch_interval", config.watch_interval),
+        ("data_timeout", config.data_timeout),
+        ("connect_timeout", config.connect_timeout),
+    ] {
+        if !is_positive(value.0) {
+            issues.push(&format!("{}.{}", path, name), POSITIVE.to_string());
+        }
+    }
+    if !is_non_negative(config.initial_backoff.0) {
+        issues.push(
+            &format!("{}.initial_backoff", path),
+            NON_NEGATIVE.to_string(),
+        );
+    }
+    if !config.max_backoff.0.is_finite() || config.max_backoff.0 < config.initial_backoff.0 {
+        issues.push(
+            &format!("{}.max_backoff", path),
+            "must not be lower than initial_backoff".to_string(),
+        );
+    }
+    if !(config.backoff_multiplier.0.is_finite() && config.backoff_multiplier.0 >= 1.0) {
+        issues.push(
+            &format!("{}.backoff_multiplier", path),
+            "must be a finite number of at least 1".to_string(),
+        );
+    }
+    if !(0.0..=1.0).contains(&config.jitter.0) {
+        issues.push(
+            &format!("{}.jitter", path),
+            "must be between 0 and 1".to_string(),
+        );
+    }
+}
+
+const POSITIVE: &str = "must be a finite number greater than 0";
+const NON_NEGATIVE: &str = "must be a finite number, not negative";
+
+/// `value` is finite and greater than 0, e.g. seconds converted to a
+/// `Duration`, which panics on infinite and NaN values.
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn is_non_negative(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+fn check_file(issues: &mut Issues, path: &str, file: &str) {
+    if !Path::new(file).is_file() {
+        issues.push(path, format!("file {} not found", file));
+    }
+}
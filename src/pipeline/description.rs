@@ -0,0 +1,214 @@
+//! gst-launch style description of the pipeline a config builds, made without
+//! GStreamer. Keep it in sync with the backends and the sources and sinks bins.
+
+use super::config::{
+    Backend, FilterConfig, MsgBrokerSinkConfig, ObjconvConfig, PipelineConfig, SourceConfig,
+    SourceKind, StreamMuxConfig,
+};
+
+/// Describe the pipeline built from `config`, one branch per line.
+pub fn describe(config: &PipelineConfig) -> String {
+    let backend = config.backend;
+    let mut lines = Vec::new();
+
+    // main branch
+    let mut main = vec![streammux(backend, &config.streammux), "queue".to_string()];
+    main.extend(config.filters.iter().map(|f| filter(backend, f)));
+    main.push("identity".to_string());
+    main.push("queue".to_string());
+    main.push(element(backend, "nvvideoconvert", "videoconvert"));
+    main.push(element(backend, "nvdsosd", "identity"));
+    main.push("tee name=tee".to_string());
+    lines.push(main.join(" ! "));
+
+    // sinks
+    let sinks = &config.sinks;
+    if let Some(broker) = &sinks.msg_broker {
+        lines.push(branch(&[
+            "tee.".to_string(),
+            "queue leaky=downstream max-size-buffers=2".to_string(),
+            objconv(backend, &broker.objconv),
+            element(backend, "nvmsgconv", "identity"),
+            msg_broker(backend, broker),
+        ]));
+    }
+    if sinks.rtsp {
+        lines.push(branch(&[
+            "tee.".to_string(),
+            element(backend, "nvstreamdemux name=demux", "tee name=demux"),
+        ]));
+        for source in &config.sources {
+            lines.push(rtsp_sink(backend, source));
+        }
+    }
+    if sinks.display {
+        lines.push(branch(&[
+            "tee.".to_string(),
+            "queue".to_string(),
+            element(backend, "nvmultistreamtiler", "identity"),
+            element(backend, "nveglglessink", "fakesink sync=false"),
+        ]));
+    }
+
+    // sources
+    for source in &config.sources {
+        lines.push(source_branch(backend, source));
+    }
+
+    lines.join(" \\\n  ")
+}
+
+fn branch(elements: &[String]) -> String {
+    elements.join(" ! ")
+}
+
+fn element(backend: Backend, nvidia: &str, software: &str) -> String {
+    match backend {
+        Backend::Nvidia => nvidia.to_string(),
+        Backend::Software => software.to_string(),
+    }
+}
+
+/// Quote `value` if needed by gst-launch.
+fn value(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || ";!\"".contains(c)) {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn streammux(backend: Backend, config: &StreamMuxConfig) -> String {
+    match backend {
+        Backend::Nvidia => format!(
+            "nvstreammux name=streammux batch-size={} enable-padding={} live-source=true width={} height={}",
+            config.batch_size, config.enable_padding, config.width, config.height
+        ),
+        Backend::Software => "compositor name=streammux background=black".to_string(),
+    }
+}
+
+fn filter(backend: Backend, config: &FilterConfig) -> String {
+    if backend == Backend::Software {
+        return "identity".to_string();
+    }
+
+    match config {
+        FilterConfig::NvInfer(infer) => {
+            let mut desc = format!("nvinfer config-file-path={}", value(&infer.config_path));
+            if let Some(unique_id) = infer.gie_unique_id {
+                desc.push_str(&format!(" unique-id={}", unique_id));
+            }
+            if let Some(gie_id) = infer.operate_on_gie_id {
+                desc.push_str(&format!(
+                    " process-mode=secondary infer-on-gie-id={}",
+                    gie_id
+                ));
+            }
+            if !infer.operate_on_class_ids.is_empty() {
+                let class_ids: Vec<String> = infer
+                    .operate_on_class_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect();
+                desc.push_str(&format!(" infer-on-class-ids={}", class_ids.join(":")));
+            }
+            if let Some(interval) = infer.interval {
+                desc.push_str(&format!(" interval={}", interval));
+            }
+            if let Some(batch_size) = infer.batch_size {
+                desc.push_str(&format!(" batch-size={}", batch_size));
+            }
+            desc
+        }
+        FilterConfig::Analytics(_) | FilterConfig::Processor { .. } => "identity".to_string(),
+        FilterConfig::Tracker {
+            lib_path,
+            config_path,
+        } => format!(
+            "nvtracker tracker-width=640 tracker-height=384 ll-lib-file={} ll-config-file={}",
+            value(lib_path.as_deref().unwrap_or(
+                "/opt/nvidia/deepstream/deepstream/lib/libnvds_nvmultiobjecttracker.so"
+            )),
+            value(
+                config_path
+                    .as_deref()
+                    .unwrap_or("config/filters/config_tracker_NvDCF_perf.yml")
+            ),
+        ),
+    }
+}
+
+/// The `nvobjconv` element, with the properties not at their default.
+fn objconv(backend: Backend, config: &ObjconvConfig) -> String {
+    let defaults = ObjconvConfig::default().properties();
+    let properties = config
+        .properties()
+        .into_iter()
+        .filter(|property| !defaults.contains(property))
+        .map(|(name, v)| format!(" {}={}", name, value(&v)));
+    match backend {
+        Backend::Nvidia => format!("nvobjconv{}", properties.collect::<String>()),
+        Backend::Software => "identity".to_string(),
+    }
+}
+
+fn msg_broker(backend: Backend, config: &MsgBrokerSinkConfig) -> String {
+    match backend {
+        Backend::Nvidia => format!(
+            "nvmsgbroker proto-lib=/opt/nvidia/deepstream/deepstream/lib/libnvds_kafka_proto.so conn-str={} topic={} config=config/filters/msgbroker_config.txt sync=false",
+            value(&format!("{};{}", config.server, config.port)),
+            value(&config.topic)
+        ),
+        Backend::Software => "fakesink sync=false".to_string(),
+    }
+}
+
+fn rtsp_sink(backend: Backend, source: &SourceConfig) -> String {
+    let caps = match backend {
+        Backend::Nvidia => "video/x-raw(memory:NVMM), format=I420",
+        Backend::Software => "video/x-raw, format=I420",
+    };
+    let demux_pad = match backend {
+        Backend::Nvidia => format!("demux.src_{}", source.id),
+        Backend::Software => "demux.".to_string(),
+    };
+
+    branch(&[
+        demux_pad,
+        "queue".to_string(),
+        element(backend, "nvvideoconvert", "videoconvert"),
+        format!("capsfilter caps={}", value(caps)),
+        element(backend, "nvv4l2h264enc", "x264enc tune=zerolatency"),
+        "h264parse".to_string(),
+        "rtph264pay".to_string(),
+        "udpsink host=127.0.0.1 async=false sync=false".to_string(),
+    ])
+}
+
+fn source_branch(backend: Backend, source: &SourceConfig) -> String {
+    // credentials are left out of the description
+    let src = match &source.kind {
+        SourceKind::Test => "videotestsrc".to_string(),
+        SourceKind::Uri { uri, .. } => match backend {
+            Backend::Nvidia => format!(
+                "nvurisrcbin uri={} rtsp-reconnect-interval=10 ! queue",
+                value(uri)
+            ),
+            Backend::Software => format!("uridecodebin uri={} ! queue", value(uri)),
+        },
+        SourceKind::File { path, .. } => {
+            let uri = format!("file://{}", path);
+            match backend {
+                Backend::Nvidia => format!("nvurisrcbin uri={} ! queue", value(&uri)),
+                Backend::Software => format!("uridecodebin uri={} ! queue", value(&uri)),
+            }
+        }
+        SourceKind::Rtsp { uri, .. } => format!(
+            "rtspsrc location={} latency=100 drop-on-latency=true ! decodebin ! queue",
+            value(uri)
+        ),
+    };
+
+    format!("{} ! streammux.sink_{}", src, source.id)
+}
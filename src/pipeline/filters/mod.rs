@@ -1,55 +1,311 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use gst::prelude::*;
+use log::{debug, error, info};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
 
+use crate::common::SourceId;
+
+use super::analytics::Analytics;
+use super::backend::ElementFactory;
 use super::common::{add_bin_ghost_pad, MissingElement};
 use super::config::FilterConfig;
+use super::metrics::AnalyticsCounters;
+
+pub mod object_filter;
+pub mod processor;
 
-mod nvinfer;
-mod tracker;
+/// Time to wait for the filters to be idle when replacing them.
+const SWAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Filters in the bin, changed from the idle probe of the swap.
+struct Chain {
+    elements: Vec<gst::Element>,
+    processors: Vec<processor::SharedProcessor>,
+    config: Vec<FilterConfig>,
+    /// A swap waits for the filters to be idle.
+    swapping: bool,
+}
 
-pub fn create_bin(filters_config: Vec<FilterConfig>) -> Result<gst::Bin, Error> {
-    let num_filters = filters_config.len();
+/// Bin with the filters, between a queue and an identity that stay in place
+/// when the filters are replaced, so the bin pads and their probes are kept.
+pub struct FilterChain {
+    pub bin: gst::Bin,
+    factory: Arc<dyn ElementFactory>,
+    entry: gst::Element,
+    exit: gst::Element,
+    chain: Arc<Mutex<Chain>>,
+    analytics: AnalyticsCounters,
+}
 
-    let bin = gst::Bin::new(Some("filter_bin"));
+impl FilterChain {
+    pub fn new(
+        factory: Arc<dyn ElementFactory>,
+        filters_config: Vec<FilterConfig>,
+    ) -> Result<Self, Error> {
+        let bin = gst::Bin::new(Some("filter_bin"));
 
-    if num_filters == 0 {
-        let queue =
+        let entry =
             gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
-        bin.add(&queue)?;
-        add_bin_ghost_pad(&bin, &queue, "sink")?;
-        add_bin_ghost_pad(&bin, &queue, "src")?;
+        let exit =
+            gst::ElementFactory::make("identity", None).map_err(|_| MissingElement("identity"))?;
+        bin.add_many(&[&entry, &exit])?;
+        add_bin_ghost_pad(&bin, &entry, "sink")?;
+        add_bin_ghost_pad(&bin, &exit, "src")?;
+
+        let analytics = AnalyticsCounters::new();
+        let (elements, processors) =
+            create_elements(factory.as_ref(), &filters_config, &analytics)?;
+        bin.add_many(&elements.iter().collect::<Vec<_>>())?;
+        link_chain(&entry, &elements, &exit)?;
+
+        Ok(FilterChain {
+            bin,
+            factory,
+            entry,
+            exit,
+            chain: Arc::new(Mutex::new(Chain {
+                elements,
+                processors,
+                config: filters_config,
+                swapping: false,
+            })),
+            analytics,
+        })
+    }
 
-        return Ok(bin);
+    /// Config of the filters in the bin.
+    pub fn config(&self) -> Vec<FilterConfig> {
+        self.chain.lock().unwrap().config.clone()
     }
 
-    let mut last_element: Option<gst::Element> = None;
-    for (i, config) in filters_config.iter().enumerate() {
-        // create element
-        let elem = match config {
-            FilterConfig::NvInfer { config_path } => {
-                nvinfer::create_element(config_path.to_string())?
+    /// Counts of the analytics filters, the same ones across updates.
+    pub fn analytics(&self) -> &AnalyticsCounters {
+        &self.analytics
+    }
+
+    /// Forget the state of a removed source in the filters.
+    pub fn remove_source(&self, source_id: &SourceId) {
+        self.analytics.remove(source_id);
+        for processor in &self.chain.lock().unwrap().processors {
+            processor.lock().unwrap().remove_source(source_id);
+        }
+    }
+
+    /// Replace the filters with the ones of `filters_config`.
+    ///
+    /// The new filters are created, added to the bin and brought to its state
+    /// first, from this thread, so the current ones are kept if one can't be
+    /// and the streaming isn't blocked while they start, e.g. while nvinfer
+    /// loads its engine. The idle probe of the queue src pad only relinks the
+    /// chain, the frames in the old filters are dropped and they're stopped
+    /// and removed out of the streaming thread.
+    ///
+    /// An update is refused while the previous one waits for the filters to
+    /// be idle, it fails if they aren't idle within [`SWAP_TIMEOUT`] but the
+    /// swap is still done once they are.
+    pub fn update(&mut self, filters_config: Vec<FilterConfig>) -> Result<(), Error> {
+        if self.chain.lock().unwrap().swapping {
+            return Err(anyhow!("The previous filters are still being replaced"));
+        }
+        let (new_elements, new_processors) =
+            create_elements(self.factory.as_ref(), &filters_config, &self.analytics)?;
+        prepare_chain(&self.bin, &new_elements)?;
+        self.chain.lock().unwrap().swapping = true;
+
+        let chain = self.chain.clone();
+        let bin = self.bin.clone();
+        let entry = self.entry.clone();
+        let exit = self.exit.clone();
+        let (swapped, swap_result) = mpsc::channel();
+        let entry_src = self.entry.static_pad("src").expect("Cant get queue srcpad");
+        debug!("Waiting for the filters to be idle ...");
+        // the probe runs right away, in this thread, if the pad is already idle
+        entry_src.add_probe(gst::PadProbeType::IDLE, move |_, _| {
+            let mut chain = chain.lock().unwrap();
+            let result = swap_chain(&entry, &exit, &chain.elements, &new_elements);
+            match &result {
+                Ok(()) => {
+                    let old = std::mem::replace(&mut chain.elements, new_elements.clone());
+                    remove_chain_async(&bin, old);
+                    chain.processors = new_processors.clone();
+                    chain.config = filters_config.clone();
+                }
+                Err(_) => remove_chain_async(&bin, new_elements.clone()),
+            }
+            chain.swapping = false;
+            let _ = swapped.send(result);
+            gst::PadProbeReturn::Remove
+        });
+
+        match swap_result.recv_timeout(SWAP_TIMEOUT) {
+            Ok(Ok(())) => {
+                info!("Filters replaced");
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(anyhow!(
+                "The filters are not idle, they'll be replaced once they are"
+            )),
+        }
+    }
+}
+
+fn create_elements(
+    factory: &dyn ElementFactory,
+    filters_config: &[FilterConfig],
+    analytics: &AnalyticsCounters,
+) -> Result<(Vec<gst::Element>, Vec<processor::SharedProcessor>), Error> {
+    let mut elements = Vec::new();
+    let mut processors = Vec::new();
+    for config in filters_config {
+        let processor = match config {
+            FilterConfig::NvInfer(infer) => {
+                elements.push(factory.inference(infer)?);
+                continue;
             }
             FilterConfig::Tracker {
                 lib_path,
                 config_path,
-            } => tracker::create_element(lib_path.clone(), config_path.clone())?,
+            } => {
+                elements.push(factory.tracker(lib_path.as_deref(), config_path.as_deref())?);
+                continue;
+            }
+            FilterConfig::Analytics(config) => {
+                let analytics = Analytics::new(config.clone(), analytics.clone());
+                processor::per_source(analytics)
+            }
+            FilterConfig::Processor { name, config } => processor::create_processor(name, config)?,
         };
+        let processor = Arc::new(Mutex::new(processor));
+        elements.push(processor::processor_element(factory, processor.clone())?);
+        processors.push(processor);
+    }
+
+    Ok((elements, processors))
+}
+
+fn link_chain(
+    entry: &gst::Element,
+    elements: &[gst::Element],
+    exit: &gst::Element,
+) -> Result<(), Error> {
+    let mut last = entry;
+    for elem in elements.iter().chain(std::iter::once(exit)) {
+        last.link(elem)?;
+        last = elem;
+    }
 
-        // add element to bin
-        bin.add(&elem)?;
+    Ok(())
+}
 
-        // link element
-        if i == 0 {
-            add_bin_ghost_pad(&bin, &elem, "sink")?;
-        } else if let Some(last_elem) = last_element {
-            last_elem.link(&elem)?
+fn unlink_pad(pad: &gst::Pad) -> Result<(), Error> {
+    if let Some(peer) = pad.peer() {
+        match pad.direction() {
+            gst::PadDirection::Src => pad.unlink(&peer)?,
+            _ => peer.unlink(pad)?,
         }
-        if i == num_filters - 1 {
-            add_bin_ghost_pad(&bin, &elem, "src")?;
+    }
+
+    Ok(())
+}
+
+/// Add `elements` to `bin`, linked to each other but not to the chain, and
+/// bring them to the state of `bin`. They're removed if one can't be.
+pub fn prepare_chain(bin: &gst::Bin, elements: &[gst::Element]) -> Result<(), Error> {
+    let result = add_chain(bin, elements);
+    if result.is_err() {
+        remove_chain(bin, elements);
+    }
+
+    result
+}
+
+fn add_chain(bin: &gst::Bin, elements: &[gst::Element]) -> Result<(), Error> {
+    for elem in elements {
+        bin.add(elem)?;
+    }
+    for pair in elements.windows(2) {
+        pair[0].link(&pair[1])?;
+    }
+    for elem in elements {
+        elem.sync_state_with_parent()?;
+    }
+
+    Ok(())
+}
+
+/// Put `elements`, prepared with [`prepare_chain`], between `entry` and
+/// `exit` in place of `current`. It runs while the entry src pad is idle, so
+/// only the pads at the ends of the chains are relinked.
+///
+/// The buffers `current` still pushes are dropped, and `current` is linked
+/// back if `elements` can't be.
+pub fn swap_chain(
+    entry: &gst::Element,
+    exit: &gst::Element,
+    current: &[gst::Element],
+    elements: &[gst::Element],
+) -> Result<(), Error> {
+    // a filter pushing to an unlinked pad would post an error
+    let drain = current.last().map(|last| {
+        let pad = last.static_pad("src").expect("Cant get filter srcpad");
+        let probe = pad.add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+            |_, _| gst::PadProbeReturn::Drop,
+        );
+        (pad, probe)
+    });
+
+    let result = link_ends(entry, exit, elements);
+    if result.is_err() {
+        if let Some((pad, Some(probe))) = drain {
+            pad.remove_probe(probe);
+        }
+        if let Err(e) = link_ends(entry, exit, current) {
+            error!("Cant link the current filters back: {}", e);
+        }
+    }
+
+    result
+}
+
+/// Link `entry` to the first of `elements` and the last of them to `exit`,
+/// the elements being already linked to each other.
+fn link_ends(
+    entry: &gst::Element,
+    exit: &gst::Element,
+    elements: &[gst::Element],
+) -> Result<(), Error> {
+    unlink_pad(&entry.static_pad("src").expect("Cant get entry srcpad"))?;
+    unlink_pad(&exit.static_pad("sink").expect("Cant get exit sinkpad"))?;
+    match (elements.first(), elements.last()) {
+        (Some(first), Some(last)) => {
+            entry.link(first)?;
+            last.link(exit)?;
         }
+        _ => entry.link(exit)?,
+    }
+
+    Ok(())
+}
 
-        last_element = Some(elem);
+/// Stop the `elements` in `bin` and remove them.
+fn remove_chain(bin: &gst::Bin, elements: &[gst::Element]) {
+    for elem in elements {
+        if elem.parent().as_ref() != Some(bin.upcast_ref()) {
+            continue;
+        }
+        if let Err(e) = elem.set_state(gst::State::Null) {
+            error!("Cant stop filter {}: {}", elem.name(), e);
+        }
+        if let Err(e) = bin.remove(elem) {
+            error!("Cant remove filter {}: {}", elem.name(), e);
+        }
     }
+}
 
-    Ok(bin)
+/// [`remove_chain`] from another thread than the streaming one.
+fn remove_chain_async(bin: &gst::Bin, elements: Vec<gst::Element>) {
+    bin.call_async(move |bin| remove_chain(bin, &elements));
 }
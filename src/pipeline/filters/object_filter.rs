@@ -0,0 +1,69 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use ds::meta_api::ObjectMetaApi;
+
+use crate::common::SourceId;
+
+use super::processor::{parse_config, Frame, FrameProcessor};
+
+/// Objects kept by the `object_filter` processor, all the conditions set
+/// must hold.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectFilterConfig {
+    /// Sources filtered, all of them when empty.
+    pub source_ids: Vec<SourceId>,
+    /// Classes kept, all of them when empty.
+    pub class_ids: Vec<i32>,
+    pub min_confidence: Option<f32>,
+    /// Size of the box, in pixels of the streammux frames.
+    pub min_width: Option<f32>,
+    pub min_height: Option<f32>,
+}
+
+impl ObjectFilterConfig {
+    pub fn keeps(&self, class_id: i32, confidence: f32, width: f32, height: f32) -> bool {
+        (self.class_ids.is_empty() || self.class_ids.contains(&class_id))
+            && self.min_confidence.is_none_or(|min| confidence >= min)
+            && self.min_width.is_none_or(|min| width >= min)
+            && self.min_height.is_none_or(|min| height >= min)
+    }
+}
+
+/// Removes the objects of the frames its config doesn't keep.
+pub struct ObjectFilter {
+    config: ObjectFilterConfig,
+}
+
+impl ObjectFilter {
+    pub fn new(config: &serde_yaml::Value) -> Result<Self, Error> {
+        Ok(ObjectFilter {
+            config: parse_config(config)?,
+        })
+    }
+
+    fn keeps(&self, object: &dyn ObjectMetaApi) -> bool {
+        let bbox = object.bbox();
+        self.config.keeps(
+            object.class_id(),
+            object.confidence(),
+            bbox.width,
+            bbox.height,
+        )
+    }
+}
+
+impl FrameProcessor for ObjectFilter {
+    type State = ();
+
+    fn process_frame(&mut self, frame: &mut Frame, _state: &mut ()) {
+        let source_ids = &self.config.source_ids;
+        if !source_ids.is_empty() && !source_ids.contains(&frame.source_id()) {
+            return;
+        }
+        frame
+            .frame_meta
+            .retain_objects(&mut |object| self.keeps(object));
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Error};
+use gst::prelude::*;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ds::gst_meta::batch_meta_ptrs;
+use ds::meta::NvDsBatchMeta;
+use ds::meta_api::{BatchMetaApiMut, FrameMetaApiMut};
+use ds::nvbufsurface::{NvBufSurface, NvBufSurfaceParams};
+use ds::owned_meta::BatchGstMeta;
+use ds::user_meta::UserMetaData;
+
+use crate::common::SourceId;
+
+use super::super::backend::ElementFactory;
+use super::object_filter::ObjectFilter;
+
+/// Processor of a filter, shared with the chain to drop the state of the
+/// removed sources.
+pub type SharedProcessor = Arc<Mutex<Box<dyn BatchProcessor>>>;
+
+type Constructor =
+    Box<dyn Fn(&serde_yaml::Value) -> Result<Box<dyn BatchProcessor>, Error> + Send + Sync>;
+
+/// Logic run on the frames going through the filters, added to the chain by
+/// a `Processor` filter with the name it's registered with.
+///
+/// The frames are the ones of the DeepStream batches and of the
+/// [`BatchGstMeta`] batches.
+pub trait FrameProcessor: Send + 'static {
+    /// State of a source, created on its first frame.
+    type State: Default + Send;
+
+    /// Map the frames surface for `process_frame`.
+    fn needs_surface(&self) -> bool {
+        false
+    }
+
+    fn process_frame(&mut self, frame: &mut Frame, state: &mut Self::State);
+}
+
+/// A frame of a batch.
+pub struct Frame<'a> {
+    pub frame_meta: &'a mut dyn FrameMetaApiMut,
+    /// Set if the processor needs it and the buffer is a DeepStream one.
+    pub surface: Option<&'a NvBufSurfaceParams>,
+}
+
+impl<'a> Frame<'a> {
+    pub fn source_id(&self) -> SourceId {
+        self.frame_meta.source_id() as SourceId
+    }
+
+    /// Add `data` to the frame user metas.
+    pub fn attach_user_meta<T: UserMetaData>(&mut self, data: T) {
+        self.frame_meta.add_user_data(Box::new(data));
+    }
+}
+
+impl<'a> std::fmt::Debug for Frame<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Frame")
+            .field("source_id", &self.frame_meta.source_id())
+            .field("frame_number", &self.frame_meta.frame_number())
+            .field("surface", &self.surface)
+            .finish()
+    }
+}
+
+/// A [`FrameProcessor`] with the states of the sources.
+pub trait BatchProcessor: Send {
+    fn needs_surface(&self) -> bool;
+
+    fn process_batch(&mut self, batch: &mut dyn BatchMetaApiMut, surface: Option<&NvBufSurface>);
+
+    /// Drop the state of a removed source.
+    fn remove_source(&mut self, source_id: &SourceId);
+}
+
+struct PerSource<P: FrameProcessor> {
+    processor: P,
+    states: HashMap<SourceId, P::State>,
+}
+
+impl<P: FrameProcessor> BatchProcessor for PerSource<P> {
+    fn needs_surface(&self) -> bool {
+        self.processor.needs_surface()
+    }
+
+    fn process_batch(&mut self, batch: &mut dyn BatchMetaApiMut, surface: Option<&NvBufSurface>) {
+        for frame_meta in batch.frames_mut() {
+            let state = self
+                .states
+                .entry(frame_meta.source_id() as SourceId)
+                .or_default();
+            let surface = surface.and_then(|s| s.surface_params(frame_meta.batch_id()));
+            let mut frame = Frame {
+                frame_meta,
+                surface,
+            };
+            self.processor.process_frame(&mut frame, state);
+        }
+    }
+
+    fn remove_source(&mut self, source_id: &SourceId) {
+        self.states.remove(source_id);
+    }
+}
+
+static PROCESSORS: Lazy<Mutex<BTreeMap<String, Constructor>>> = Lazy::new(Default::default);
+
+fn registry() -> MutexGuard<'static, BTreeMap<String, Constructor>> {
+    PROCESSORS.lock().unwrap()
+}
+
+fn constructor<P, F>(new: F) -> Constructor
+where
+    P: FrameProcessor,
+    F: Fn(&serde_yaml::Value) -> Result<P, Error> + Send + Sync + 'static,
+{
+    Box::new(move |config| Ok(per_source(new(config)?)))
+}
+
+/// Register a processor as `name`, `new` creates it from the `config` of its
+/// filter. A processor already registered as `name` is replaced.
+pub fn register_processor<P, F>(name: &str, new: F)
+where
+    P: FrameProcessor,
+    F: Fn(&serde_yaml::Value) -> Result<P, Error> + Send + Sync + 'static,
+{
+    registry().insert(name.to_string(), constructor(new));
+}
+
+/// Register the processors of this crate, before the configs are loaded.
+pub fn register_builtin_processors() {
+    register_processor("object_filter", ObjectFilter::new);
+}
+
+pub fn processor_names() -> Vec<String> {
+    registry().keys().cloned().collect()
+}
+
+pub fn create_processor(
+    name: &str,
+    config: &serde_yaml::Value,
+) -> Result<Box<dyn BatchProcessor>, Error> {
+    let registry = registry();
+    let new = registry
+        .get(name)
+        .ok_or_else(|| anyhow!("No processor registered as {}", name))?;
+    new(config)
+}
+
+/// Config of a processor, its defaults if the filter has none.
+pub fn parse_config<T: DeserializeOwned + Default>(config: &serde_yaml::Value) -> Result<T, Error> {
+    if config.is_null() {
+        return Ok(T::default());
+    }
+    Ok(serde_yaml::from_value(config.clone())?)
+}
+
+/// `processor` with a state per source.
+pub fn per_source<P: FrameProcessor>(processor: P) -> Box<dyn BatchProcessor> {
+    Box::new(PerSource {
+        processor,
+        states: HashMap::new(),
+    })
+}
+
+/// Element of `factory` running `processor` on the buffers it forwards.
+pub fn processor_element(
+    factory: &dyn ElementFactory,
+    processor: SharedProcessor,
+) -> Result<gst::Element, Error> {
+    let element = factory.frame_processor()?;
+    let pad = element
+        .static_pad("src")
+        .expect("Cant get frame processor srcpad");
+
+    pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+        if let Some(gst::PadProbeData::Buffer(buffer)) = &mut info.data {
+            let mut processor = processor.lock().unwrap();
+
+            {
+                // the surface is read from the memory of the NVMM buffers,
+                // mapped until the batch is processed
+                let caps = pad.current_caps();
+                let map = match (processor.needs_surface(), &caps) {
+                    (true, Some(_)) => buffer.map_readable().ok(),
+                    _ => None,
+                };
+                let surface = map
+                    .as_ref()
+                    .zip(caps.as_ref())
+                    .and_then(|(map, caps)| NvBufSurface::from_buffer_map(map, caps));
+
+                for ptr in batch_meta_ptrs(buffer) {
+                    // the metas are added to the batch meta, not the buffer
+                    let batch_meta = unsafe { NvDsBatchMeta::from_ptr(ptr) };
+                    processor.process_batch(batch_meta, surface);
+                }
+            }
+
+            // the owned batches are metas of the buffer, it's only made
+            // writable if it has some
+            if buffer.meta::<BatchGstMeta>().is_some() {
+                for mut meta in buffer.make_mut().iter_meta_mut::<BatchGstMeta>() {
+                    processor.process_batch(meta.batch_mut(), None);
+                }
+            }
+        }
+
+        gst::PadProbeReturn::Ok
+    });
+
+    Ok(element)
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::common::SourceId;
+use crate::pipeline::analytics::{
+    AnalyticsEvent, Direction, EventKind, ObjectPosition, SourceAnalytics,
+};
+use crate::pipeline::config::SourceAnalyticsConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LineCounts {
+    #[serde(rename = "in")]
+    pub crossed_in: u64,
+    #[serde(rename = "out")]
+    pub crossed_out: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ZoneCounts {
+    pub entries: u64,
+    pub exits: u64,
+    /// Objects that reached the loitering threshold of the zone.
+    pub loitering: u64,
+    /// Objects in the zone.
+    pub occupancy: u64,
+}
+
+/// Cumulative counts of the lines and zones of a source, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SourceAnalyticsCounts {
+    pub lines: BTreeMap<String, LineCounts>,
+    pub zones: BTreeMap<String, ZoneCounts>,
+}
+
+impl SourceAnalyticsCounts {
+    /// Counts at zero for all the lines and zones of `config`.
+    pub fn new(config: &SourceAnalyticsConfig) -> Self {
+        SourceAnalyticsCounts {
+            lines: config
+                .lines
+                .iter()
+                .map(|line| (line.name.clone(), LineCounts::default()))
+                .collect(),
+            zones: config
+                .zones
+                .iter()
+                .map(|zone| (zone.name.clone(), ZoneCounts::default()))
+                .collect(),
+        }
+    }
+
+    pub fn apply(&mut self, event: &AnalyticsEvent) {
+        match event.kind {
+            EventKind::LineCrossing { direction } => {
+                let line = self.lines.entry(event.name.clone()).or_default();
+                match direction {
+                    Direction::In => line.crossed_in += 1,
+                    Direction::Out => line.crossed_out += 1,
+                }
+            }
+            EventKind::ZoneEntry => {
+                let zone = self.zones.entry(event.name.clone()).or_default();
+                zone.entries += 1;
+                zone.occupancy += 1;
+            }
+            EventKind::Loitering { .. } => {
+                self.zones.entry(event.name.clone()).or_default().loitering += 1;
+            }
+            EventKind::ZoneExit { .. } => {
+                let zone = self.zones.entry(event.name.clone()).or_default();
+                zone.exits += 1;
+                zone.occupancy = zone.occupancy.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Counts and tracked objects of the analytics filter, kept when the filters
+/// are replaced so the objects in the zones still leave them after.
+#[derive(Clone, Default)]
+pub struct AnalyticsCounters {
+    counts_by_source: Arc<Mutex<HashMap<SourceId, SourceAnalyticsCounts>>>,
+    trackers: Arc<Mutex<HashMap<SourceId, SourceAnalytics>>>,
+}
+
+impl AnalyticsCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the `events` of a frame of `source_id`, returning its counts.
+    pub fn apply(
+        &self,
+        source_id: SourceId,
+        config: &SourceAnalyticsConfig,
+        events: &[AnalyticsEvent],
+    ) -> SourceAnalyticsCounts {
+        let mut counts_by_source = self.counts_by_source.lock().unwrap();
+        let counts = counts_by_source
+            .entry(source_id)
+            .or_insert_with(|| SourceAnalyticsCounts::new(config));
+        for event in events {
+            counts.apply(event);
+        }
+        counts.clone()
+    }
+
+    /// Track the `objects` of a frame of `source_id`, returning its events and
+    /// counts.
+    ///
+    /// The tracker of the source is replaced when its config changes, the
+    /// objects it had in the zones are forgotten and the occupancy of the
+    /// zones starts over.
+    pub fn update(
+        &self,
+        source_id: SourceId,
+        config: &SourceAnalyticsConfig,
+        max_missing_frames: u32,
+        timestamp: Duration,
+        objects: &[ObjectPosition],
+    ) -> (Vec<AnalyticsEvent>, SourceAnalyticsCounts) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let tracker = trackers
+            .entry(source_id)
+            .or_insert_with(|| SourceAnalytics::new(config.clone(), max_missing_frames));
+        if tracker.config() != config || tracker.max_missing_frames() != max_missing_frames {
+            *tracker = SourceAnalytics::new(config.clone(), max_missing_frames);
+            self.reset_occupancy(source_id);
+        }
+        let events = tracker.update(timestamp, objects);
+        let counts = self.apply(source_id, config, &events);
+        (events, counts)
+    }
+
+    fn reset_occupancy(&self, source_id: SourceId) {
+        if let Some(counts) = self.counts_by_source.lock().unwrap().get_mut(&source_id) {
+            for zone in counts.zones.values_mut() {
+                zone.occupancy = 0;
+            }
+        }
+    }
+
+    pub fn counts(&self, source_id: &SourceId) -> Option<SourceAnalyticsCounts> {
+        self.counts_by_source
+            .lock()
+            .unwrap()
+            .get(source_id)
+            .cloned()
+    }
+
+    /// Forget the counts and objects of a removed source.
+    pub fn remove(&self, source_id: &SourceId) {
+        self.counts_by_source.lock().unwrap().remove(source_id);
+        self.trackers.lock().unwrap().remove(source_id);
+    }
+}
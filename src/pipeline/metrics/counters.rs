@@ -0,0 +1,73 @@
+use anyhow::Error;
+use gst::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ds::meta_api::for_each_batch_meta;
+
+use crate::common::SourceId;
+
+/// Frames and objects of a source seen since it was added.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SourceCounters {
+    pub frames: u64,
+    /// Detections by class label, or class id for the objects without label,
+    /// an object is counted in every frame it's detected in.
+    pub objects: HashMap<String, u64>,
+}
+
+pub struct StreamCounters {
+    counters_by_source: Arc<Mutex<HashMap<SourceId, SourceCounters>>>,
+}
+
+impl StreamCounters {
+    pub fn new(bin: &gst::Bin) -> Result<Self, Error> {
+        let stream_counters = Self {
+            counters_by_source: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let srcpad: gst::Pad = bin.static_pad("src").expect("Failed to get srcpad");
+        let counters_by_source_clone = stream_counters.counters_by_source.clone();
+        srcpad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                let mut counters_by_source = counters_by_source_clone.lock().unwrap();
+
+                for_each_batch_meta(buffer, |batch_meta| {
+                    for frame in batch_meta.frames() {
+                        let counters = counters_by_source
+                            .entry(frame.source_id() as SourceId)
+                            .or_default();
+                        counters.frames += 1;
+
+                        for object in frame.objects() {
+                            let class = match object.obj_label() {
+                                "" => object.class_id().to_string(),
+                                label => label.to_string(),
+                            };
+                            *counters.objects.entry(class).or_default() += 1;
+                        }
+                    }
+                });
+            }
+
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(stream_counters)
+    }
+
+    pub fn counters(&self, source_id: &SourceId) -> Option<SourceCounters> {
+        self.counters_by_source
+            .lock()
+            .unwrap()
+            .get(source_id)
+            .cloned()
+    }
+
+    /// Forget the counters of a removed source, a source added again with the
+    /// same id starts from zero.
+    pub fn remove(&self, source_id: &SourceId) {
+        self.counters_by_source.lock().unwrap().remove(source_id);
+    }
+}
@@ -1,100 +1,176 @@
 use anyhow::Error;
 use gst::prelude::*;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-use ds::gst_meta::{DsMeta, GstNvDsMetaType};
+use ds::meta_api::for_each_batch_meta;
 
 use crate::common::SourceId;
 
-struct Metric {
-    last_instant: Option<Instant>,
-    fps_vec: Vec<f64>,
-    n: usize,
-    max_seconds: u64,
+use super::super::config::MetricsConfig;
+
+/// Source of the time of the frames, replaced in the tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
 }
 
-impl Metric {
-    fn new() -> Self {
-        Self {
-            last_instant: None,
-            fps_vec: Vec::new(),
-            n: 100,
-            max_seconds: 60,
-        }
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
     }
+}
 
-    fn update(&mut self) {
-        // update fps list
-        if let Some(last_instant) = self.last_instant {
-            // reset
-            if last_instant.elapsed() > Duration::new(self.max_seconds, 0) {}
+/// FPS of a source over the window, `min`, `max` and the percentiles are those
+/// of the instantaneous FPS between two frames.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FpsStats {
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
 
-            self.fps_vec
-                .push(1.0 / last_instant.elapsed().as_secs_f64());
-            if self.fps_vec.len() > self.n {
-                self.fps_vec.remove(0);
-            }
+/// Counts the frames of a source over a sliding time window.
+pub struct FpsCounter {
+    window: Duration,
+    stall_timeout: Duration,
+    clock: Arc<dyn Clock>,
+    frames: VecDeque<Instant>,
+    /// Last frame, kept once it's out of the window to detect the stalls.
+    last_frame: Option<Instant>,
+}
+
+impl FpsCounter {
+    pub fn new(window: Duration, stall_timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        FpsCounter {
+            window,
+            stall_timeout,
+            clock,
+            frames: VecDeque::new(),
+            last_frame: None,
+        }
+    }
+
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        if self.is_stalled(now) {
+            self.frames.clear();
         }
 
-        // update instant
-        self.last_instant = Some(Instant::now());
+        self.last_frame = Some(now);
+        self.frames.push_back(now);
+        self.evict(now);
     }
 
-    fn fps(&self) -> Option<f64> {
-        if self.fps_vec.len() == 0 {
+    /// Stats over the window, `None` before the first frame or once the source
+    /// stalled for `stall_timeout`.
+    pub fn stats(&mut self) -> Option<FpsStats> {
+        let now = self.clock.now();
+        if self.last_frame.is_none() || self.is_stalled(now) {
             return None;
         }
+        self.evict(now);
 
-        if let Some(last_instant) = self.last_instant {
-            if last_instant.elapsed() > Duration::new(self.max_seconds, 0) {
-                return None;
+        // frame intervals over the time between the first and the last frame
+        let avg = match (self.frames.front(), self.frames.back()) {
+            (Some(first), Some(last)) if last > first => {
+                (self.frames.len() - 1) as f64 / last.duration_since(*first).as_secs_f64()
             }
+            _ => 0.0,
+        };
+
+        let mut rates: Vec<f64> = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|(previous, next)| next.saturating_duration_since(*previous))
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| 1.0 / interval.as_secs_f64())
+            .collect();
+        rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        Some(FpsStats {
+            avg,
+            min: rates.first().copied().unwrap_or(avg),
+            max: rates.last().copied().unwrap_or(avg),
+            p50: percentile(&rates, 50.0).unwrap_or(avg),
+            p90: percentile(&rates, 90.0).unwrap_or(avg),
+            p99: percentile(&rates, 99.0).unwrap_or(avg),
+        })
+    }
+
+    fn is_stalled(&self, now: Instant) -> bool {
+        match self.last_frame {
+            Some(last) => now.saturating_duration_since(last) > self.stall_timeout,
+            None => false,
         }
+    }
 
-        // return average fps
-        let mut sum = 0.0;
-        for fps in &self.fps_vec {
-            sum += fps
+    fn evict(&mut self, now: Instant) {
+        while let Some(frame) = self.frames.front() {
+            if now.saturating_duration_since(*frame) <= self.window {
+                break;
+            }
+            self.frames.pop_front();
         }
-        Some(sum / self.fps_vec.len() as f64)
     }
 }
 
+/// Nearest-rank percentile of sorted `values`.
+fn percentile(values: &[f64], percent: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let rank = (percent / 100.0 * values.len() as f64).ceil() as usize;
+
+    Some(values[rank.clamp(1, values.len()) - 1])
+}
+
+#[derive(Clone)]
 pub struct FPSMetrics {
-    metric_by_source: Arc<Mutex<HashMap<SourceId, Metric>>>,
+    metric_by_source: Arc<Mutex<HashMap<SourceId, FpsCounter>>>,
 }
 
 impl FPSMetrics {
-    pub fn new(bin: &gst::Bin) -> Result<Self, Error> {
+    pub fn new(bin: &gst::Bin, config: &MetricsConfig) -> Result<Self, Error> {
+        Self::with_clock(bin, config, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(
+        bin: &gst::Bin,
+        config: &MetricsConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, Error> {
         let fps_metrics = Self {
             metric_by_source: Arc::new(Mutex::new(HashMap::new())),
         };
+        let window = Duration::from_secs_f64(config.fps_window);
+        let stall_timeout = Duration::from_secs_f64(config.stall_timeout);
 
         let srcpad: gst::Pad = bin.static_pad("src").expect("Failed to get srcpad");
         let metric_by_source_clone = fps_metrics.metric_by_source.clone();
         srcpad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
-            if let Some(gst::PadProbeData::Buffer(ref mut buffer)) = &mut info.data {
-                let buffer = buffer.make_mut();
-
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
                 let mut metrics = metric_by_source_clone.lock().unwrap();
 
-                for mut meta in buffer.iter_meta_mut::<DsMeta>() {
-                    if let GstNvDsMetaType::BatchGstMeta = meta.meta_type() {
-                        let mut batch_meta = meta.batch_meta().unwrap();
-                        for mut frame in batch_meta.iter_frame() {
-                            // get frame metric
-                            let source_id = frame.source_id();
-                            let mut metric = metrics
-                                .entry(source_id as SourceId)
-                                .or_insert(Metric::new());
-
-                            // update metric
-                            metric.update();
-                        }
+                for_each_batch_meta(buffer, |batch_meta| {
+                    for frame in batch_meta.frames() {
+                        // get frame metric
+                        let source_id = frame.source_id();
+                        let metric = metrics.entry(source_id as SourceId).or_insert_with(|| {
+                            FpsCounter::new(window, stall_timeout, clock.clone())
+                        });
+
+                        // update metric
+                        metric.update();
                     }
-                }
+                });
             }
 
             gst::PadProbeReturn::Ok
@@ -103,13 +179,14 @@ impl FPSMetrics {
         Ok(fps_metrics)
     }
 
-    pub fn fps(&self, source_id: &SourceId) -> Option<f64> {
-        let metric_by_source = self.metric_by_source.lock().unwrap();
+    pub fn fps(&self, source_id: &SourceId) -> Option<FpsStats> {
+        let mut metric_by_source = self.metric_by_source.lock().unwrap();
 
-        if let Some(metric) = metric_by_source.get(source_id) {
-            metric.fps()
-        } else {
-            None
-        }
+        metric_by_source.get_mut(source_id)?.stats()
+    }
+
+    /// Forget the frames of a removed source.
+    pub fn remove(&self, source_id: &SourceId) {
+        self.metric_by_source.lock().unwrap().remove(source_id);
     }
 }
@@ -0,0 +1,178 @@
+use gst::prelude::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ds::meta_api::for_each_batch_meta;
+
+use crate::common::SourceId;
+
+/// Upper bounds of the latency histograms buckets, in seconds.
+pub const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Stamps older than this are dropped, their frames never reached the stages.
+const MAX_STAMP_AGE: Duration = Duration::from_secs(30);
+
+/// Stage of the latency reported by DeepStream itself.
+const DEEPSTREAM_STAGE: &str = "deepstream";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Count of the values in each of [`LATENCY_BUCKETS`], not cumulative.
+    counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn observe(&mut self, value: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.counts[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Upper bound of every bucket with the count of the values lower or equal
+    /// to it, the values above the last bound are only in `count`.
+    pub fn cumulative(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        LATENCY_BUCKETS
+            .iter()
+            .zip(self.counts.iter())
+            .scan(0, |total, (bound, count)| {
+                *total += count;
+                Some((*bound, *total))
+            })
+    }
+}
+
+#[derive(Default)]
+struct LatencyState {
+    /// When the buffers of the sources left their bin, by source and pts.
+    stamps: HashMap<(SourceId, u64), Instant>,
+    stamps_order: VecDeque<(Instant, (SourceId, u64))>,
+    histograms: HashMap<SourceId, BTreeMap<String, Histogram>>,
+}
+
+impl LatencyState {
+    fn stamp(&mut self, key: (SourceId, u64), now: Instant) {
+        while let Some((stamped, key)) = self.stamps_order.front() {
+            if now.saturating_duration_since(*stamped) < MAX_STAMP_AGE {
+                break;
+            }
+            // a key stamped again is only removed with its last stamp
+            if self.stamps.get(key) == Some(stamped) {
+                self.stamps.remove(key);
+            }
+            self.stamps_order.pop_front();
+        }
+
+        self.stamps.insert(key, now);
+        self.stamps_order.push_back((now, key));
+    }
+
+    fn remove(&mut self, source_id: &SourceId) {
+        self.stamps.retain(|(id, _), _| id != source_id);
+        self.stamps_order.retain(|(_, (id, _))| id != source_id);
+        self.histograms.remove(source_id);
+    }
+
+    fn observe(&mut self, source_id: SourceId, stage: &str, latency: Duration) {
+        self.histograms
+            .entry(source_id)
+            .or_default()
+            .entry(stage.to_string())
+            .or_default()
+            .observe(latency.as_secs_f64());
+    }
+}
+
+/// Latency of the frames from their source bin to the stages of the pipeline.
+///
+/// The buffers are stamped with their pts when they leave the source bin,
+/// the stages find the stamp of each frame of a batch from the `source_id`
+/// and `buf_pts` of its frame meta.
+#[derive(Clone, Default)]
+pub struct LatencyTracker {
+    state: Arc<Mutex<LatencyState>>,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp the buffers of source `source_id` going through `pad`.
+    pub fn stamp_source(&self, pad: &gst::Pad, source_id: SourceId) {
+        let state = self.state.clone();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                if let Some(pts) = buffer.pts() {
+                    let key = (source_id, pts.nseconds());
+                    state.lock().unwrap().stamp(key, Instant::now());
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    /// Measure the latency of the frames going through `pad` as `stage`.
+    pub fn measure(&self, pad: &gst::Pad, stage: &str) {
+        let state = self.state.clone();
+        let stage = stage.to_string();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                let now = Instant::now();
+                let mut state = state.lock().unwrap();
+
+                for_each_batch_meta(buffer, |batch_meta| {
+                    for frame in batch_meta.frames() {
+                        let source_id = frame.source_id() as SourceId;
+                        let key = (source_id, frame.buf_pts());
+                        if let Some(stamp) = state.stamps.get(&key).copied() {
+                            state.observe(source_id, &stage, now.saturating_duration_since(stamp));
+                        }
+                    }
+                });
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    /// Measure the latency DeepStream reports for the frames going through
+    /// `pad`, when its latency measurement is enabled.
+    pub fn measure_deepstream(&self, pad: &gst::Pad, batch_size: usize) {
+        let state = self.state.clone();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                let latencies = ds::latency::measure_buffer_latency(buffer, batch_size);
+
+                let mut state = state.lock().unwrap();
+                for frame in latencies {
+                    state.observe(frame.source_id as SourceId, DEEPSTREAM_STAGE, frame.latency);
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    /// Latency histograms of every source, by stage.
+    pub fn histograms(&self) -> HashMap<SourceId, BTreeMap<String, Histogram>> {
+        self.state.lock().unwrap().histograms.clone()
+    }
+
+    /// Forget the stamps and histograms of a removed source.
+    pub fn remove(&self, source_id: &SourceId) {
+        self.state.lock().unwrap().remove(source_id);
+    }
+}
@@ -1,3 +1,9 @@
+pub mod analytics;
+pub mod counters;
 pub mod fps;
+pub mod latency;
 
-pub use fps::FPSMetrics;
+pub use analytics::{AnalyticsCounters, SourceAnalyticsCounts};
+pub use counters::{SourceCounters, StreamCounters};
+pub use fps::{FPSMetrics, FpsStats};
+pub use latency::{Histogram, LatencyTracker};
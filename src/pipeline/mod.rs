@@ -2,44 +2,71 @@ use gst::prelude::*;
 use gst::MessageView;
 
 use anyhow::{anyhow, Error};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
 use crate::common::SourceId;
 
 mod common;
-use common::MissingElement;
 
+pub mod analytics;
+pub mod backend;
 pub mod config;
-mod filters;
-mod metrics;
+pub mod description;
+pub mod filters;
+pub mod metrics;
+pub mod overlay;
 mod sinks;
+mod source_state;
 pub mod sources;
 
+pub use metrics::{FpsStats, Histogram, SourceAnalyticsCounts, SourceCounters};
+pub use source_state::{Recovery, SourceRegistry, SourceState, SourceStatus, MAX_SOURCE_RESTARTS};
+
+/// Time to wait before restarting a failed source.
+const SOURCE_RESTART_DELAY: Duration = Duration::from_secs(5);
+
 pub struct Pipeline {
+    factory: Arc<dyn backend::ElementFactory>,
     pipeline: gst::Pipeline,
     streammux: gst::Element,
     pipeline_sink: sinks::PipelineSink,
-    sources_bin_name: HashMap<SourceId, String>,
+    sources: Arc<Mutex<SourceRegistry>>,
+    /// Kept to be notified of their removal.
+    source_objects: HashMap<SourceId, Box<dyn sources::Source>>,
     fps_metrics: metrics::FPSMetrics,
+    stream_counters: metrics::StreamCounters,
+    latency: metrics::LatencyTracker,
+    filters: filters::FilterChain,
+    overlay: overlay::Overlay,
 }
 
 impl Pipeline {
     pub fn new(
+        backend: config::Backend,
         streammux_config: config::StreamMuxConfig,
         filters_config: Vec<config::FilterConfig>,
         sinks_config: config::SinksConfig,
+        metrics_config: config::MetricsConfig,
+        overlay_config: config::OverlayConfig,
     ) -> Result<Self, Error> {
         gst::init()?;
 
+        let factory = backend::create_factory(backend);
         let pipeline = gst::Pipeline::new(None);
 
         // create elementes
-        let streammux = create_streamux(&streammux_config).expect("Cant create steamux");
-        let filters_bin = filters::create_bin(filters_config)?;
-        let pipeline_sink = sinks::PipelineSink::new(sinks_config)?;
+        let streammux = factory
+            .streammux(&streammux_config)
+            .expect("Cant create steamux");
+        let filters = filters::FilterChain::new(factory.clone(), filters_config)?;
+        let filters_bin = filters.bin.clone();
+        let latency = metrics::LatencyTracker::new();
+        let pipeline_sink = sinks::PipelineSink::new(&factory, sinks_config, &latency)?;
         // add elements
         pipeline.add_many(&[&streammux])?;
         pipeline.add(&filters_bin)?;
@@ -53,70 +80,162 @@ impl Pipeline {
             .link(&pipeline_sink.bin)
             .expect("Failed to link filters_bin with sink");
 
-        let fps_metrics = metrics::FPSMetrics::new(&filters_bin)?;
+        let fps_metrics = metrics::FPSMetrics::new(&filters_bin, &metrics_config)?;
+        let stream_counters = metrics::StreamCounters::new(&filters_bin)?;
+
+        // latency of every stage since the sources
+        let streammux_pad = streammux
+            .static_pad("src")
+            .expect("Cant get streamux srcpad");
+        latency.measure(&streammux_pad, "streammux");
+        let filters_pad = filters_bin.static_pad("src").expect("Failed to get srcpad");
+        latency.measure(&filters_pad, "filters");
+        let sink_pad = pipeline_sink
+            .bin
+            .static_pad("sink")
+            .expect("Cant get sink bin sinkpad");
+        if backend == config::Backend::Nvidia && ds::latency::latency_measurement_enabled() {
+            latency.measure_deepstream(&sink_pad, streammux_config.batch_size as usize);
+        }
+
+        // drawn by the osd of the sink bin
+        let overlay = overlay::Overlay::new(&sink_pad, overlay_config, fps_metrics.clone());
 
         Ok(Pipeline {
+            factory,
             pipeline,
             streammux,
             pipeline_sink,
-            sources_bin_name: HashMap::new(),
+            sources: Arc::new(Mutex::new(SourceRegistry::default())),
+            source_objects: HashMap::new(),
             fps_metrics,
+            stream_counters,
+            latency,
+            filters,
+            overlay,
         })
     }
 
-    pub fn add_source(&mut self, src: &dyn sources::Source, id: &SourceId) -> Result<(), Error> {
+    /// Element factory of the pipeline backend, to create the sources with.
+    pub fn element_factory(&self) -> Arc<dyn backend::ElementFactory> {
+        self.factory.clone()
+    }
+
+    pub fn add_source(
+        &mut self,
+        src: Box<dyn sources::Source>,
+        id: &SourceId,
+    ) -> Result<(), Error> {
         debug!("Adding source {} ...", id);
-        if self.sources_bin_name.get(id).is_some() {
+        if self.sources.lock().unwrap().contains(id) {
             return Err(anyhow!("Source {} alredy in pipelein", id));
         }
 
         let bin = src.get_bin();
-        self.pipeline.add_many(&[bin])?;
-        let sink_name = format!("sink_{}", id);
+        self.pipeline.add(bin)?;
+        if let Err(e) = self.link_source(bin, id) {
+            self.unlink_source(bin, id);
+            return Err(e);
+        }
+
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(*id, bin.clone(), src.reconnects());
+
+        debug!("Source {} added with name {}", id, bin.name());
+        self.source_objects.insert(*id, src);
+        Ok(())
+    }
 
+    /// Link the bin of a source, already in the pipeline, to the streammux
+    /// and start it.
+    fn link_source(&mut self, bin: &gst::Bin, id: &SourceId) -> Result<(), Error> {
+        let sink_name = format!("sink_{}", id);
         let sinkpad = self
             .streammux
             .request_pad_simple(&sink_name)
-            .expect("Cant get streamux sinkpad");
-        let srcpad = bin.static_pad("src").expect("Catn get source bin srcpad");
+            .ok_or_else(|| anyhow!("Cant get streamux {} pad", sink_name))?;
+        let srcpad = bin
+            .static_pad("src")
+            .ok_or_else(|| anyhow!("Cant get source bin srcpad"))?;
         srcpad.link(&sinkpad)?;
+        self.latency.stamp_source(&srcpad, *id);
+
+        // Keep the source EOS out of the streammux, it would end the whole batch
+        let sources = self.sources.clone();
+        let source_id = *id;
+        srcpad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            match &info.data {
+                Some(gst::PadProbeData::Event(event)) if event.type_() == gst::EventType::Eos => {}
+                _ => return gst::PadProbeReturn::Ok,
+            }
+            info!("Source {} reached the end of stream", source_id);
+            sources
+                .lock()
+                .unwrap()
+                .set_status(&source_id, SourceStatus::Eos);
+
+            // the bin can't be stopped from its streaming thread
+            let sources = sources.clone();
+            glib::idle_add(move || {
+                Self::stop_ended_source(&sources, source_id);
+                glib::Continue(false)
+            });
+
+            gst::PadProbeReturn::Drop
+        });
 
         // Start bin
         bin.sync_state_with_parent()?;
 
         // Add source rtsp sink
-        self.pipeline_sink.add_source_sink(id)?;
-
-        self.sources_bin_name.insert(*id, bin.name().to_string());
+        self.pipeline_sink.add_source_sink(id)
+    }
 
-        debug!("Source {} added with name {}", id, bin.name());
-        Ok(())
+    /// Undo what was done by a failed [`Pipeline::link_source`], so the
+    /// source can be added again.
+    fn unlink_source(&mut self, bin: &gst::Bin, id: &SourceId) {
+        if let Err(e) = bin.set_state(gst::State::Null) {
+            warn!("Cant stop source {}: {}", id, e);
+        }
+        if let Some(sinkpad) = self.streammux.static_pad(&format!("sink_{}", id)) {
+            self.streammux.release_request_pad(&sinkpad);
+        }
+        if let Err(e) = self.pipeline.remove(bin) {
+            warn!("Cant remove source {}: {}", id, e);
+        }
+        self.latency.remove(id);
     }
 
     pub fn remove_source(&mut self, id: &SourceId) -> Result<(), Error> {
         debug!("Removing source {} ...", id);
-        if let Some(bin_name) = self.sources_bin_name.remove(id) {
-            // get source bin
-            let bin = self.pipeline.by_name(&bin_name).unwrap();
-
+        let bin = self.sources.lock().unwrap().remove(id);
+        if let Some(bin) = bin {
             // stop bin
             bin.set_state(gst::State::Null)?;
 
             // unlink source bin from streamux
             let sink_name = format!("sink_{}", id);
-            let sinkpad = self
-                .streammux
-                .static_pad(&sink_name)
-                .expect("Cant get streamux sinkpad");
-            sinkpad.send_event(gst::event::FlushStop::new(false));
-            self.streammux.release_request_pad(&sinkpad);
+            if let Some(sinkpad) = self.streammux.static_pad(&sink_name) {
+                sinkpad.send_event(gst::event::FlushStop::new(false));
+                self.streammux.release_request_pad(&sinkpad);
+            }
 
             self.pipeline.remove(&bin)?;
 
             // Remove source sink
             self.pipeline_sink.remove_source_sink(id)?;
 
-            debug!("Source {} removed with name {}", id, bin_name);
+            if let Some(mut src) = self.source_objects.remove(id) {
+                src.on_remove();
+            }
+            self.fps_metrics.remove(id);
+            self.stream_counters.remove(id);
+            self.filters.remove_source(id);
+            self.latency.remove(id);
+
+            debug!("Source {} removed with name {}", id, bin.name());
         } else {
             return Err(anyhow!("Source {} not found", id));
         }
@@ -135,6 +254,7 @@ impl Pipeline {
 
         let pipeline_weak = self.pipeline.downgrade();
         let main_loop_clone = main_loop.clone();
+        let sources = self.sources.clone();
         bus.connect_message(None, move |_, msg| {
             let pipeline = match pipeline_weak.upgrade() {
                 Some(pipeline) => pipeline,
@@ -142,13 +262,24 @@ impl Pipeline {
             };
 
             match msg.view() {
-                // Just set the pipeline to READY (which stops playback).
+                // the EOS of the sources are kept out of the streammux, this one
+                // comes from the removed sinks, the other sources keep playing
                 MessageView::Eos(..) => {
-                    pipeline
-                        .set_state(gst::State::Ready)
-                        .expect("Unable to set the pipeline to the `Ready` state");
+                    debug!(
+                        "End of stream from {:?}",
+                        msg.src().map(|s| s.path_string())
+                    );
                 }
                 MessageView::Error(err) => {
+                    // errors of a source only affect that source
+                    let source_id = err
+                        .src()
+                        .and_then(|src| sources.lock().unwrap().owner(&src));
+                    if let Some(source_id) = source_id {
+                        Self::restart_failed_source(&sources, source_id, err.error().to_string());
+                        return;
+                    }
+
                     let main_loop = &main_loop_clone;
                     error!(
                         "Error from {:?}: {} ({:?})",
@@ -171,31 +302,186 @@ impl Pipeline {
     }
 
     pub fn is_running(&self) -> bool {
+        self.state() != gst::State::Null
+    }
+
+    pub fn state(&self) -> gst::State {
         let (_, pipeline_state, _) = self.pipeline.state(None);
-        pipeline_state != gst::State::Null
+        pipeline_state
     }
 
-    pub fn sources_fps(&self) -> HashMap<SourceId, Option<f64>> {
+    pub fn sources_status(&self) -> HashMap<SourceId, SourceState> {
+        self.sources.lock().unwrap().states().clone()
+    }
+
+    pub fn set_source_status(&self, id: &SourceId, status: SourceStatus) {
+        self.sources.lock().unwrap().set_status(id, status);
+    }
+
+    /// Stop the bin of a source without removing it, marking it as failed.
+    pub fn stop_source(&self, id: &SourceId, error: String) -> Result<(), Error> {
+        let bin = {
+            let mut sources = self.sources.lock().unwrap();
+            sources.set_status(id, SourceStatus::Failed { error });
+            sources.bin(id).cloned()
+        };
+        let bin = bin.ok_or_else(|| anyhow!("Source {} not found", id))?;
+        bin.set_state(gst::State::Null)?;
+
+        Ok(())
+    }
+
+    /// Stop the bin of a source that reached the end of stream.
+    fn stop_ended_source(sources: &Mutex<SourceRegistry>, source_id: SourceId) {
+        let bin = {
+            let sources = sources.lock().unwrap();
+            match sources.state(&source_id) {
+                Some(state) if state.status == SourceStatus::Eos => {}
+                _ => return,
+            }
+            sources.bin(&source_id).cloned()
+        };
+
+        if let Some(bin) = bin {
+            if let Err(e) = bin.set_state(gst::State::Null) {
+                error!("Cant stop source {}: {}", source_id, e);
+            }
+        }
+    }
+
+    /// Stop the bin of a failed source and start it again after a delay, unless
+    /// the source reconnects by itself.
+    fn restart_failed_source(
+        sources: &Arc<Mutex<SourceRegistry>>,
+        source_id: SourceId,
+        error: String,
+    ) {
+        let recovery = sources.lock().unwrap().fail(&source_id, error.clone());
+        let (bin, restart) = match recovery {
+            Some(Recovery::Restart(bin)) => {
+                error!(
+                    "Source {} failed, restarting it in {:?}: {}",
+                    source_id, SOURCE_RESTART_DELAY, error
+                );
+                (bin, true)
+            }
+            Some(Recovery::GiveUp(bin)) => {
+                error!(
+                    "Source {} failed after {} restarts, stopping it: {}",
+                    source_id, MAX_SOURCE_RESTARTS, error
+                );
+                (bin, false)
+            }
+            Some(Recovery::Reconnect) => {
+                error!("Source {} failed: {}", source_id, error);
+                return;
+            }
+            Some(Recovery::Scheduled) => {
+                debug!("Source {} already failed: {}", source_id, error);
+                return;
+            }
+            None => return,
+        };
+        if let Err(e) = bin.set_state(gst::State::Null) {
+            error!("Cant stop source {}: {}", source_id, e);
+        }
+        if !restart {
+            return;
+        }
+
+        let sources = sources.clone();
+        let bin_weak = bin.downgrade();
+        glib::timeout_add(SOURCE_RESTART_DELAY, move || {
+            let bin = match bin_weak.upgrade() {
+                Some(bin) => bin,
+                None => return glib::Continue(false),
+            };
+            // the source may have been removed or replaced meanwhile
+            if !sources.lock().unwrap().restart(&source_id, &bin) {
+                return glib::Continue(false);
+            }
+
+            info!("Restarting source {}", source_id);
+            if let Err(e) = bin.sync_state_with_parent() {
+                warn!("Cant restart source {}: {}", source_id, e);
+            }
+            glib::Continue(false)
+        });
+    }
+
+    pub fn sources_fps(&self) -> HashMap<SourceId, Option<FpsStats>> {
         let mut sources_fps = HashMap::new();
-        for source_id in self.sources_bin_name.keys() {
+        for source_id in self.sources.lock().unwrap().ids() {
             sources_fps.insert(*source_id, self.fps_metrics.fps(source_id));
         }
 
         sources_fps
     }
-}
 
-/// Create nvstreammux element and config it.
-fn create_streamux(streammux_config: &config::StreamMuxConfig) -> Result<gst::Element, Error> {
-    let streammux = gst::ElementFactory::make("nvstreammux", None)
-        .map_err(|_| MissingElement("nvstreammux"))?;
+    pub fn sources_counters(&self) -> HashMap<SourceId, SourceCounters> {
+        let mut sources_counters = HashMap::new();
+        for source_id in self.sources.lock().unwrap().ids() {
+            let counters = self.stream_counters.counters(source_id);
+            sources_counters.insert(*source_id, counters.unwrap_or_default());
+        }
 
-    // Set propertys
-    streammux.set_property("batch-size", streammux_config.batch_size)?;
-    streammux.set_property("enable-padding", streammux_config.enable_padding)?;
-    streammux.set_property("live-source", true)?;
-    streammux.set_property("width", streammux_config.width)?;
-    streammux.set_property("height", streammux_config.height)?;
+        sources_counters
+    }
 
-    Ok(streammux)
+    /// Counts of the lines and zones of the sources the analytics filter runs on.
+    pub fn sources_analytics(&self) -> HashMap<SourceId, SourceAnalyticsCounts> {
+        let mut sources_analytics = HashMap::new();
+        for source_id in self.sources.lock().unwrap().ids() {
+            if let Some(counts) = self.filters.analytics().counts(source_id) {
+                sources_analytics.insert(*source_id, counts);
+            }
+        }
+
+        sources_analytics
+    }
+
+    pub fn filters_config(&self) -> Vec<config::FilterConfig> {
+        self.filters.config()
+    }
+
+    /// Replace the filters while the pipeline runs, the sources are kept.
+    pub fn update_filters(
+        &mut self,
+        filters_config: Vec<config::FilterConfig>,
+    ) -> Result<(), Error> {
+        self.filters.update(filters_config)
+    }
+
+    pub fn overlay_config(&self) -> config::OverlayConfig {
+        self.overlay.config()
+    }
+
+    pub fn update_overlay(&self, overlay_config: config::OverlayConfig) {
+        self.overlay.update(overlay_config);
+    }
+
+    /// Bin of the sinks, their branches are linked to its tee.
+    #[cfg(test)]
+    pub fn sink_bin(&self) -> &gst::Bin {
+        &self.pipeline_sink.bin
+    }
+
+    pub fn sinks_config(&self) -> &config::SinksConfig {
+        self.pipeline_sink.config()
+    }
+
+    /// Add, remove or replace the sinks while the pipeline runs.
+    pub fn update_sinks(&mut self, sinks_config: config::SinksConfig) -> Result<(), Error> {
+        let sources: Vec<SourceId> = self.sources.lock().unwrap().ids().copied().collect();
+        self.pipeline_sink.update(sinks_config, &sources)
+    }
+
+    /// Latency histograms of the sources, by stage.
+    pub fn sources_latency(&self) -> HashMap<SourceId, BTreeMap<String, Histogram>> {
+        self.latency.histograms()
+    }
+
+    pub fn broker_overruns(&self) -> u64 {
+        self.pipeline_sink.broker_overruns()
+    }
 }
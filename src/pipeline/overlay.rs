@@ -0,0 +1,117 @@
+use gst::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use ds::display_meta::{Color, DisplayMeta, Font, Text};
+use ds::gst_meta::batch_meta_ptrs;
+use ds::meta::NvDsBatchMeta;
+
+use crate::common::SourceId;
+
+use super::config::OverlayConfig;
+use super::metrics::FPSMetrics;
+
+const COUNTERS_POSITION: (u32, u32) = (10, 10);
+const COUNTERS_LINE_HEIGHT: u32 = 24;
+const COUNTERS_BG_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+
+/// Draws the zones of the overlay config and the counters of the sources on
+/// the frames going through a pad, as display metas the osd renders.
+///
+/// Only DeepStream batch metas get the display metas, the software backend
+/// has no osd to draw them.
+pub struct Overlay {
+    config: Arc<Mutex<OverlayConfig>>,
+}
+
+impl Overlay {
+    pub fn new(pad: &gst::Pad, config: OverlayConfig, fps_metrics: FPSMetrics) -> Self {
+        let config = Arc::new(Mutex::new(config));
+
+        let config_clone = config.clone();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            let config = config_clone.lock().unwrap();
+            if config.zones.is_empty() && !config.counters {
+                return gst::PadProbeReturn::Ok;
+            }
+
+            if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                for ptr in batch_meta_ptrs(buffer) {
+                    // the display metas go to the batch meta, not the buffer
+                    let batch_meta = unsafe { NvDsBatchMeta::from_ptr(ptr) };
+                    for frame in batch_meta.iter_frame() {
+                        let source_id = frame.source_id() as SourceId;
+                        let mut objects = BTreeMap::new();
+                        for object in frame.iter_objects() {
+                            let class = match object.obj_label() {
+                                "" => object.class_id().to_string(),
+                                label => label.to_string(),
+                            };
+                            *objects.entry(class).or_default() += 1;
+                        }
+                        let fps = fps_metrics.fps(&source_id).map(|stats| stats.avg);
+
+                        let display_meta = source_display_meta(&config, source_id, fps, &objects);
+                        display_meta.attach(batch_meta, frame);
+                    }
+                }
+            }
+
+            gst::PadProbeReturn::Ok
+        });
+
+        Overlay { config }
+    }
+
+    pub fn config(&self) -> OverlayConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Draw the elements of `config` from the next frame.
+    pub fn update(&self, config: OverlayConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+}
+
+/// Elements drawn on a frame of `source_id`, from its `fps` and the count of
+/// the `objects` of the frame by class.
+pub fn source_display_meta(
+    config: &OverlayConfig,
+    source_id: SourceId,
+    fps: Option<f64>,
+    objects: &BTreeMap<String, u64>,
+) -> DisplayMeta {
+    let mut builder = DisplayMeta::builder();
+
+    for zone in config.zones.iter().filter(|z| z.source_id == source_id) {
+        let [red, green, blue, alpha] = zone.color;
+        let color = Color::rgba(red, green, blue, alpha);
+        builder = builder.polygon(&zone.points, 3, color);
+        if let Some((x, y)) = zone.points.first() {
+            let name = Text::new(&zone.name, *x, *y).font(Font::new("Serif", 12, color));
+            builder = builder.text(name);
+        }
+    }
+
+    if config.counters {
+        let (x, y) = COUNTERS_POSITION;
+        let fps = match fps {
+            Some(fps) => format!("{:.1} fps", fps),
+            None => "- fps".to_string(),
+        };
+        let title = format!("Source {}: {}", source_id, fps);
+        builder = builder.text(Text::new(&title, x, y).background(COUNTERS_BG_COLOR));
+
+        if !objects.is_empty() {
+            let objects: Vec<String> = objects
+                .iter()
+                .map(|(class, count)| format!("{}: {}", class, count))
+                .collect();
+            let objects = Text::new(&objects.join("  "), x, y + COUNTERS_LINE_HEIGHT)
+                .background(COUNTERS_BG_COLOR);
+            builder = builder.text(objects);
+        }
+    }
+
+    builder.build()
+}
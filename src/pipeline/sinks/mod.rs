@@ -1,70 +1,231 @@
 use anyhow::Error;
 use gst::prelude::*;
+use log::{debug, info, warn};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::common::SourceId;
 
+use super::backend::ElementFactory;
 use super::common;
 use super::config::SinksConfig;
+use super::metrics::LatencyTracker;
 use common::MissingElement;
 
 mod msg_broker;
 mod render_sink;
 mod rtsp_sink;
 
+/// Time to wait for a removed branch to drain before removing it anyway.
+const BRANCH_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bin of a sink, linked to a src pad of the tee.
+struct Branch {
+    bin: gst::Bin,
+    tee_pad: gst::Pad,
+}
+
 pub struct PipelineSink {
     pub bin: gst::Bin,
-    rtsp_demux: Option<rtsp_sink::RTSPDemuxSink>,
+    factory: Arc<dyn ElementFactory>,
+    latency: LatencyTracker,
+    tee: gst::Element,
+    config: SinksConfig,
+    msg_broker: Option<Branch>,
+    rtsp_demux: Option<(rtsp_sink::RTSPDemuxSink, Branch)>,
+    display: Option<Branch>,
+    broker_overruns: Arc<AtomicU64>,
 }
 
 impl PipelineSink {
-    pub fn new(config: SinksConfig) -> Result<Self, Error> {
+    pub fn new(
+        factory: &Arc<dyn ElementFactory>,
+        config: SinksConfig,
+        latency: &LatencyTracker,
+    ) -> Result<Self, Error> {
         let bin = gst::Bin::new(Some("sink_bin"));
 
         let queue =
             gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
-        let nvvidconv = gst::ElementFactory::make("nvvideoconvert", None)
-            .map_err(|_| MissingElement("nvvideoconvert"))?;
-        let nvosd =
-            gst::ElementFactory::make("nvdsosd", None).map_err(|_| MissingElement("nvdsosd"))?;
+        let nvvidconv = factory.video_convert()?;
+        let nvosd = factory.osd()?;
         let tee = gst::ElementFactory::make("tee", None).map_err(|_| MissingElement("tee"))?;
+        // Keep flowing while no sink branch is linked, e.g. all sinks disabled
+        // or the only branch being replaced.
+        tee.set_property("allow-not-linked", true)?;
         bin.add_many(&[&queue, &nvvidconv, &nvosd, &tee])?;
         queue.link(&nvvidconv)?;
         nvvidconv.link(&nvosd)?;
         nvosd.link(&tee)?;
 
+        let osd_pad = nvosd.static_pad("src").expect("Cant get osd srcpad");
+        latency.measure(&osd_pad, "osd");
+
+        common::add_bin_ghost_pad(&bin, &queue, "sink")?;
+
+        let mut pipeline_sink = PipelineSink {
+            bin,
+            factory: factory.clone(),
+            latency: latency.clone(),
+            tee,
+            config: SinksConfig::default(),
+            msg_broker: None,
+            rtsp_demux: None,
+            display: None,
+            broker_overruns: Arc::new(AtomicU64::new(0)),
+        };
+        pipeline_sink.update(config, &[])?;
+
+        Ok(pipeline_sink)
+    }
+
+    pub fn config(&self) -> &SinksConfig {
+        &self.config
+    }
+
+    /// Add the branches enabled in `config` and remove the disabled ones, the
+    /// changed ones are replaced. The rtsp sinks are added for `sources`.
+    pub fn update(&mut self, config: SinksConfig, sources: &[SourceId]) -> Result<(), Error> {
         // Add kafka msg broker
-        if let Some(broker_config) = config.msg_broker {
-            let broker = msg_broker::create_bin(Some("kafkamsgbroker_sink"), broker_config)?;
-            bin.add(&broker)?;
-            common::link_element_to_tee_src_pad(&tee, &broker)?;
+        if config.msg_broker != self.config.msg_broker {
+            if let Some(branch) = self.msg_broker.take() {
+                info!("Removing the msg broker sink");
+                self.remove_branch(branch);
+            }
+            self.config.msg_broker = None;
+            if let Some(broker_config) = &config.msg_broker {
+                let broker = msg_broker::create_bin(
+                    self.factory.as_ref(),
+                    None,
+                    broker_config.clone(),
+                    self.broker_overruns.clone(),
+                    &self.latency,
+                )?;
+                self.msg_broker = Some(self.add_branch(broker)?);
+                self.config.msg_broker = config.msg_broker.clone();
+            }
         }
 
         // Add rtsp demuxer
-        let rtsp_demux: Option<rtsp_sink::RTSPDemuxSink> = match config.rtsp {
-            true => {
+        if config.rtsp != self.config.rtsp {
+            if let Some((rtsp_demux, branch)) = self.rtsp_demux.take() {
+                info!("Removing the rtsp sinks");
+                rtsp_demux.close();
+                self.remove_branch(branch);
+            }
+            self.config.rtsp = false;
+            if config.rtsp {
                 rtsp_sink::init(8554);
-                let rtsp_demux = rtsp_sink::RTSPDemuxSink::new(Some("rtsp_demux"))?;
-                bin.add(&rtsp_demux.bin)?;
-                common::link_element_to_tee_src_pad(&tee, &rtsp_demux.bin)?;
-                Some(rtsp_demux)
+                let rtsp_demux = rtsp_sink::RTSPDemuxSink::new(
+                    self.factory.clone(),
+                    None,
+                    self.latency.clone(),
+                )?;
+                let branch = self.add_branch(rtsp_demux.bin.clone())?;
+                for id in sources {
+                    rtsp_demux.add_sink(id)?;
+                }
+                self.rtsp_demux = Some((rtsp_demux, branch));
+                self.config.rtsp = true;
             }
-            false => None,
-        };
+        }
 
         // Add display sinks
-        if config.display {
-            let render_sink = render_sink::create_bin(Some("render_sink"))?;
-            bin.add(&render_sink)?;
-            common::link_element_to_tee_src_pad(&tee, &render_sink)?;
+        if config.display != self.config.display {
+            if let Some(branch) = self.display.take() {
+                info!("Removing the display sink");
+                self.remove_branch(branch);
+            }
+            self.config.display = false;
+            if config.display {
+                let render_sink =
+                    render_sink::create_bin(self.factory.as_ref(), None, &self.latency)?;
+                self.display = Some(self.add_branch(render_sink)?);
+                self.config.display = true;
+            }
         }
 
-        common::add_bin_ghost_pad(&bin, &queue, "sink")?;
+        Ok(())
+    }
+
+    fn add_branch(&self, bin: gst::Bin) -> Result<Branch, Error> {
+        self.bin.add(&bin)?;
+        let tee_pad = common::link_element_to_tee_src_pad(&self.tee, &bin)?;
+        bin.sync_state_with_parent()?;
+
+        Ok(Branch { bin, tee_pad })
+    }
+
+    /// Unlink `branch` from the tee once its pad is idle and send it an EOS, the
+    /// branch is removed when its sinks got the EOS.
+    fn remove_branch(&self, branch: Branch) {
+        let parent = self.bin.clone();
+        let tee = self.tee.clone();
+        let Branch { bin, tee_pad } = branch;
+
+        // without data flowing there is nothing to drain
+        if bin.current_state() != gst::State::Playing {
+            release_branch(&parent, &tee, &bin, &tee_pad);
+            return;
+        }
+
+        tee_pad.add_probe(gst::PadProbeType::IDLE, move |tee_pad, _| {
+            let sinkpad = bin.static_pad("sink").expect("Cant get sink bin sinkpad");
+            if let Err(e) = tee_pad.unlink(&sinkpad) {
+                warn!("Cant unlink the removed sink: {}", e);
+            }
 
-        Ok(PipelineSink { bin, rtsp_demux })
+            let pending = Arc::new(Mutex::new(Some((bin.clone(), tee_pad.clone()))));
+            let release = {
+                let parent = parent.clone();
+                let tee = tee.clone();
+                move || {
+                    if let Some((bin, tee_pad)) = pending.lock().unwrap().take() {
+                        release_branch(&parent, &tee, &bin, &tee_pad);
+                    }
+                    glib::Continue(false)
+                }
+            };
+
+            // the state can't be changed from the streaming thread
+            let sinks: Vec<gst::Element> = bin.iterate_sinks().into_iter().flatten().collect();
+            let remaining = Arc::new(AtomicUsize::new(sinks.len()));
+            for sink in sinks {
+                let sink_pad = match sink.static_pad("sink") {
+                    Some(sink_pad) => sink_pad,
+                    None => continue,
+                };
+                let remaining = remaining.clone();
+                let release = release.clone();
+                sink_pad.add_probe(
+                    gst::PadProbeType::EVENT_DOWNSTREAM,
+                    move |_, info| match &info.data {
+                        Some(gst::PadProbeData::Event(event))
+                            if event.type_() == gst::EventType::Eos =>
+                        {
+                            if remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                                debug!("Removed sink drained");
+                                glib::idle_add(release.clone());
+                            }
+                            gst::PadProbeReturn::Remove
+                        }
+                        _ => gst::PadProbeReturn::Ok,
+                    },
+                );
+            }
+            if remaining.load(Ordering::SeqCst) == 0 {
+                glib::idle_add(release.clone());
+            }
+            glib::timeout_add(BRANCH_DRAIN_TIMEOUT, release);
+
+            sinkpad.send_event(gst::event::Eos::new());
+            gst::PadProbeReturn::Remove
+        });
     }
 
     pub fn add_source_sink(&self, id: &SourceId) -> Result<(), Error> {
-        if let Some(rtsp_demux) = &self.rtsp_demux {
+        if let Some((rtsp_demux, _)) = &self.rtsp_demux {
             rtsp_demux.add_sink(id)?;
         }
 
@@ -72,10 +233,26 @@ impl PipelineSink {
     }
 
     pub fn remove_source_sink(&self, id: &SourceId) -> Result<(), Error> {
-        if let Some(rtsp_demux) = &self.rtsp_demux {
+        if let Some((rtsp_demux, _)) = &self.rtsp_demux {
             rtsp_demux.remove_sink(id)?;
         }
 
         Ok(())
     }
+
+    /// Messages dropped because the msg broker was too slow.
+    pub fn broker_overruns(&self) -> u64 {
+        self.broker_overruns.load(Ordering::Relaxed)
+    }
+}
+
+fn release_branch(parent: &gst::Bin, tee: &gst::Element, bin: &gst::Bin, tee_pad: &gst::Pad) {
+    if let Err(e) = bin.set_state(gst::State::Null) {
+        warn!("Cant stop the removed sink: {}", e);
+    }
+    if let Err(e) = parent.remove(bin) {
+        warn!("Cant remove the removed sink: {}", e);
+    }
+    tee.release_request_pad(tee_pad);
+    debug!("Sink {} removed", bin.name());
 }
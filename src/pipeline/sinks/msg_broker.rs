@@ -1,36 +1,40 @@
 use anyhow::Error;
 use gst::prelude::*;
-use log::{warn};
+use log::warn;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+use super::super::backend::ElementFactory;
 use super::super::common;
 use super::super::config::MsgBrokerSinkConfig;
+use super::super::metrics::LatencyTracker;
 use common::MissingElement;
 
-/// Return a bin with nveglglessink
-pub fn create_bin(name: Option<&str>, config: MsgBrokerSinkConfig) -> Result<gst::Bin, Error> {
+/// Return a bin sending the objects to the broker, queue → nvobjconv →
+/// nvmsgconv → nvmsgbroker, counting the queue overruns in `overruns`
+pub fn create_bin(
+    factory: &dyn ElementFactory,
+    name: Option<&str>,
+    config: MsgBrokerSinkConfig,
+    overruns: Arc<AtomicU64>,
+    latency: &LatencyTracker,
+) -> Result<gst::Bin, Error> {
     let bin = gst::Bin::new(name);
 
     let queue = gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
-    let obj_transform =
-        gst::ElementFactory::make("nvobjconv", None).map_err(|_| MissingElement("nvobjconv"))?;
-    let transform =
-        gst::ElementFactory::make("nvmsgconv", None).map_err(|_| MissingElement("nvmsgconv"))?;
-    let sink = gst::ElementFactory::make("nvmsgbroker", None)
-        .map_err(|_| MissingElement("nvmsgbroker"))?;
+    let obj_transform = factory.object_converter(&config.objconv)?;
+    let transform = factory.message_converter()?;
+    let sink = factory.message_broker(&config)?;
 
     // set threshold on queue to avoid pipeline choke when broker is stuck on network
     // * leaky=2 (2): downstream       - Leaky on downstream (old buffers)
     queue.set_property_from_str("leaky", "downstream");
     queue.set_property("max-size-buffers", 2_u32)?;
     queue.connect("overrun", false, move |_args| {
+        overruns.fetch_add(1, Ordering::Relaxed);
         warn!("nvmsgbroker queue overrun; Older Message Buffer");
         None
     })?;
-    sink.set_property("proto-lib", "/opt/nvidia/deepstream/deepstream/lib/libnvds_kafka_proto.so")?;
-    sink.set_property("conn-str", format!("{};{}", config.server, config.port))?;
-    sink.set_property("topic", config.topic)?;
-    sink.set_property("config", "config/filters/msgbroker_config.txt")?;
-    sink.set_property("sync", false)?;
 
     bin.add_many(&[&queue, &obj_transform, &transform, &sink])?;
     common::add_bin_ghost_pad(&bin, &queue, "sink")?;
@@ -38,5 +42,10 @@ pub fn create_bin(name: Option<&str>, config: MsgBrokerSinkConfig) -> Result<gst
     obj_transform.link(&transform)?;
     transform.link(&sink)?;
 
+    let sinkpad = sink
+        .static_pad("sink")
+        .expect("Cant get msg broker sinkpad");
+    latency.measure(&sinkpad, "msg_broker");
+
     Ok(bin)
 }
@@ -1,23 +1,32 @@
 use anyhow::Error;
 use gst::prelude::*;
 
+use super::super::backend::ElementFactory;
 use super::super::common;
+use super::super::metrics::LatencyTracker;
 use common::MissingElement;
 
 /// Return a bin with nveglglessink
-pub fn create_bin(name: Option<&str>) -> Result<gst::Bin, Error> {
+pub fn create_bin(
+    factory: &dyn ElementFactory,
+    name: Option<&str>,
+    latency: &LatencyTracker,
+) -> Result<gst::Bin, Error> {
     let bin = gst::Bin::new(name);
 
     let queue = gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
-    let tiler = gst::ElementFactory::make("nvmultistreamtiler", None)
-        .map_err(|_| MissingElement("nvmultistreamtiler"))?;
-    let sink = gst::ElementFactory::make("nveglglessink", None)
-        .map_err(|_| MissingElement("nveglglessink"))?;
+    let tiler = factory.tiler()?;
+    let sink = factory.video_sink()?;
 
     bin.add_many(&[&queue, &tiler, &sink])?;
     common::add_bin_ghost_pad(&bin, &queue, "sink")?;
     queue.link(&tiler)?;
     tiler.link(&sink)?;
 
+    let sinkpad = sink
+        .static_pad("sink")
+        .expect("Cant get video sink sinkpad");
+    latency.measure(&sinkpad, "display");
+
     Ok(bin)
 }
@@ -3,12 +3,15 @@ use derive_more::{Display, Error};
 use gst::prelude::*;
 use gst_rtsp_server::prelude::*;
 use log::info;
-use state::LocalStorage;
+use once_cell::sync::OnceCell;
 use std::net::UdpSocket;
+use std::sync::Arc;
 
 use crate::common::SourceId;
 
+use super::super::backend::ElementFactory;
 use super::super::common;
+use super::super::metrics::LatencyTracker;
 use common::MissingElement;
 
 enum EncoderType {
@@ -19,34 +22,48 @@ enum EncoderType {
 #[display(fmt = "Could not get mount points")]
 struct NoMountPoints;
 
-static mut SERVER: LocalStorage<gst_rtsp_server::RTSPServer> = LocalStorage::new();
+/// Server of the process, shared by the sinks of every thread.
+static SERVER: OnceCell<gst_rtsp_server::RTSPServer> = OnceCell::new();
 
+/// Start the rtsp server, once, the sinks added again later reuse it.
 pub fn init(rtsp_port: u32) {
-    unsafe {
-        SERVER.set(gst_rtsp_server::RTSPServer::new);
-
-        let server = SERVER.get();
+    SERVER.get_or_init(|| {
+        let server = gst_rtsp_server::RTSPServer::new();
         server
             .set_property("service", rtsp_port.to_string())
             .unwrap();
         let _id = server.attach(None).unwrap();
-    }
+        server
+    });
 }
 
-pub fn create_bin(name: Option<&str>, rtsp_path: &str) -> Result<gst::Bin, Error> {
+fn server() -> &'static gst_rtsp_server::RTSPServer {
+    SERVER.get().expect("rtsp server not started")
+}
+
+pub fn create_bin(
+    factory: &dyn ElementFactory,
+    name: Option<&str>,
+    rtsp_path: &str,
+    latency: &LatencyTracker,
+) -> Result<gst::Bin, Error> {
     let bin = gst::Bin::new(name);
 
     let queue = gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
 
-    let transform = gst::ElementFactory::make("nvvideoconvert", None)
-        .map_err(|_| MissingElement("nvvideoconvert"))?;
+    let transform = factory.video_convert()?;
 
     let cap_filter =
         gst::ElementFactory::make("capsfilter", None).map_err(|_| MissingElement("capsfilter"))?;
-    let caps = gst::Caps::builder("video/x-raw")
-        .features(&["memory:NVMM"])
-        .field("format", "I420")
-        .build();
+    let caps = match factory.memory_feature() {
+        Some(feature) => gst::Caps::builder("video/x-raw")
+            .features(&[feature])
+            .field("format", "I420")
+            .build(),
+        None => gst::Caps::builder("video/x-raw")
+            .field("format", "I420")
+            .build(),
+    };
     cap_filter.set_property("caps", &caps)?;
 
     let codecparse =
@@ -55,8 +72,7 @@ pub fn create_bin(name: Option<&str>, rtsp_path: &str) -> Result<gst::Bin, Error
     let rtppay =
         gst::ElementFactory::make("rtph264pay", None).map_err(|_| MissingElement("rtph264pay"))?;
 
-    let encoder = gst::ElementFactory::make("nvv4l2h264enc", None)
-        .map_err(|_| MissingElement("nvv4l2h264enc"))?;
+    let encoder = factory.h264_encoder()?;
 
     let udp_port = UdpSocket::bind("127.0.0.1:0")?.local_addr()?.port();
     let sink = gst::ElementFactory::make("udpsink", None).map_err(|_| MissingElement("udpsink"))?;
@@ -81,6 +97,12 @@ pub fn create_bin(name: Option<&str>, rtsp_path: &str) -> Result<gst::Bin, Error
     codecparse.link(&rtppay)?;
     rtppay.link(&sink)?;
 
+    // the encoded buffers have no frame meta
+    let encoder_pad = encoder
+        .static_pad("sink")
+        .expect("Cant get encoder sinkpad");
+    latency.measure(&encoder_pad, "rtsp");
+
     common::add_bin_ghost_pad(&bin, &queue, "sink")?;
 
     start_rtsp_streaming(rtsp_path, udp_port, EncoderType::H264);
@@ -103,56 +125,67 @@ fn start_rtsp_streaming(rtsp_path: &str, udpsink_port: u16, encoder: EncoderType
         "( udpsrc name=pay0 port={} buffer-size={} caps=\"application/x-rtp, media=video, clock-rate=90000, encoding-name={}, payload=96 \" )",
         udpsink_port, udp_buffer_size, encoder_name);
 
-    unsafe {
-        let server = SERVER.get();
-        let mounts = server.mount_points().ok_or(NoMountPoints).unwrap();
-        let factory = gst_rtsp_server::RTSPMediaFactory::new();
-        factory.set_launch(udpsrc_pipeline.as_str());
-        factory.set_shared(true);
-        mounts.add_factory(&format!("/{}", rtsp_path), &factory);
-        info!(
-            "Stream ready at rtsp://127.0.0.1:{}/{}",
-            server.bound_port(),
-            rtsp_path
-        );
-    }
+    let server = server();
+    let mounts = server.mount_points().ok_or(NoMountPoints).unwrap();
+    let factory = gst_rtsp_server::RTSPMediaFactory::new();
+    factory.set_launch(udpsrc_pipeline.as_str());
+    factory.set_shared(true);
+    mounts.add_factory(&format!("/{}", rtsp_path), &factory);
+    info!(
+        "Stream ready at rtsp://127.0.0.1:{}/{}",
+        server.bound_port(),
+        rtsp_path
+    );
 }
 
 fn stop_rtsp_server(rtsp_path: &str) {
-    unsafe {
-        let server = SERVER.get();
-        let mounts = server.mount_points().ok_or(NoMountPoints).unwrap();
-        mounts.remove_factory(&format!("/{}", rtsp_path));
-        info!(
-            "Stream closed at rtsp://127.0.0.1:{}/{}",
-            server.bound_port(),
-            rtsp_path
-        );
-    }
+    let server = server();
+    let mounts = server.mount_points().ok_or(NoMountPoints).unwrap();
+    mounts.remove_factory(&format!("/{}", rtsp_path));
+    info!(
+        "Stream closed at rtsp://127.0.0.1:{}/{}",
+        server.bound_port(),
+        rtsp_path
+    );
 }
 
 pub struct RTSPDemuxSink {
     pub bin: gst::Bin,
     streamdemux: gst::Element,
+    factory: Arc<dyn ElementFactory>,
+    latency: LatencyTracker,
 }
 
 impl RTSPDemuxSink {
-    pub fn new(name: Option<&str>) -> Result<Self, Error> {
+    pub fn new(
+        factory: Arc<dyn ElementFactory>,
+        name: Option<&str>,
+        latency: LatencyTracker,
+    ) -> Result<Self, Error> {
         let bin = gst::Bin::new(name);
 
-        let streamdemux = gst::ElementFactory::make("nvstreamdemux", None)
-            .map_err(|_| MissingElement("nvstreamdemux"))?;
+        let streamdemux = factory.stream_demux()?;
 
         bin.add_many(&[&streamdemux])?;
         common::add_bin_ghost_pad(&bin, &streamdemux, "sink")?;
 
-        Ok(RTSPDemuxSink { bin, streamdemux })
+        Ok(RTSPDemuxSink {
+            bin,
+            streamdemux,
+            factory,
+            latency,
+        })
     }
 
     pub fn add_sink(&self, id: &SourceId) -> Result<(), Error> {
         let src_name = format!("src_{}", id);
 
-        let sink = create_bin(Some(&format!("rtspbin_{}", id)), &get_rtsp_path(id))?;
+        let sink = create_bin(
+            self.factory.as_ref(),
+            Some(&format!("rtspbin_{}", id)),
+            &get_rtsp_path(id),
+            &self.latency,
+        )?;
         self.bin.add(&sink)?;
 
         // get streamdemux src pad or create if not exists
@@ -179,6 +212,17 @@ impl RTSPDemuxSink {
         Ok(())
     }
 
+    /// Close the rtsp streams of every source, the bin is about to be removed.
+    pub fn close(&self) {
+        for sink in self.bin.children() {
+            let name = sink.name();
+            let id = name.strip_prefix("rtspbin_").and_then(|id| id.parse().ok());
+            if let Some(id) = id {
+                stop_rtsp_server(&get_rtsp_path(&id));
+            }
+        }
+    }
+
     pub fn remove_sink(&self, id: &SourceId) -> Result<(), Error> {
         // get rtsp sink bin
         let sink = self.bin.by_name(&format!("rtspbin_{}", id)).unwrap();
@@ -0,0 +1,149 @@
+use gst::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::common::SourceId;
+
+/// Restarts of a failed source, it's left stopped when it fails again.
+pub const MAX_SOURCE_RESTARTS: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum SourceStatus {
+    Playing,
+    /// The stream ended, the source bin is stopped.
+    Eos,
+    /// The source posted an error, its bin is restarted after a delay.
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceState {
+    #[serde(flatten)]
+    pub status: SourceStatus,
+    pub restarts: u32,
+}
+
+/// What is done with a source that posted an error.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// The source reconnects by itself.
+    Reconnect,
+    /// The source already failed, its restart is already scheduled.
+    Scheduled,
+    /// Restart this bin after a delay.
+    Restart(gst::Bin),
+    /// The source failed [`MAX_SOURCE_RESTARTS`] times, stop this bin for
+    /// good.
+    GiveUp(gst::Bin),
+}
+
+/// Sources bins of the pipeline, shared with the bus watch and the pads probes.
+#[derive(Debug, Default)]
+pub struct SourceRegistry {
+    bins: HashMap<SourceId, gst::Bin>,
+    states: HashMap<SourceId, SourceState>,
+    /// Sources that recover from their errors by themselves.
+    reconnecting: HashSet<SourceId>,
+}
+
+impl SourceRegistry {
+    pub fn insert(&mut self, id: SourceId, bin: gst::Bin, reconnects: bool) {
+        self.bins.insert(id, bin);
+        if reconnects {
+            self.reconnecting.insert(id);
+        }
+        self.states.insert(
+            id,
+            SourceState {
+                status: SourceStatus::Playing,
+                restarts: 0,
+            },
+        );
+    }
+
+    /// Remove the source, returning its bin.
+    pub fn remove(&mut self, id: &SourceId) -> Option<gst::Bin> {
+        self.states.remove(id);
+        self.reconnecting.remove(id);
+        self.bins.remove(id)
+    }
+
+    pub fn contains(&self, id: &SourceId) -> bool {
+        self.bins.contains_key(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &SourceId> {
+        self.bins.keys()
+    }
+
+    pub fn bin(&self, id: &SourceId) -> Option<&gst::Bin> {
+        self.bins.get(id)
+    }
+
+    /// Source whose bin is `object` or one of its ancestors.
+    pub fn owner(&self, object: &gst::Object) -> Option<SourceId> {
+        let mut object = Some(object.clone());
+        while let Some(current) = object {
+            let found = self
+                .bins
+                .iter()
+                .find(|(_, bin)| bin.upcast_ref::<gst::Object>() == &current);
+            if let Some((id, _)) = found {
+                return Some(*id);
+            }
+            object = current.parent();
+        }
+
+        None
+    }
+
+    pub fn state(&self, id: &SourceId) -> Option<&SourceState> {
+        self.states.get(id)
+    }
+
+    pub fn states(&self) -> &HashMap<SourceId, SourceState> {
+        &self.states
+    }
+
+    pub fn set_status(&mut self, id: &SourceId, status: SourceStatus) {
+        if let Some(state) = self.states.get_mut(id) {
+            state.status = status;
+        }
+    }
+
+    /// Mark the source as failed with `error`, `None` if it's not in the
+    /// registry.
+    pub fn fail(&mut self, id: &SourceId, error: String) -> Option<Recovery> {
+        let state = self.states.get_mut(id)?;
+        let recovery = if self.reconnecting.contains(id) {
+            Recovery::Reconnect
+        } else if let SourceStatus::Failed { .. } = state.status {
+            // a failing source usually posts many errors
+            return Some(Recovery::Scheduled);
+        } else if state.restarts >= MAX_SOURCE_RESTARTS {
+            Recovery::GiveUp(self.bins[id].clone())
+        } else {
+            Recovery::Restart(self.bins[id].clone())
+        };
+        state.status = SourceStatus::Failed { error };
+
+        Some(recovery)
+    }
+
+    /// Count the restart of the failed source with the bin `bin`, `false` if
+    /// the source was removed or replaced meanwhile.
+    pub fn restart(&mut self, id: &SourceId, bin: &gst::Bin) -> bool {
+        if self.bin(id) != Some(bin) {
+            return false;
+        }
+        if let Some(state) = self.states.get_mut(id) {
+            state.restarts += 1;
+            state.status = SourceStatus::Playing;
+        }
+
+        true
+    }
+}
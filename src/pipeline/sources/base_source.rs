@@ -4,4 +4,10 @@ pub trait Source {
     fn get_bin(&self) -> &gst::Bin;
     fn link(&self, dst: &gst::Element) -> Result<(), Error>;
     fn on_remove(&mut self);
+
+    /// Whether the source recovers from its errors by itself, otherwise the
+    /// pipeline restarts it.
+    fn reconnects(&self) -> bool {
+        false
+    }
 }
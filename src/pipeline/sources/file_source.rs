@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Error};
+use gst::prelude::*;
+use log::{debug, error};
+use std::sync::{Arc, Mutex};
+
+use super::super::backend::ElementFactory;
+
+use super::base_source::Source;
+use super::uri_source::URISource;
+
+/// Playback options of a [`FileSource`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilePlayback {
+    pub looping: bool,
+    pub start_offset: Option<gst::ClockTime>,
+    pub end_offset: Option<gst::ClockTime>,
+    pub rate: f64,
+}
+
+impl Default for FilePlayback {
+    fn default() -> Self {
+        FilePlayback {
+            looping: false,
+            start_offset: None,
+            end_offset: None,
+            rate: 1.0,
+        }
+    }
+}
+
+impl FilePlayback {
+    /// Whether a seek is needed before the first frame.
+    fn needs_initial_seek(&self) -> bool {
+        self.start_offset.is_some() || self.end_offset.is_some() || self.rate != 1.0
+    }
+}
+
+/// Decodes a local file.
+///
+/// The seeks are done on the source bin alone, the flushes they cause are
+/// dropped and the timestamps of every loop are shifted after the previous
+/// one, so the streammux sees a single continuous stream.
+pub struct FileSource {
+    source: URISource,
+}
+
+#[derive(Default)]
+struct LoopState {
+    /// Waiting for the segment of a seek, the data in between is dropped.
+    seeking: bool,
+    /// The initial seek was sent.
+    initial_seek_sent: bool,
+    /// The segment was sent downstream, the later ones are dropped.
+    segment_sent: bool,
+    /// Added to the timestamps of the buffers, the duration of the previous loops.
+    offset: gst::ClockTime,
+    /// End of the last buffer, before applying the offset.
+    last_end: Option<gst::ClockTime>,
+}
+
+impl FileSource {
+    pub fn new(
+        factory: &dyn ElementFactory,
+        path: &str,
+        playback: FilePlayback,
+    ) -> Result<Self, Error> {
+        let path = std::fs::canonicalize(path).map_err(|e| anyhow!("{}: {}", path, e))?;
+        let uri = glib::filename_to_uri(&path, None)?;
+
+        let source = URISource::new(factory, &uri, None, None)?;
+
+        let src_pad = source
+            .get_bin()
+            .static_pad("src")
+            .expect("Cant get file source bin srcpad");
+        let state = Arc::new(Mutex::new(LoopState {
+            seeking: playback.needs_initial_seek(),
+            ..Default::default()
+        }));
+        src_pad.add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |pad, info| Self::probe(pad, info, &state, playback),
+        );
+
+        Ok(FileSource { source })
+    }
+
+    fn probe(
+        pad: &gst::Pad,
+        info: &mut gst::PadProbeInfo,
+        state: &Arc<Mutex<LoopState>>,
+        playback: FilePlayback,
+    ) -> gst::PadProbeReturn {
+        let mut state = state.lock().unwrap();
+
+        match &mut info.data {
+            Some(gst::PadProbeData::Buffer(buffer)) => {
+                if state.seeking {
+                    if !state.initial_seek_sent {
+                        state.initial_seek_sent = true;
+                        Self::seek(pad, playback);
+                    }
+                    return gst::PadProbeReturn::Drop;
+                }
+
+                if let Some(pts) = buffer.pts() {
+                    state.last_end = Some(pts + buffer.duration().unwrap_or(gst::ClockTime::ZERO));
+                }
+                if state.offset > gst::ClockTime::ZERO {
+                    let offset = state.offset;
+                    let buffer = buffer.make_mut();
+                    if let Some(pts) = buffer.pts() {
+                        buffer.set_pts(pts + offset);
+                    }
+                    if let Some(dts) = buffer.dts() {
+                        buffer.set_dts(dts + offset);
+                    }
+                }
+                gst::PadProbeReturn::Ok
+            }
+            Some(gst::PadProbeData::Event(event)) => match event.view() {
+                gst::EventView::FlushStart(_) | gst::EventView::FlushStop(_) => {
+                    gst::PadProbeReturn::Drop
+                }
+                gst::EventView::Segment(segment_event) => {
+                    if state.seeking && !state.initial_seek_sent {
+                        // segment from before the initial seek
+                        return gst::PadProbeReturn::Drop;
+                    }
+                    state.seeking = false;
+                    if state.segment_sent {
+                        return gst::PadProbeReturn::Drop;
+                    }
+                    state.segment_sent = true;
+
+                    // the timestamps of the next loops are after the segment stop
+                    if playback.looping {
+                        if let Some(segment) =
+                            segment_event.segment().downcast_ref::<gst::ClockTime>()
+                        {
+                            let mut segment = segment.clone();
+                            segment.set_stop(gst::ClockTime::NONE);
+                            *event = gst::event::Segment::new(&segment);
+                        }
+                    }
+                    gst::PadProbeReturn::Ok
+                }
+                gst::EventView::Eos(_) if playback.looping => {
+                    let start = playback.start_offset.unwrap_or(gst::ClockTime::ZERO);
+                    if let Some(last_end) = state.last_end {
+                        state.offset += last_end.saturating_sub(start);
+                    }
+                    state.seeking = true;
+                    debug!("Looping file source, timestamps offset {}", state.offset);
+                    Self::seek(pad, playback);
+                    gst::PadProbeReturn::Drop
+                }
+                _ => gst::PadProbeReturn::Ok,
+            },
+            _ => gst::PadProbeReturn::Ok,
+        }
+    }
+
+    /// Seek to the start offset, from the main loop as flushing seeks can't be
+    /// done from the streaming thread.
+    fn seek(pad: &gst::Pad, playback: FilePlayback) {
+        let pad_weak = pad.downgrade();
+        glib::idle_add(move || {
+            if let Some(pad) = pad_weak.upgrade() {
+                let stop_type = match playback.end_offset {
+                    Some(_) => gst::SeekType::Set,
+                    None => gst::SeekType::None,
+                };
+                let seek = gst::event::Seek::new(
+                    playback.rate,
+                    gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                    gst::SeekType::Set,
+                    Some(playback.start_offset.unwrap_or(gst::ClockTime::ZERO)),
+                    stop_type,
+                    playback.end_offset,
+                );
+                if !pad.send_event(seek) {
+                    error!("File source seek failed");
+                }
+            }
+            glib::Continue(false)
+        });
+    }
+}
+
+impl Source for FileSource {
+    fn link(&self, dst: &gst::Element) -> Result<(), Error> {
+        self.source.link(dst)
+    }
+
+    fn get_bin(&self) -> &gst::Bin {
+        self.source.get_bin()
+    }
+
+    fn on_remove(&mut self) {
+        self.source.on_remove();
+    }
+}
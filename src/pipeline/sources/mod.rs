@@ -1,9 +1,13 @@
 mod base_source;
+mod file_source;
+mod reconnect;
 mod rtsp_source;
 mod test_source;
 mod uri_source;
 
 pub use base_source::Source;
+pub use file_source::{FilePlayback, FileSource};
+pub use reconnect::{ReconnectCallback, ReconnectEvent, ReconnectPolicy, Reconnector};
 pub use rtsp_source::RTSPSource;
 pub use test_source::TestSource;
 pub use uri_source::URISource;
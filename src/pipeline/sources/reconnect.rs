@@ -0,0 +1,141 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::super::config::ReconnectConfig;
+
+/// When and how often a source reconnects, see [`ReconnectConfig`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    pub watch_interval: Duration,
+    pub data_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: f64,
+    pub jitter: f64,
+    pub max_attempts: Option<u32>,
+}
+
+impl From<&ReconnectConfig> for ReconnectPolicy {
+    fn from(config: &ReconnectConfig) -> Self {
+        ReconnectPolicy {
+            watch_interval: Duration::from_secs_f64(config.watch_interval.0),
+            data_timeout: Duration::from_secs_f64(config.data_timeout.0),
+            connect_timeout: Duration::from_secs_f64(config.connect_timeout.0),
+            initial_backoff: Duration::from_secs_f64(config.initial_backoff.0),
+            max_backoff: Duration::from_secs_f64(config.max_backoff.0),
+            backoff_multiplier: config.backoff_multiplier.0,
+            jitter: config.jitter.0,
+            max_attempts: config.max_attempts,
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        (&ReconnectConfig::default()).into()
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before the attempt following `attempt`, `jitter` between -1 and 1
+    /// scales the configured jitter.
+    pub fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+
+        Duration::from_secs_f64((backoff * (1.0 + self.jitter * jitter)).max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum ReconnectEvent {
+    /// The source is reset, `attempt` starts from 1.
+    Attempt { attempt: u32 },
+    /// Data is flowing again.
+    Reconnected { attempts: u32 },
+    /// `max_attempts` failed, the source is no longer reconnected.
+    GaveUp { attempts: u32 },
+}
+
+/// Called with the reconnection events of a source, from the main loop.
+pub type ReconnectCallback = Arc<dyn Fn(ReconnectEvent) + Send + Sync>;
+
+/// Decides when to reset a source from the time of its last data.
+#[derive(Debug)]
+pub struct Reconnector {
+    policy: ReconnectPolicy,
+    reconnecting: bool,
+    gave_up: bool,
+    attempts: u32,
+    attempt_started: Option<Instant>,
+    next_attempt: Instant,
+}
+
+impl Reconnector {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Reconnector {
+            policy,
+            reconnecting: false,
+            gave_up: false,
+            attempts: 0,
+            attempt_started: None,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    /// Called every watch interval, returns [`ReconnectEvent::Attempt`] when the
+    /// source must be reset.
+    pub fn update(&mut self, last_data: Instant, now: Instant) -> Option<ReconnectEvent> {
+        if self.gave_up {
+            return None;
+        }
+        if !self.reconnecting {
+            if now.saturating_duration_since(last_data) < self.policy.data_timeout {
+                return None;
+            }
+            self.reconnecting = true;
+            self.attempts = 0;
+            self.attempt_started = None;
+            self.next_attempt = now;
+        }
+
+        if let Some(attempt_started) = self.attempt_started {
+            if last_data > attempt_started {
+                self.reconnecting = false;
+                self.attempt_started = None;
+                return Some(ReconnectEvent::Reconnected {
+                    attempts: self.attempts,
+                });
+            }
+            if now.saturating_duration_since(attempt_started) < self.policy.connect_timeout {
+                return None;
+            }
+
+            // attempt failed
+            self.attempt_started = None;
+            if matches!(self.policy.max_attempts, Some(max) if self.attempts >= max) {
+                self.reconnecting = false;
+                self.gave_up = true;
+                return Some(ReconnectEvent::GaveUp {
+                    attempts: self.attempts,
+                });
+            }
+            let jitter = rand::random::<f64>() * 2.0 - 1.0;
+            self.next_attempt = now + self.policy.backoff(self.attempts, jitter);
+        }
+
+        if now >= self.next_attempt {
+            self.attempts += 1;
+            self.attempt_started = Some(now);
+            return Some(ReconnectEvent::Attempt {
+                attempt: self.attempts,
+            });
+        }
+
+        None
+    }
+}
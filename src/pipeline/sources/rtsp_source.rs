@@ -2,12 +2,15 @@ use anyhow::Error;
 use gst::prelude::*;
 use log::{debug, error, warn};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Instant;
+
+use crate::common::SourceId;
 
 use super::super::common;
 use super::super::common::MissingElement;
 
 use super::base_source::Source;
+use super::{ReconnectCallback, ReconnectEvent, ReconnectPolicy, Reconnector};
 
 fn pad_add_handler(src: &gst::Element, src_pad: &gst::Pad, sink: &gst::Element) {
     debug!(
@@ -70,7 +73,6 @@ fn watch_source_async_state_change(bin: &gst::Bin, ctx: &Arc<Mutex<ReconectionCo
     // Bin successfully changed state to PLAYING. Stop watching state
     if state == gst::State::Playing {
         let mut ctx = ctx.lock().unwrap();
-        ctx.reconecting = false;
         ctx.async_state_watch_timeout = None;
         return false;
     }
@@ -86,6 +88,7 @@ fn watch_source_async_state_change(bin: &gst::Bin, ctx: &Arc<Mutex<ReconectionCo
 
 fn reset_source_bin(
     bin: &gst::Bin,
+    source_id: SourceId,
     ctx: &Arc<Mutex<Contex>>,
     reconnection_ctx: &Arc<Mutex<ReconectionContext>>,
 ) {
@@ -100,8 +103,11 @@ fn reset_source_bin(
         log::error!("Cant sync state with parent of source {}", bin.name());
     }
 
-    if let Some(parser) = &ctx.lock().unwrap().parser {
-        if !parser.send_event(ds::events::new_stream_reset(0).unwrap()) {
+    // the streammux sink pad of the source is named after its id, there's no
+    // reset event without DeepStream
+    let reset = ds::events::new_stream_reset(source_id as i32);
+    if let (Some(parser), Some(reset)) = (&ctx.lock().unwrap().parser, reset) {
+        if !parser.send_event(reset) {
             log::error!("Interrupted, Reconnection event not sent");
         }
     }
@@ -131,9 +137,6 @@ fn reset_source_bin(
                 let mut reconnection_ctx_lock = reconnection_ctx.lock().unwrap();
                 reconnection_ctx_lock.async_state_watch_timeout = Some(timeout_id);
             }
-        } else if success == gst::StateChangeSuccess::Success && state == gst::State::Playing {
-            let mut reconnection_ctx_lock = reconnection_ctx.lock().unwrap();
-            reconnection_ctx_lock.reconecting = false;
         }
     };
 }
@@ -154,46 +157,39 @@ impl Contex {
 
 struct ReconectionContext {
     last_buffer_update: Instant,
-    last_reset_time: Instant,
-    started: bool,
-    reconecting: bool,
-    last_reconnect_time: Instant,
     have_eos: bool,
+    reconnector: Reconnector,
     async_state_watch_timeout: Option<glib::SourceId>,
+    source_watch_timeout: Option<glib::SourceId>,
 }
 
 impl ReconectionContext {
-    pub fn new() -> Self {
-        let last_buffer_update = Instant::now();
-        let last_reset_time = Instant::now();
-        let started = false;
-        let reconecting = false;
-        let last_reconnect_time = Instant::now();
-        let have_eos = false;
-
+    pub fn new(policy: ReconnectPolicy) -> Self {
         Self {
-            last_buffer_update,
-            last_reset_time,
-            started,
-            reconecting,
-            last_reconnect_time,
-            have_eos,
+            last_buffer_update: Instant::now(),
+            have_eos: false,
+            reconnector: Reconnector::new(policy),
             async_state_watch_timeout: None,
+            source_watch_timeout: None,
         }
     }
 }
 
 pub struct RTSPSource {
     bin: gst::Bin,
-    source_watch_timeout: Option<glib::SourceId>,
     reconnection_ctx: Arc<Mutex<ReconectionContext>>,
 }
 
 impl RTSPSource {
+    /// Source `source_id` of the pipeline, reconnected following `policy` when
+    /// it stops sending data, with the outcomes reported to `on_reconnect`.
     pub fn new(
         uri: &str,
         username: Option<&str>,
         password: Option<&str>,
+        source_id: SourceId,
+        policy: ReconnectPolicy,
+        on_reconnect: ReconnectCallback,
     ) -> Result<Self, Error> {
         let bin = gst::Bin::new(None);
 
@@ -205,7 +201,8 @@ impl RTSPSource {
             gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
 
         let ctx = Arc::new(Mutex::new(Contex::new()));
-        let reconnection_ctx = Arc::new(Mutex::new(ReconectionContext::new()));
+        let watch_interval = policy.watch_interval;
+        let reconnection_ctx = Arc::new(Mutex::new(ReconectionContext::new(policy)));
 
         // Config rtspsrc
         rtspsrc.set_property("location", &uri)?;
@@ -305,10 +302,6 @@ impl RTSPSource {
         let reconection_ctx_clone = reconnection_ctx.clone();
         rtspsrc.connect_pad_added(move |src, src_pad| {
             let reconection_ctx_clone2 = reconection_ctx_clone.clone();
-            {
-                let mut ctx = reconection_ctx_clone2.lock().unwrap();
-                ctx.started = true;
-            }
             src_pad.add_probe(
                 gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM,
                 move |_, info| {
@@ -355,51 +348,61 @@ impl RTSPSource {
 
         let reconnection_ctx_clone = reconnection_ctx.clone();
         let bin_week = bin.downgrade();
-        let source_watch_timeout = Some(glib::timeout_add(
-            std::time::Duration::from_secs(1),
-            move || {
-                let bin = bin_week.upgrade().unwrap();
-                let reset_requierd = {
-                    let reconnection_ctx = reconnection_ctx_clone.lock().unwrap();
-                    let update_elapsed = reconnection_ctx.last_buffer_update.elapsed();
-                    let reset_elapsed = reconnection_ctx.last_reset_time.elapsed();
-
-                    if reconnection_ctx.reconecting {
-                        if reconnection_ctx.last_reconnect_time.elapsed() >= Duration::from_secs(30)
-                        {
-                            log::warn!("Reconect failed from source {}, trying again", bin.name());
-                            true
-                        } else {
-                            false
-                        }
-                    } else if reconnection_ctx.started
-                        && update_elapsed >= Duration::from_secs(10)
-                        && reset_elapsed >= Duration::from_secs(10)
-                    {
-                        log::warn!("No data from source {}, trying reconect", bin.name());
-                        true
-                    } else {
-                        false
-                    }
-                };
+        let source_watch_timeout = glib::timeout_add(watch_interval, move || {
+            let bin = match bin_week.upgrade() {
+                Some(bin) => bin,
+                None => return glib::Continue(false),
+            };
+            let event = {
+                let mut reconnection_ctx = reconnection_ctx_clone.lock().unwrap();
+                let last_buffer_update = reconnection_ctx.last_buffer_update;
+                reconnection_ctx
+                    .reconnector
+                    .update(last_buffer_update, Instant::now())
+            };
+            let event = match event {
+                Some(event) => event,
+                None => return glib::Continue(true),
+            };
 
-                if reset_requierd {
-                    log::debug!("Reset source");
-                    {
-                        let mut reconnection_ctx = reconnection_ctx_clone.lock().unwrap();
-                        reconnection_ctx.reconecting = true;
-                        reconnection_ctx.last_reconnect_time = Instant::now();
-                    }
-                    reset_source_bin(&bin, &ctx, &reconnection_ctx_clone);
+            match event {
+                ReconnectEvent::Attempt { attempt } => {
+                    log::warn!(
+                        "No data from source {}, reconnection attempt {}",
+                        source_id,
+                        attempt
+                    );
+                    on_reconnect(event);
+                    reset_source_bin(&bin, source_id, &ctx, &reconnection_ctx_clone);
+                }
+                ReconnectEvent::Reconnected { attempts } => {
+                    log::info!(
+                        "Source {} reconnected after {} attempts",
+                        source_id,
+                        attempts
+                    );
+                    on_reconnect(event);
                 }
+                ReconnectEvent::GaveUp { attempts } => {
+                    log::error!(
+                        "Source {} not reconnected after {} attempts, giving up",
+                        source_id,
+                        attempts
+                    );
+                    on_reconnect(event);
+
+                    // nothing left to watch
+                    reconnection_ctx_clone.lock().unwrap().source_watch_timeout = None;
+                    return glib::Continue(false);
+                }
+            }
 
-                glib::Continue(true)
-            },
-        ));
+            glib::Continue(true)
+        });
+        reconnection_ctx.lock().unwrap().source_watch_timeout = Some(source_watch_timeout);
 
         Ok(RTSPSource {
             bin,
-            source_watch_timeout,
             reconnection_ctx,
         })
     }
@@ -416,11 +419,15 @@ impl Source for RTSPSource {
         &self.bin
     }
 
+    fn reconnects(&self) -> bool {
+        true
+    }
+
     fn on_remove(&mut self) {
         let mut reconnection_ctx = self.reconnection_ctx.lock().unwrap();
 
         // stop glib timeouts
-        if let Some(source_id) = self.source_watch_timeout.take() {
+        if let Some(source_id) = reconnection_ctx.source_watch_timeout.take() {
             glib::source_remove(source_id);
         };
         if let Some(source_id) = reconnection_ctx.async_state_watch_timeout.take() {
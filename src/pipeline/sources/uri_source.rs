@@ -2,6 +2,7 @@ use anyhow::Error;
 use gst::prelude::*;
 use log::{debug, error, info, warn};
 
+use super::super::backend::ElementFactory;
 use super::super::common;
 use super::super::common::MissingElement;
 
@@ -12,16 +13,17 @@ pub struct URISource {
 }
 
 impl URISource {
-    pub fn new(uri: &str, username: Option<&str>, password: Option<&str>) -> Result<Self, Error> {
+    pub fn new(
+        factory: &dyn ElementFactory,
+        uri: &str,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<Self, Error> {
         let bin = gst::Bin::new(None);
 
-        let urisrc = gst::ElementFactory::make("nvurisrcbin", None)
-            .map_err(|_| MissingElement("nvurisrcbin"))?;
+        let urisrc = factory.uri_decoder(uri)?;
         let queue =
             gst::ElementFactory::make("queue", None).map_err(|_| MissingElement("queue"))?;
-        // Config nvurisrcbin
-        urisrc.set_property("uri", uri)?;
-        urisrc.set_property("rtsp-reconnect-interval", 10_u32)?;
 
         // Add elements to queue
         bin.add_many(&[&urisrc, &queue])?;
@@ -49,6 +51,7 @@ impl URISource {
             });
 
         // Connect the pad-added signal
+        let memory_feature = factory.memory_feature();
         let queue_weak = queue.downgrade();
         urisrc.connect_pad_added(move |src, src_pad| {
             debug!("Received new pad {} from {}", src_pad.name(), src.name());
@@ -79,9 +82,11 @@ impl URISource {
                 return;
             }
 
-            let features = new_pad_caps.features(0).unwrap();
-            if !features.contains("memory:NVMM") {
-                panic!("Feature {} not contain 'memory:NVMM'.", features);
+            if let Some(memory_feature) = memory_feature {
+                let features = new_pad_caps.features(0).unwrap();
+                if !features.contains(memory_feature) {
+                    panic!("Feature {} not contain '{}'.", features, memory_feature);
+                }
             }
 
             let res = src_pad.link(&sink_pad);
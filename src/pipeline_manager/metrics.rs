@@ -0,0 +1,305 @@
+//! Prometheus text exposition of the pipeline and sources metrics.
+
+use std::fmt::Write;
+
+use crate::common::SourceId;
+
+use super::PipelineManager;
+use crate::pipeline::{Histogram, SourceStatus};
+
+/// Writes metrics in the Prometheus text format, a family header followed by
+/// its samples.
+#[derive(Debug, Default)]
+pub struct PrometheusWriter {
+    text: String,
+}
+
+impl PrometheusWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) -> &mut Self {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+        self
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", format_value(value));
+        self
+    }
+
+    /// Samples of a histogram family, its cumulative buckets, sum and count.
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        histogram: &Histogram,
+    ) -> &mut Self {
+        let bucket = format!("{}_bucket", name);
+        let buckets = histogram
+            .cumulative()
+            .map(|(bound, count)| (format_value(bound), count))
+            .chain(std::iter::once(("+Inf".to_string(), histogram.count)));
+        for (bound, count) in buckets {
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &bound));
+            self.sample(&bucket, &bucket_labels, count as f64);
+        }
+        self.sample(&format!("{}_sum", name), labels, histogram.sum);
+        self.sample(&format!("{}_count", name), labels, histogram.count as f64)
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    match value {
+        v if v == f64::INFINITY => "+Inf".to_string(),
+        v if v == f64::NEG_INFINITY => "-Inf".to_string(),
+        v => v.to_string(),
+    }
+}
+
+impl PipelineManager {
+    /// Metrics of the pipeline and of every source, for the `/metrics` endpoint.
+    pub(super) fn metrics(&self) -> String {
+        let mut ids: Vec<SourceId> = self.sources_config.keys().copied().collect();
+        ids.sort_unstable();
+        let labels: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        let sources = || {
+            ids.iter()
+                .zip(labels.iter().map(|l| [("source", l.as_str())]))
+        };
+
+        let fps = self.pipeline.sources_fps();
+        let counters = self.pipeline.sources_counters();
+        let status = self.pipeline.sources_status();
+        let latency = self.pipeline.sources_latency();
+        let analytics = self.pipeline.sources_analytics();
+        let mut writer = PrometheusWriter::new();
+
+        let state = self.pipeline.state();
+        writer.family(
+            "deepstream_pipeline_state",
+            "gauge",
+            "State of the pipeline, 1 for the current one",
+        );
+        for s in [
+            gst::State::Null,
+            gst::State::Ready,
+            gst::State::Paused,
+            gst::State::Playing,
+        ] {
+            let name = format!("{:?}", s).to_lowercase();
+            let value = if s == state { 1.0 } else { 0.0 };
+            writer.sample("deepstream_pipeline_state", &[("state", &name)], value);
+        }
+        writer
+            .family(
+                "deepstream_msgbroker_queue_overruns_total",
+                "counter",
+                "Messages dropped because the msg broker was too slow",
+            )
+            .sample(
+                "deepstream_msgbroker_queue_overruns_total",
+                &[],
+                self.pipeline.broker_overruns() as f64,
+            );
+
+        writer.family(
+            "deepstream_source_up",
+            "gauge",
+            "1 when the source is playing, 0 when it ended or failed",
+        );
+        for (id, labels) in sources() {
+            let up = matches!(status.get(id), Some(s) if s.status == SourceStatus::Playing);
+            writer.sample("deepstream_source_up", &labels, if up { 1.0 } else { 0.0 });
+        }
+
+        writer.family(
+            "deepstream_source_fps",
+            "gauge",
+            "Frames per second of the source, 0 without recent frames",
+        );
+        for (id, labels) in sources() {
+            let value = fps.get(id).copied().flatten().map_or(0.0, |f| f.avg);
+            writer.sample("deepstream_source_fps", &labels, value);
+        }
+
+        writer.family(
+            "deepstream_source_frames_total",
+            "counter",
+            "Frames processed from the source",
+        );
+        for (id, labels) in sources() {
+            let value = counters.get(id).map_or(0, |c| c.frames);
+            writer.sample("deepstream_source_frames_total", &labels, value as f64);
+        }
+
+        writer.family(
+            "deepstream_source_detections_total",
+            "counter",
+            "Objects detected in the frames of the source, by class, once per frame",
+        );
+        for (id, [source]) in sources() {
+            if let Some(counters) = counters.get(id) {
+                let mut classes: Vec<_> = counters.objects.iter().collect();
+                classes.sort();
+                for (class, count) in classes {
+                    writer.sample(
+                        "deepstream_source_detections_total",
+                        &[source, ("class", class)],
+                        *count as f64,
+                    );
+                }
+            }
+        }
+
+        writer.family(
+            "deepstream_source_restarts_total",
+            "counter",
+            "Restarts of the source after an error",
+        );
+        for (id, labels) in sources() {
+            let value = status.get(id).map_or(0, |s| s.restarts);
+            writer.sample("deepstream_source_restarts_total", &labels, value as f64);
+        }
+
+        writer.family(
+            "deepstream_source_reconnect_attempts_total",
+            "counter",
+            "Reconnection attempts of the source",
+        );
+        for (id, labels) in sources() {
+            let value = self.reconnect_stats.get(id).map_or(0, |s| s.attempts);
+            writer.sample(
+                "deepstream_source_reconnect_attempts_total",
+                &labels,
+                value as f64,
+            );
+        }
+
+        writer.family(
+            "deepstream_source_reconnects_total",
+            "counter",
+            "Successful reconnections of the source",
+        );
+        for (id, labels) in sources() {
+            let value = self.reconnect_stats.get(id).map_or(0, |s| s.reconnects);
+            writer.sample("deepstream_source_reconnects_total", &labels, value as f64);
+        }
+
+        writer.family(
+            "deepstream_source_latency_seconds",
+            "histogram",
+            "Time since the frame left the source bin, by pipeline stage",
+        );
+        for (id, [source]) in sources() {
+            for (stage, histogram) in latency.get(id).into_iter().flatten() {
+                writer.histogram(
+                    "deepstream_source_latency_seconds",
+                    &[source, ("stage", stage)],
+                    histogram,
+                );
+            }
+        }
+
+        writer.family(
+            "deepstream_source_line_crossings_total",
+            "counter",
+            "Objects that crossed a line of the analytics filter, by direction",
+        );
+        for (id, [source]) in sources() {
+            for (line, counts) in analytics.get(id).into_iter().flat_map(|a| &a.lines) {
+                for (direction, count) in [("in", counts.crossed_in), ("out", counts.crossed_out)] {
+                    writer.sample(
+                        "deepstream_source_line_crossings_total",
+                        &[source, ("line", line), ("direction", direction)],
+                        count as f64,
+                    );
+                }
+            }
+        }
+
+        writer.family(
+            "deepstream_source_zone_entries_total",
+            "counter",
+            "Objects that entered a zone of the analytics filter",
+        );
+        for (id, [source]) in sources() {
+            for (zone, counts) in analytics.get(id).into_iter().flat_map(|a| &a.zones) {
+                writer.sample(
+                    "deepstream_source_zone_entries_total",
+                    &[source, ("zone", zone)],
+                    counts.entries as f64,
+                );
+            }
+        }
+
+        writer.family(
+            "deepstream_source_zone_exits_total",
+            "counter",
+            "Objects that left a zone of the analytics filter",
+        );
+        for (id, [source]) in sources() {
+            for (zone, counts) in analytics.get(id).into_iter().flat_map(|a| &a.zones) {
+                writer.sample(
+                    "deepstream_source_zone_exits_total",
+                    &[source, ("zone", zone)],
+                    counts.exits as f64,
+                );
+            }
+        }
+
+        writer.family(
+            "deepstream_source_zone_loitering_total",
+            "counter",
+            "Objects that loitered in a zone of the analytics filter",
+        );
+        for (id, [source]) in sources() {
+            for (zone, counts) in analytics.get(id).into_iter().flat_map(|a| &a.zones) {
+                writer.sample(
+                    "deepstream_source_zone_loitering_total",
+                    &[source, ("zone", zone)],
+                    counts.loitering as f64,
+                );
+            }
+        }
+
+        writer.family(
+            "deepstream_source_zone_occupancy",
+            "gauge",
+            "Objects in a zone of the analytics filter",
+        );
+        for (id, [source]) in sources() {
+            for (zone, counts) in analytics.get(id).into_iter().flat_map(|a| &a.zones) {
+                writer.sample(
+                    "deepstream_source_zone_occupancy",
+                    &[source, ("zone", zone)],
+                    counts.occupancy as f64,
+                );
+            }
+        }
+
+        writer.finish()
+    }
+}
@@ -1,45 +1,185 @@
+use crate::api;
 use crate::common::SourceId;
 
 use super::pipeline;
-use super::pipeline::config::{PipelineConfig, SourceConfig};
-use super::pipeline::Pipeline;
+use super::pipeline::config::{
+    validate_source, ApiConfig, HashableF64, PipelineConfig, Secret, SourceConfig,
+};
+use super::pipeline::config::{GiveUp, SourceKind};
+use super::pipeline::sources::{ReconnectCallback, ReconnectEvent, ReconnectPolicy, Source};
+use super::pipeline::{FpsStats, Pipeline, SourceAnalyticsCounts, SourceState, SourceStatus};
 
-use anyhow::Error;
-use log::debug;
-use std::collections::HashMap;
-use std::{thread, time};
+use anyhow::{anyhow, Error};
+use derive_more::{Display, Error};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+pub mod metrics;
+mod watcher;
+
+/// Time to wait for more config file events before reloading it.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Commands that can be sent to a running manager.
+#[derive(Debug)]
+pub enum Command {
+    AddOrUpdateSource(SourceConfig),
+    RemoveSource(SourceId),
+    ListSources,
+    GetSource(SourceId),
+    Status,
+    Metrics,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceInfo {
+    #[serde(flatten)]
+    pub config: SourceConfig,
+    pub fps: Option<FpsStats>,
+    pub status: Option<SourceState>,
+    pub reconnect: Option<ReconnectStats>,
+    /// Counts of the analytics filter, if it runs on the source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics: Option<SourceAnalyticsCounts>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineStatus {
+    pub state: String,
+    pub running: bool,
+    pub num_sources: usize,
+}
+
+#[derive(Debug)]
+pub enum Reply {
+    Done,
+    Sources(Vec<SourceInfo>),
+    Source(Box<SourceInfo>),
+    Status(PipelineStatus),
+    /// Prometheus text exposition.
+    Metrics(String),
+}
+
+#[derive(Debug, Display, Error)]
+pub enum CommandError {
+    #[display(fmt = "Source {} not found", _0)]
+    SourceNotFound(#[error(not(source))] SourceId),
+    #[display(fmt = "{}", _0)]
+    InvalidConfig(#[error(not(source))] String),
+    #[display(fmt = "{}", _0)]
+    Failed(#[error(not(source))] String),
+}
+
+pub type CommandResult = Result<Reply, CommandError>;
+
+/// Events handled by the manager loop.
+pub enum Event {
+    Command(Command, mpsc::Sender<CommandResult>),
+    /// The config file changed or a reload was requested with SIGHUP.
+    ConfigChanged,
+    Reconnect(SourceId, ReconnectEvent),
+}
+
+/// Reconnections of a source since it was added.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconnectStats {
+    pub attempts: u32,
+    pub reconnects: u32,
+    pub gave_up: bool,
+}
+
+/// Sources to change to go from a config to another.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SourcesDiff {
+    pub added: Vec<SourceId>,
+    pub updated: Vec<SourceId>,
+    pub removed: Vec<SourceId>,
+}
+
+impl SourcesDiff {
+    /// Compare the `current` sources with the `new` ones, ignoring `skip`.
+    pub fn new(
+        current: &HashMap<SourceId, SourceConfig>,
+        new: &[SourceConfig],
+        skip: &HashSet<SourceId>,
+    ) -> Self {
+        let mut diff = SourcesDiff::default();
+
+        for config in new.iter().filter(|c| !skip.contains(&c.id)) {
+            match current.get(&config.id) {
+                None => diff.added.push(config.id),
+                Some(old) if old.get_hash() != config.get_hash() => diff.updated.push(config.id),
+                Some(_) => {}
+            }
+        }
+
+        let new_ids: HashSet<&SourceId> = new.iter().map(|c| &c.id).collect();
+        diff.removed = current
+            .keys()
+            .filter(|id| !skip.contains(id) && !new_ids.contains(id))
+            .copied()
+            .collect();
+
+        diff.added.sort();
+        diff.updated.sort();
+        diff.removed.sort();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
 
 pub struct PipelineManager {
     pipeline: Pipeline,
     config_filename: String,
-    sources_config_hash: HashMap<SourceId, u64>,
+    api_config: Option<ApiConfig>,
+    sources_config: HashMap<SourceId, SourceConfig>,
+    /// Sources added through the API, not synced with the config file.
+    api_sources: HashSet<SourceId>,
+    events_sender: mpsc::Sender<Event>,
+    events: mpsc::Receiver<Event>,
+    config_watcher: Option<notify::RecommendedWatcher>,
+    reconnect_stats: HashMap<SourceId, ReconnectStats>,
+    /// When to reload the config, the file events come in bursts so it's
+    /// reloaded once they stop.
+    pending_reload: Option<Instant>,
 }
 
 impl PipelineManager {
     pub fn new(filename: &str) -> Result<Self, Error> {
-        let pipeline_config = match PipelineConfig::from_file(filename) {
-            Ok(pipeline_config) => pipeline_config,
-            Err(e) => panic!("Error load pipelin config file, {}", e),
-        };
+        let pipeline_config = PipelineConfig::from_file(filename)
+            .map_err(|e| anyhow!("Error loading config file {}. {}", filename, e))?;
 
-        let pipeline = match Pipeline::new(
+        let pipeline = Pipeline::new(
+            pipeline_config.backend,
             pipeline_config.streammux,
             pipeline_config.filters,
             pipeline_config.sinks,
-        ) {
-            Ok(r) => r,
-            Err(e) => {
-                panic!("Error! {}", e);
-            }
-        };
+            pipeline_config.metrics,
+            pipeline_config.overlay,
+        )?;
+
+        let (events_sender, events) = mpsc::channel();
 
         let mut manager = PipelineManager {
             pipeline,
             config_filename: filename.to_string(),
-            sources_config_hash: HashMap::new(),
+            api_config: pipeline_config.api,
+            sources_config: HashMap::new(),
+            api_sources: HashSet::new(),
+            events_sender,
+            events,
+            config_watcher: None,
+            reconnect_stats: HashMap::new(),
+            pending_reload: None,
         };
 
-        manager.update_config()?;
+        manager.update_sources(&pipeline_config.sources)?;
 
         Ok(manager)
     }
@@ -47,24 +187,51 @@ impl PipelineManager {
     pub fn add_or_update_source(&mut self, config: &SourceConfig) -> Result<(), Error> {
         let source_id = &config.id;
 
-        if let Some(old_config_hash) = self.sources_config_hash.get(source_id) {
+        if let Some(old_config) = self.sources_config.get(source_id) {
             // skip if same config
-            if old_config_hash == &config.get_hash() {
+            if old_config.get_hash() == config.get_hash() {
                 debug!("Same config of source {}, skip update", source_id);
                 return Ok(());
             }
+        }
 
-            // if source alredy exist, remove it
+        // created before the current source is removed, which is kept if it fails
+        let src = self.create_source(config)?;
+        let old_config = self.sources_config.remove(source_id);
+        if old_config.is_some() {
             self.pipeline.remove_source(source_id)?;
         }
+        if let Err(e) = self.pipeline.add_source(src, source_id) {
+            if let Some(old_config) = old_config {
+                self.restore_source(old_config);
+            }
+            return Err(e);
+        }
+
+        self.sources_config.insert(*source_id, config.clone());
 
-        // add source
-        match &config.kind {
+        Ok(())
+    }
+
+    /// Add back a source whose update failed.
+    fn restore_source(&mut self, config: SourceConfig) {
+        let id = config.id;
+        let restored = self
+            .create_source(&config)
+            .and_then(|src| self.pipeline.add_source(src, &id));
+        match restored {
+            Ok(()) => {
+                self.sources_config.insert(id, config);
+            }
+            Err(e) => error!("Cant restore source {}. {}", id, e),
+        }
+    }
+
+    fn create_source(&self, config: &SourceConfig) -> Result<Box<dyn Source>, Error> {
+        let src: Box<dyn Source> = match &config.kind {
             pipeline::config::SourceKind::Test => {
-                let src = pipeline::sources::TestSource::new().expect("Cant cerate test source");
-                self.pipeline
-                    .add_source(&src, source_id)
-                    .expect("Cant add source");
+                let src = pipeline::sources::TestSource::new()?;
+                Box::new(src)
             }
             pipeline::config::SourceKind::Uri {
                 uri,
@@ -72,87 +239,327 @@ impl PipelineManager {
                 password,
             } => {
                 let src = pipeline::sources::URISource::new(
+                    self.pipeline.element_factory().as_ref(),
                     uri,
-                    username.as_deref(),
-                    password.as_deref(),
-                )
-                .expect("Cant cerate uri source");
-                self.pipeline
-                    .add_source(&src, source_id)
-                    .expect("Cant add source");
+                    username.as_ref().map(Secret::expose),
+                    password.as_ref().map(Secret::expose),
+                )?;
+                Box::new(src)
             }
             pipeline::config::SourceKind::Rtsp {
                 uri,
                 username,
                 password,
+                reconnect,
             } => {
+                let events_sender = self.events_sender.clone();
+                let id = config.id;
+                let on_reconnect: ReconnectCallback = Arc::new(move |event| {
+                    let _ = events_sender.send(Event::Reconnect(id, event));
+                });
                 let src = pipeline::sources::RTSPSource::new(
-                    &uri,
-                    username.as_deref(),
-                    password.as_deref(),
-                )
-                .expect("Cant create rtsp source");
-                self.pipeline
-                    .add_source(&src, source_id)
-                    .expect("Cant add source");
+                    uri,
+                    username.as_ref().map(Secret::expose),
+                    password.as_ref().map(Secret::expose),
+                    id,
+                    ReconnectPolicy::from(reconnect),
+                    on_reconnect,
+                )?;
+                Box::new(src)
+            }
+            pipeline::config::SourceKind::File {
+                path,
+                looping,
+                start_offset,
+                end_offset,
+                playback_rate,
+            } => {
+                let seconds = |s: &HashableF64| gst::ClockTime::from_nseconds((s.0 * 1e9) as u64);
+                let playback = pipeline::sources::FilePlayback {
+                    looping: *looping,
+                    start_offset: start_offset.as_ref().map(seconds),
+                    end_offset: end_offset.as_ref().map(seconds),
+                    rate: playback_rate.map_or(1.0, |r| r.0),
+                };
+                let src = pipeline::sources::FileSource::new(
+                    self.pipeline.element_factory().as_ref(),
+                    path,
+                    playback,
+                )?;
+                Box::new(src)
             }
         };
 
-        self.sources_config_hash
-            .insert(*source_id, config.get_hash());
+        Ok(src)
+    }
 
-        Ok(())
+    /// Config of the sources in the pipeline.
+    #[cfg(test)]
+    pub fn sources_config(&self) -> &HashMap<SourceId, SourceConfig> {
+        &self.sources_config
     }
 
     pub fn remove_source(&mut self, id: &SourceId) -> Result<(), Error> {
-        if self.sources_config_hash.remove(id).is_some() {
+        if self.sources_config.remove(id).is_some() {
+            self.reconnect_stats.remove(id);
             self.pipeline.remove_source(id)?;
         }
 
         Ok(())
     }
 
+    /// Reload the config file, keeping the current config if it's invalid.
+    ///
+    /// The parts of the config that can't be applied are logged and left as
+    /// they are, it only fails if the pipeline stopped running.
     pub fn update_config(&mut self) -> Result<(), Error> {
-        // load config file
         let filename = &self.config_filename;
         let pipeline_config = match PipelineConfig::from_file(filename) {
             Ok(pipeline_config) => pipeline_config,
-            Err(e) => panic!("Error load pipelin config file, {}", e),
+            Err(e) => {
+                error!(
+                    "Error reloading config file {}, keeping the last good config. {}",
+                    filename, e
+                );
+                return Ok(());
+            }
         };
 
-        // add or update sources
-        for src_config in &pipeline_config.sources {
-            self.add_or_update_source(src_config)?;
+        if self.pipeline.filters_config() != pipeline_config.filters {
+            info!("Config reloaded, replacing the filters");
+            if let Err(e) = self.pipeline.update_filters(pipeline_config.filters) {
+                error!("Cant replace the filters. {}", e);
+            }
+        }
+        if self.pipeline.overlay_config() != pipeline_config.overlay {
+            info!("Config reloaded, updating the overlay");
+            self.pipeline.update_overlay(pipeline_config.overlay);
+        }
+        if self.pipeline.sinks_config() != &pipeline_config.sinks {
+            info!("Config reloaded, updating the sinks");
+            if let Err(e) = self.pipeline.update_sinks(pipeline_config.sinks) {
+                error!("Cant update the sinks. {}", e);
+            }
+        }
+
+        if let Err(e) = self.update_sources(&pipeline_config.sources) {
+            error!("{}", e);
         }
 
-        // delete sources not in config
-        let mut src_id_to_remove = Vec::new();
-        for src_id in self.sources_config_hash.keys() {
-            let mut not_found = true;
-            for src_config in &pipeline_config.sources {
-                if src_id == &(src_config.id) {
-                    not_found = false;
-                    break;
+        if !self.pipeline.is_running() {
+            return Err(anyhow!("Pipeline stopped while reloading the config"));
+        }
+        Ok(())
+    }
+
+    /// Apply the changes from the current sources to `sources`.
+    ///
+    /// A source that fails is logged and skipped, so the others are still
+    /// applied, and it's tried again by the next reload. The error lists the
+    /// sources that failed.
+    fn update_sources(&mut self, sources: &[SourceConfig]) -> Result<(), Error> {
+        // the sources added through the api are left as they are
+        let diff = SourcesDiff::new(&self.sources_config, sources, &self.api_sources);
+        if diff.is_empty() {
+            debug!("Config reloaded, no source changed");
+            return Ok(());
+        }
+
+        let mut failed = Vec::new();
+        for src_config in sources {
+            if diff.added.contains(&src_config.id) || diff.updated.contains(&src_config.id) {
+                if let Err(e) = self.add_or_update_source(src_config) {
+                    error!("Cant add or update source {}. {}", src_config.id, e);
+                    failed.push(src_config.id);
                 }
             }
-            if not_found {
-                src_id_to_remove.push(src_id.to_owned());
+        }
+        for src_id in diff.removed.iter() {
+            if let Err(e) = self.remove_source(src_id) {
+                error!("Cant remove source {}. {}", src_id, e);
+                failed.push(*src_id);
             }
         }
-        for src_id in src_id_to_remove.iter() {
-            self.remove_source(src_id)?;
+
+        info!(
+            "Config reloaded, added: {:?}, updated: {:?}, removed: {:?}",
+            diff.added, diff.updated, diff.removed
+        );
+
+        if !failed.is_empty() {
+            return Err(anyhow!("Sources {:?} not applied", failed));
+        }
+        Ok(())
+    }
+
+    fn source_info(&self, id: &SourceId) -> Option<SourceInfo> {
+        let config = self.sources_config.get(id)?;
+        let fps = self.pipeline.sources_fps().get(id).copied().flatten();
+        let status = self.pipeline.sources_status().remove(id);
+        let reconnect = self.reconnect_stats.get(id).cloned();
+        let analytics = self.pipeline.sources_analytics().remove(id);
+
+        Some(SourceInfo {
+            config: config.clone(),
+            fps,
+            status,
+            reconnect,
+            analytics,
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> CommandResult {
+        let failed = |e: Error| CommandError::Failed(e.to_string());
+
+        match command {
+            Command::AddOrUpdateSource(config) => {
+                let issues = validate_source(&config);
+                if !issues.is_empty() {
+                    let issues: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+                    return Err(CommandError::InvalidConfig(issues.join(", ")));
+                }
+                let id = config.id;
+                self.add_or_update_source(&config).map_err(failed)?;
+                self.api_sources.insert(id);
+                Ok(Reply::Done)
+            }
+            Command::RemoveSource(id) => {
+                if !self.sources_config.contains_key(&id) {
+                    return Err(CommandError::SourceNotFound(id));
+                }
+                self.remove_source(&id).map_err(failed)?;
+                self.api_sources.remove(&id);
+                Ok(Reply::Done)
+            }
+            Command::ListSources => {
+                let mut ids: Vec<&SourceId> = self.sources_config.keys().collect();
+                ids.sort();
+                let sources = ids.iter().filter_map(|id| self.source_info(id)).collect();
+                Ok(Reply::Sources(sources))
+            }
+            Command::GetSource(id) => match self.source_info(&id) {
+                Some(info) => Ok(Reply::Source(Box::new(info))),
+                None => Err(CommandError::SourceNotFound(id)),
+            },
+            Command::Status => Ok(Reply::Status(PipelineStatus {
+                state: format!("{:?}", self.pipeline.state()),
+                running: self.pipeline.is_running(),
+                num_sources: self.sources_config.len(),
+            })),
+            Command::Metrics => Ok(Reply::Metrics(self.metrics())),
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Command(command, reply) => {
+                debug!("Command {:?}", command);
+                let result = self.handle_command(command);
+                if let Err(e) = &result {
+                    error!("Command failed: {}", e);
+                }
+                // the requester may have timed out
+                let _ = reply.send(result);
+            }
+            Event::ConfigChanged => {
+                self.pending_reload = Some(Instant::now() + RELOAD_DEBOUNCE);
+            }
+            Event::Reconnect(id, event) => {
+                if let Err(e) = self.handle_reconnect(id, event) {
+                    error!("Error handling reconnection of source {}: {}", id, e);
+                }
+            }
+        }
+    }
+
+    fn handle_reconnect(&mut self, id: SourceId, event: ReconnectEvent) -> Result<(), Error> {
+        // events of a removed source may still be queued
+        let give_up = match self.sources_config.get(&id).map(|c| &c.kind) {
+            Some(SourceKind::Rtsp { reconnect, .. }) => reconnect.give_up,
+            _ => return Ok(()),
+        };
+        let stats = self.reconnect_stats.entry(id).or_default();
+
+        match event {
+            ReconnectEvent::Attempt { attempt } => {
+                stats.attempts += 1;
+                let error = format!("No data, reconnection attempt {}", attempt);
+                self.pipeline
+                    .set_source_status(&id, SourceStatus::Failed { error });
+            }
+            ReconnectEvent::Reconnected { .. } => {
+                stats.reconnects += 1;
+                self.pipeline.set_source_status(&id, SourceStatus::Playing);
+            }
+            ReconnectEvent::GaveUp { attempts } => {
+                stats.gave_up = true;
+                match give_up {
+                    GiveUp::Stop => {
+                        let error = format!("Not reconnected after {} attempts", attempts);
+                        self.pipeline.stop_source(&id, error)?;
+                    }
+                    GiveUp::Remove => {
+                        info!("Removing source {}, not reconnected", id);
+                        self.remove_source(&id)?;
+                        self.api_sources.remove(&id);
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Send [`Event::ConfigChanged`] when the config file changes or on SIGHUP.
+    fn watch_config(&mut self) {
+        match watcher::watch_config_file(&self.config_filename, self.events_sender.clone()) {
+            Ok(config_watcher) => self.config_watcher = Some(config_watcher),
+            Err(e) => warn!("Cant watch config file, reload it with SIGHUP: {}", e),
+        }
+        if let Err(e) = watcher::watch_sighup(self.events_sender.clone()) {
+            warn!("Cant handle SIGHUP: {}", e);
+        }
+    }
+
     pub fn run(&mut self) -> Result<(), Error> {
         // start pipeline
         self.pipeline.start()?;
 
-        let delay = time::Duration::from_secs(10);
+        // start control api
+        if let Some(api_config) = &self.api_config {
+            api::start(api_config, self.events_sender.clone())?;
+        }
+
+        self.watch_config();
+
+        let delay = Duration::from_secs(10);
+        let mut next_check = Instant::now() + delay;
         loop {
-            thread::sleep(delay);
+            let deadline = match self.pending_reload {
+                Some(reload) => reload.min(next_check),
+                None => next_check,
+            };
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(timeout) {
+                // a busy channel doesn't delay the reload and the checks
+                Ok(event) => self.handle_event(event),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("Manager events channel closed"));
+                }
+            }
+
+            let now = Instant::now();
+            if self.pending_reload.is_some_and(|reload| reload <= now) {
+                self.pending_reload = None;
+                if let Err(e) = self.update_config() {
+                    error!("{}", e);
+                }
+            }
+            if next_check > now {
+                continue;
+            }
+            next_check = now + delay;
 
             if !self.pipeline.is_running() {
                 break;
@@ -161,9 +568,6 @@ impl PipelineManager {
             // log fps
             let fps = self.pipeline.sources_fps();
             log::debug!("FPS: {:?}", fps);
-
-            // sync with config
-            self.update_config()?;
         }
 
         Ok(())
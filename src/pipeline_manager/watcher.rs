@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Error};
+use log::{debug, error, info};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use super::Event;
+
+/// Send [`Event::ConfigChanged`] every time `filename` is written, created or
+/// replaced. The returned watcher stops watching when dropped.
+pub fn watch_config_file(
+    filename: &str,
+    events: mpsc::Sender<Event>,
+) -> Result<RecommendedWatcher, Error> {
+    let path = Path::new(filename);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid config file name {}", filename))?
+        .to_owned();
+    // watch the parent dir, editors usually replace the file instead of writing it
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+        _ => Path::new(".").to_owned(),
+    };
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Config watch error: {}", e);
+                return;
+            }
+        };
+        if let EventKind::Access(_) = event.kind {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == Some(file_name.as_os_str()))
+        {
+            debug!("Config file event {:?}", event.kind);
+            let _ = events.send(Event::ConfigChanged);
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    info!("Watching config file {}", filename);
+
+    Ok(watcher)
+}
+
+/// Send [`Event::ConfigChanged`] on every SIGHUP.
+pub fn watch_sighup(events: mpsc::Sender<Event>) -> Result<(), Error> {
+    let mut signals = Signals::new([SIGHUP])?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("SIGHUP received, reloading config");
+            if events.send(Event::ConfigChanged).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -1,7 +1,1406 @@
-use super::pipeline::config::PipelineConfig;
+use super::api::{command_response, route, ApiResponse, Body};
+use super::cli::source_body;
+use super::pipeline::analytics::geometry::{contains, crossing};
+use super::pipeline::analytics::{
+    Analytics, AnalyticsEvent, AnalyticsFrameMeta, Direction, EventKind, ObjectPosition, Point,
+    SourceAnalytics,
+};
+use super::pipeline::config::{
+    validate_source, AnalyticsConfig, Backend, ConfigError, FilterConfig, HashableF64, InferConfig,
+    LineConfig, LineDirection, ObjconvMode, OverlayConfig, OverlayZoneConfig, PipelineConfig,
+    Secret, SinksConfig, SourceAnalyticsConfig, SourceConfig, SourceKind, StreamMuxConfig,
+    ZoneConfig,
+};
+use super::pipeline::description::describe;
+use super::pipeline::filters::object_filter::ObjectFilterConfig;
+use super::pipeline::filters::processor::{
+    create_processor, parse_config, per_source, processor_names, register_builtin_processors,
+    register_processor, Frame, FrameProcessor,
+};
+use super::pipeline::filters::{prepare_chain, swap_chain};
+use super::pipeline::metrics::fps::{Clock, FpsCounter};
+use super::pipeline::metrics::AnalyticsCounters;
+use super::pipeline::overlay::source_display_meta;
+use super::pipeline::sources::{ReconnectEvent, ReconnectPolicy, Reconnector, TestSource};
+use super::pipeline::{
+    Histogram, Pipeline, Recovery, SourceRegistry, SourceStatus, MAX_SOURCE_RESTARTS,
+};
+use super::pipeline_manager::metrics::PrometheusWriter;
+use super::pipeline_manager::{
+    Command, CommandError, CommandResult, Event, PipelineManager, Reply, SourcesDiff,
+};
+use ds::meta_schema::NvDsEventMsgMeta;
+use ds::owned_meta::{BatchMeta, FrameMeta, ObjectMeta};
+use gst::prelude::*;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Method, TestRequest};
+
+/// Sinks of [`minimal_config_yaml`] that are all disabled.
+const NO_SINKS: &str = "{display: false, rtsp: false, msg_broker: null}";
+
+/// Config without sources, with the yaml of the `filters` and the `sinks`
+/// values, either inline or as blocks starting with a newline.
+fn minimal_config_yaml(filters: &str, sinks: &str) -> String {
+    format!(
+        r#"
+sources: []
+streammux:
+  batch_size: 1
+  enable_padding: false
+  width: 1280
+  height: 720
+filters: {}
+sinks: {}
+"#,
+        filters, sinks
+    )
+}
 
 #[test]
 fn load_pipeline_config() {
     let config = PipelineConfig::from_file("config/pipeline_config.yml").unwrap();
     assert_eq!(config.sources[0].id, 1);
 }
+
+#[test]
+fn validate_pipeline_config() {
+    let yaml = r#"
+sources:
+  - id: 0
+    kind:
+      type: "rtsp"
+      uri: "http://camera/stream"
+  - id: 0
+    kind:
+      type: "test"
+streammux:
+  batch_size: 1
+  enable_padding: true
+  width: 1280
+  height: 720
+filters:
+  - NvInfer:
+      config_path: "config/filters/missing.txt"
+sinks:
+  display: false
+  rtsp: false
+  msg_broker:
+    topic: "ds-meta"
+    server: "kafka"
+    port: 70000
+"#;
+    let issues = match PipelineConfig::from_yaml(yaml) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    let paths: Vec<(&str, usize)> = issues
+        .iter()
+        .map(|i| (i.path.as_str(), i.location.unwrap().line))
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            ("sources[0].kind.uri", 6),
+            ("sources[1].id", 7),
+            ("streammux.batch_size", 11),
+            ("filters[0].NvInfer.config_path", 17),
+            ("sinks.msg_broker.port", 24),
+        ]
+    );
+}
+
+#[test]
+fn secondary_inference() {
+    let yaml = |operate_on_gie_id: u32| {
+        let filters = format!(
+            r#"
+  - NvInfer:
+      config_path: "config/filters/pgie_config.txt"
+      gie_unique_id: 1
+  - NvInfer:
+      config_path: "config/filters/pgie_config.txt"
+      gie_unique_id: 2
+      operate_on_gie_id: {}
+      operate_on_class_ids: [0, 2]
+      batch_size: 16"#,
+            operate_on_gie_id
+        );
+        minimal_config_yaml(&filters, NO_SINKS)
+    };
+
+    let config = PipelineConfig::from_yaml(&yaml(1)).unwrap();
+    match &config.filters[1] {
+        FilterConfig::NvInfer(infer) => assert_eq!(infer.operate_on_class_ids, vec![0, 2]),
+        filter => panic!("Expected NvInfer, got {:?}", filter),
+    }
+    let description = describe(&config);
+    assert!(description.contains(
+        "nvinfer config-file-path=config/filters/pgie_config.txt unique-id=2 process-mode=secondary infer-on-gie-id=1 infer-on-class-ids=0:2 batch-size=16"
+    ));
+
+    // a stage can only operate on a previous one
+    let issues = match PipelineConfig::from_yaml(&yaml(2)) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(paths, vec!["filters[1].NvInfer.operate_on_gie_id"]);
+}
+
+#[test]
+fn objconv_config() {
+    let yaml = |objconv: &str| {
+        let sinks = format!(
+            r#"
+  display: false
+  rtsp: false
+  msg_broker:
+    topic: "ds-meta"
+    server: "kafka"
+    port: 9092
+    objconv:
+{}"#,
+            objconv
+        );
+        minimal_config_yaml("[]", &sinks)
+    };
+
+    let config = PipelineConfig::from_yaml(&yaml(
+        r#"      mode: "per-track"
+      class_ids: [0, 2]
+      deny_labels: ["bicycle", "dog"]
+      min_confidence: 0.5
+      frame_interval: 5"#,
+    ))
+    .unwrap();
+    let objconv = &config.sinks.msg_broker.as_ref().unwrap().objconv;
+    assert_eq!(objconv.mode, ObjconvMode::PerTrack);
+    assert_eq!(objconv.throttle_interval, 1000);
+    // only the properties not at their default are described
+    assert!(describe(&config).contains(
+        "nvobjconv mode=per-track class-ids=\"0;2\" deny-labels=\"bicycle;dog\" min-confidence=0.5 frame-interval=5 ! "
+    ));
+
+    let issues = match PipelineConfig::from_yaml(&yaml(
+        r#"      min_confidence: 1.5
+      frame_interval: 0
+      labels: ["car;truck"]"#,
+    )) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "sinks.msg_broker.objconv.min_confidence",
+            "sinks.msg_broker.objconv.frame_interval",
+            "sinks.msg_broker.objconv.labels[0]",
+        ]
+    );
+}
+
+#[test]
+fn frame_processors() {
+    struct Noop;
+
+    impl FrameProcessor for Noop {
+        type State = u64;
+
+        fn process_frame(&mut self, _frame: &mut Frame, frames: &mut u64) {
+            *frames += 1;
+        }
+    }
+
+    let yaml = |processor: &str| {
+        let filters = format!(
+            r#"
+  - Processor:
+      name: "{}"
+      config:
+        min_confidence: 0.5
+        min_width: 16"#,
+            processor
+        );
+        minimal_config_yaml(&filters, NO_SINKS)
+    };
+
+    register_builtin_processors();
+    let config = PipelineConfig::from_yaml(&yaml("object_filter")).unwrap();
+    let filter_config = match &config.filters[0] {
+        FilterConfig::Processor { name, config } => {
+            assert_eq!(name, "object_filter");
+            parse_config::<ObjectFilterConfig>(config).unwrap()
+        }
+        filter => panic!("Expected Processor, got {:?}", filter),
+    };
+    assert!(filter_config.keeps(0, 0.9, 32.0, 32.0));
+    assert!(!filter_config.keeps(0, 0.4, 32.0, 32.0));
+    assert!(!filter_config.keeps(0, 0.9, 8.0, 32.0));
+    assert!(describe(&config).contains("! queue ! identity ! identity !"));
+
+    // the processors run on the owned batches as well
+    let object = |confidence, width| {
+        ObjectMeta::builder()
+            .confidence(confidence)
+            .bbox(0.0, 0.0, width, 32.0)
+            .build()
+    };
+    let mut batch = BatchMeta::builder()
+        .frame(
+            FrameMeta::builder()
+                .object(object(0.9, 32.0))
+                .object(object(0.4, 32.0))
+                .object(object(0.9, 8.0))
+                .build(),
+        )
+        .build();
+    let processor_config = serde_yaml::from_str("{min_confidence: 0.5, min_width: 16}").unwrap();
+    let mut processor = create_processor("object_filter", &processor_config).unwrap();
+    processor.process_batch(&mut batch, None);
+    assert_eq!(batch.frames_mut()[0].objects_mut().len(), 1);
+
+    // the processors are checked when the config is loaded
+    let issues = match PipelineConfig::from_yaml(&yaml("noop")) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    assert_eq!(issues[0].path, "filters[0].Processor.name");
+
+    register_processor("noop", |_| Ok(Noop));
+    assert!(processor_names().contains(&"noop".to_string()));
+    PipelineConfig::from_yaml(&yaml("noop")).unwrap();
+
+    // the config must be one of the processor
+    let invalid = yaml("object_filter").replace("min_width: 16", "min_width: wide");
+    let issues = match PipelineConfig::from_yaml(&invalid) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    assert_eq!(issues[0].path, "filters[0].Processor.config");
+}
+
+#[test]
+fn line_crossing_geometry() {
+    let (a, b) = (Point::new(0.0, 100.0), Point::new(200.0, 100.0));
+    // the right of a line going right is below it
+    let above = Point::new(50.0, 90.0);
+    let below = Point::new(50.0, 110.0);
+    assert_eq!(crossing(a, b, above, below), Some(Direction::In));
+    assert_eq!(crossing(a, b, below, above), Some(Direction::Out));
+    assert_eq!(crossing(a, b, above, Point::new(60.0, 80.0)), None);
+    // crossing the line outside of the segment
+    assert_eq!(
+        crossing(a, b, Point::new(250.0, 90.0), Point::new(250.0, 110.0)),
+        None
+    );
+    // stopping on the line is a single crossing
+    let on = Point::new(50.0, 100.0);
+    assert_eq!(crossing(a, b, above, on), Some(Direction::In));
+    assert_eq!(crossing(a, b, on, below), None);
+
+    let square: Vec<Point> = [(0, 0), (100, 0), (100, 100), (0, 100)]
+        .iter()
+        .map(|p| Point::from(*p))
+        .collect();
+    assert!(contains(&square, Point::new(50.0, 50.0)));
+    assert!(!contains(&square, Point::new(150.0, 50.0)));
+    assert!(!contains(&[], Point::new(50.0, 50.0)));
+}
+
+#[test]
+fn analytics_events_and_counts() {
+    let config = SourceAnalyticsConfig {
+        source_id: 1,
+        class_ids: vec![2],
+        lines: vec![LineConfig {
+            name: "gate".to_string(),
+            from: (0, 100),
+            to: (200, 100),
+            direction: LineDirection::Both,
+        }],
+        zones: vec![ZoneConfig {
+            name: "parking".to_string(),
+            points: vec![(0, 120), (200, 120), (200, 200), (0, 200)],
+            loitering_threshold: None,
+        }],
+    };
+    let mut analytics = SourceAnalytics::new(config.clone(), 2);
+    let mut frame = 0;
+    let mut update = |objects: &[ObjectPosition]| {
+        frame += 1;
+        analytics.update(Duration::from_secs(frame), objects)
+    };
+    let car = |y: f32| ObjectPosition {
+        object_id: 7,
+        class_id: 2,
+        position: Point::new(50.0, y),
+    };
+    let person = ObjectPosition {
+        object_id: 8,
+        class_id: 0,
+        position: Point::new(50.0, 150.0),
+    };
+    let kinds = |events: Vec<AnalyticsEvent>| -> Vec<(EventKind, String)> {
+        events.into_iter().map(|e| (e.kind, e.name)).collect()
+    };
+
+    assert!(update(&[car(80.0), person]).is_empty());
+    let events = update(&[car(110.0)]);
+    assert_eq!(events[0].object_id, 7);
+    assert_eq!(
+        kinds(events),
+        vec![(
+            EventKind::LineCrossing {
+                direction: Direction::In
+            },
+            "gate".to_string()
+        )]
+    );
+    let entered = update(&[car(150.0)]);
+    assert_eq!(
+        kinds(entered.clone()),
+        vec![(EventKind::ZoneEntry, "parking".to_string())]
+    );
+
+    // the car leaves the zone once it's missing for more than 2 frames,
+    // after the frame it was last seen in
+    assert!(update(&[]).is_empty());
+    assert!(update(&[]).is_empty());
+    let left = update(&[]);
+    assert_eq!(
+        kinds(left.clone()),
+        vec![(
+            EventKind::ZoneExit {
+                dwell: 0.0,
+                loitering: false
+            },
+            "parking".to_string()
+        )]
+    );
+
+    let counters = AnalyticsCounters::new();
+    counters.apply(1, &config, &entered);
+    let counts = counters.apply(1, &config, &[]);
+    assert_eq!(counts.zones["parking"].occupancy, 1);
+    assert_eq!(counts.lines["gate"].crossed_in, 0);
+    let counts = counters.apply(1, &config, &left);
+    assert_eq!(counts.zones["parking"].entries, 1);
+    assert_eq!(counts.zones["parking"].exits, 1);
+    assert_eq!(counts.zones["parking"].occupancy, 0);
+    counters.remove(&1);
+    assert!(counters.counts(&1).is_none());
+}
+
+#[test]
+fn zone_loitering() {
+    let config = SourceAnalyticsConfig {
+        source_id: 1,
+        class_ids: vec![],
+        lines: vec![],
+        zones: vec![ZoneConfig {
+            name: "entrance".to_string(),
+            points: vec![(0, 0), (100, 0), (100, 100), (0, 100)],
+            loitering_threshold: Some(10),
+        }],
+    };
+    let mut analytics = SourceAnalytics::new(config.clone(), 1);
+    let person = |object_id, x: f32| ObjectPosition {
+        object_id,
+        class_id: 0,
+        position: Point::new(x, 50.0),
+    };
+    let kinds = |events: Vec<AnalyticsEvent>| -> Vec<(u64, EventKind)> {
+        events.into_iter().map(|e| (e.object_id, e.kind)).collect()
+    };
+    let at = |secs: f64| Duration::from_secs_f64(secs);
+
+    assert_eq!(
+        kinds(analytics.update(at(100.0), &[person(1, 50.0), person(2, 50.0)])),
+        vec![(1, EventKind::ZoneEntry), (2, EventKind::ZoneEntry)]
+    );
+    // person 2 leaves before the threshold
+    assert_eq!(
+        kinds(analytics.update(at(105.0), &[person(1, 50.0), person(2, 150.0)])),
+        vec![(
+            2,
+            EventKind::ZoneExit {
+                dwell: 5.0,
+                loitering: false
+            }
+        )]
+    );
+    // person 1 loiters once
+    assert_eq!(
+        kinds(analytics.update(at(110.5), &[person(1, 50.0)])),
+        vec![(1, EventKind::Loitering { dwell: 10.5 })]
+    );
+    assert!(analytics.update(at(111.0), &[person(1, 60.0)]).is_empty());
+
+    // its track is lost, the dwell ends when it was last seen
+    assert!(analytics.update(at(112.0), &[]).is_empty());
+    let left = analytics.update(at(113.0), &[]);
+    assert_eq!(
+        kinds(left.clone()),
+        vec![(
+            1,
+            EventKind::ZoneExit {
+                dwell: 11.0,
+                loitering: true
+            }
+        )]
+    );
+
+    let counters = AnalyticsCounters::new();
+    let counts = counters.apply(
+        1,
+        &config,
+        &[AnalyticsEvent {
+            kind: EventKind::Loitering { dwell: 10.5 },
+            name: "entrance".to_string(),
+            object_id: 1,
+            class_id: 0,
+        }],
+    );
+    assert_eq!(counts.zones["entrance"].loitering, 1);
+    assert_eq!(
+        serde_json::to_value(&left[0]).unwrap(),
+        serde_json::json!({
+            "type": "zone_exit",
+            "dwell": 11.0,
+            "loitering": true,
+            "name": "entrance",
+            "object_id": 1,
+            "class_id": 0,
+        })
+    );
+}
+
+#[test]
+fn analytics_loitering_messages() {
+    let config = AnalyticsConfig {
+        sources: vec![SourceAnalyticsConfig {
+            source_id: 1,
+            class_ids: vec![],
+            lines: vec![],
+            zones: vec![ZoneConfig {
+                name: "entrance".to_string(),
+                points: vec![(0, 0), (100, 0), (100, 100), (0, 100)],
+                loitering_threshold: Some(10),
+            }],
+        }],
+        max_missing_frames: 30,
+    };
+    let mut analytics = per_source(Analytics::new(config, AnalyticsCounters::new()));
+    let person = |object_id| {
+        ObjectMeta::builder()
+            .object_id(object_id)
+            .bbox(40.0, 40.0, 20.0, 10.0)
+            .build()
+    };
+    // the second id doesn't fit the tracking id of a message
+    let batch = |secs: u64| {
+        BatchMeta::builder()
+            .frame(
+                FrameMeta::builder()
+                    .source_id(1)
+                    .buf_pts(secs * 1_000_000_000)
+                    .object(person(1))
+                    .object(person(u64::MAX))
+                    .build(),
+            )
+            .build()
+    };
+
+    analytics.process_batch(&mut batch(100), None);
+    let mut loitering = batch(111);
+    analytics.process_batch(&mut loitering, None);
+
+    let frame = &loitering.frames_mut()[0];
+    let mut events: Vec<u64> = frame
+        .iter_user_meta::<AnalyticsFrameMeta>()
+        .flat_map(|meta| meta.events.iter().map(|event| event.object_id))
+        .collect();
+    events.sort_unstable();
+    assert_eq!(events, vec![1, u64::MAX]);
+    let messages: Vec<i32> = frame
+        .iter_user_meta::<NvDsEventMsgMeta>()
+        .map(|msg_meta| msg_meta.tracking_id())
+        .collect();
+    assert_eq!(messages, vec![1]);
+}
+
+#[test]
+fn analytics_kept_across_filters_updates() {
+    let mut config = SourceAnalyticsConfig {
+        source_id: 1,
+        class_ids: vec![],
+        lines: vec![],
+        zones: vec![ZoneConfig {
+            name: "entrance".to_string(),
+            points: vec![(0, 0), (100, 0), (100, 100), (0, 100)],
+            loitering_threshold: None,
+        }],
+    };
+    let person = ObjectPosition {
+        object_id: 1,
+        class_id: 0,
+        position: Point::new(50.0, 50.0),
+    };
+    let at = Duration::from_secs;
+
+    // the counters are shared by the analytics of the old and new filters
+    let counters = AnalyticsCounters::new();
+    let (events, counts) = counters.update(1, &config, 1, at(1), &[person]);
+    assert_eq!(events.len(), 1);
+    assert_eq!(counts.zones["entrance"].occupancy, 1);
+    let (events, _) = counters.update(1, &config, 1, at(2), &[person]);
+    assert!(events.is_empty());
+    counters.update(1, &config, 1, at(3), &[]);
+    let (events, counts) = counters.update(1, &config, 1, at(4), &[]);
+    assert!(matches!(events[0].kind, EventKind::ZoneExit { .. }));
+    assert_eq!(counts.zones["entrance"].occupancy, 0);
+
+    // a new config forgets the objects in the zones
+    counters.update(1, &config, 1, at(5), &[person]);
+    config.zones[0].points = vec![(0, 0), (200, 0), (200, 200), (0, 200)];
+    let (events, counts) = counters.update(1, &config, 1, at(6), &[]);
+    assert!(events.is_empty());
+    assert_eq!(counts.zones["entrance"].entries, 2);
+    assert_eq!(counts.zones["entrance"].occupancy, 0);
+
+    counters.remove(&1);
+    let (events, _) = counters.update(1, &config, 1, at(7), &[person]);
+    assert_eq!(events[0].kind, EventKind::ZoneEntry);
+}
+
+#[test]
+fn validate_analytics() {
+    let filters = r#"
+  - Analytics:
+      sources:
+        - source_id: 1
+          lines:
+            - name: "gate"
+              from: [0, 100]
+              to: [0, 100]
+          zones:
+            - name: "gate"
+              points: [[0, 0], [10, 0], [10, 10]]
+        - source_id: 1
+          zones:
+            - name: "parking"
+              points: [[0, 0], [10, 0]]
+              loitering_threshold: 0"#;
+    let issues = match PipelineConfig::from_yaml(&minimal_config_yaml(filters, NO_SINKS)) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "filters[0].Analytics.sources[0].lines[0].to",
+            "filters[0].Analytics.sources[0].zones[0].name",
+            "filters[0].Analytics.sources[1].source_id",
+            "filters[0].Analytics.sources[1].zones[0].points",
+            "filters[0].Analytics.sources[1].zones[0].loitering_threshold",
+        ]
+    );
+}
+
+#[test]
+fn overlay_zones_and_counters() {
+    let zone = |source_id, points: Vec<(u32, u32)>| OverlayZoneConfig {
+        source_id,
+        name: "entrance".to_string(),
+        points,
+        color: [1.0, 0.0, 0.0, 1.0],
+    };
+    let config = OverlayConfig {
+        zones: vec![
+            zone(0, vec![(0, 0), (100, 0), (100, 100), (0, 100)]),
+            zone(1, vec![(0, 0), (10, 0), (10, 10)]),
+        ],
+        counters: true,
+    };
+    let objects: BTreeMap<String, u64> = vec![("car".to_string(), 2), ("person".to_string(), 1)]
+        .into_iter()
+        .collect();
+
+    let meta = source_display_meta(&config, 0, Some(24.96), &objects);
+    // only the zone of the source, closed
+    assert_eq!(meta.lines().len(), 4);
+    assert_eq!(meta.lines()[3].to, (0, 0));
+    assert_eq!(meta.lines()[0].color.red, 1.0);
+    let texts: Vec<&str> = meta.texts().iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        vec!["entrance", "Source 0: 25.0 fps", "car: 2  person: 1"]
+    );
+
+    let meta = source_display_meta(&OverlayConfig::default(), 0, None, &objects);
+    assert!(meta.is_empty());
+
+    // zones with less than 3 points are invalid
+    let mut pipeline_config =
+        PipelineConfig::from_file("config/pipeline_config_software.yml").unwrap();
+    pipeline_config.overlay.zones = vec![zone(0, vec![(0, 0), (10, 10)])];
+    let yaml = serde_yaml::to_string(&pipeline_config).unwrap();
+    let issues = match PipelineConfig::from_yaml(&yaml) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    assert_eq!(issues[0].path, "overlay.zones[0].points");
+}
+
+#[test]
+fn validate_file_source() {
+    let source: SourceConfig = serde_yaml::from_str(
+        r#"
+id: 0
+kind:
+  type: "file"
+  path: "config/missing.mp4"
+  loop: true
+  start_offset: 10.0
+  end_offset: 5.0
+  playback_rate: 0
+"#,
+    )
+    .unwrap();
+    match &source.kind {
+        SourceKind::File { looping, .. } => assert!(looping),
+        kind => panic!("Expected file source, got {:?}", kind),
+    }
+
+    let paths: Vec<String> = validate_source(&source)
+        .into_iter()
+        .map(|i| i.path)
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "source.kind.path",
+            "source.kind.end_offset",
+            "source.kind.playback_rate"
+        ]
+    );
+}
+
+#[test]
+fn validate_infinite_durations() {
+    let source: SourceConfig = serde_yaml::from_str(
+        r#"
+id: 0
+kind:
+  type: "rtsp"
+  uri: "rtsp://camera/stream"
+  reconnect:
+    data_timeout: .inf
+    initial_backoff: .nan
+    backoff_multiplier: .inf
+"#,
+    )
+    .unwrap();
+    let paths: Vec<String> = validate_source(&source)
+        .into_iter()
+        .map(|i| i.path)
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "source.kind.reconnect.data_timeout",
+            "source.kind.reconnect.initial_backoff",
+            "source.kind.reconnect.backoff_multiplier"
+        ]
+    );
+
+    let mut pipeline_config =
+        PipelineConfig::from_file("config/pipeline_config_software.yml").unwrap();
+    pipeline_config.metrics.fps_window = f64::INFINITY;
+    pipeline_config.metrics.stall_timeout = f64::NAN;
+    let yaml = serde_yaml::to_string(&pipeline_config).unwrap();
+    let issues = match PipelineConfig::from_yaml(&yaml) {
+        Err(ConfigError::Invalid(issues)) => issues,
+        r => panic!("Expected invalid config, got {:?}", r),
+    };
+    let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(paths, vec!["metrics.fps_window", "metrics.stall_timeout"]);
+}
+
+#[test]
+fn secret_interpolation() {
+    let server = |server: &str| {
+        let sinks = format!(
+            "{{display: false, rtsp: false, msg_broker: {{topic: t, server: \"{}\", port: 9092}}}}",
+            server
+        );
+        PipelineConfig::from_yaml(&minimal_config_yaml("[]", &sinks))
+            .map(|config| config.sinks.msg_broker.unwrap().server)
+    };
+
+    std::env::set_var("DS_TEST_SECRET", "hunter2");
+    let secret = server("user:${DS_TEST_SECRET}").unwrap();
+    assert_eq!(secret.expose(), "user:hunter2");
+    assert_eq!(format!("{:?}", secret), "***");
+
+    let secret = server("${DS_TEST_UNSET:-default}").unwrap();
+    assert_eq!(secret.expose(), "default");
+    match server("${DS_TEST_UNSET}") {
+        Err(ConfigError::Invalid(issues)) => {
+            assert_eq!(issues[0].path, "sinks.msg_broker.server");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    // the value isn't in the issue, it may hold the secret
+    match server("hunter2${DS_TEST_SECRET") {
+        Err(ConfigError::Invalid(issues)) => {
+            assert_eq!(issues[0].path, "sinks.msg_broker.server");
+            assert!(issues[0].message.contains("offset 7"));
+            assert!(!issues[0].to_string().contains("hunter2"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let path = std::env::temp_dir().join("ds_test_secret");
+    std::fs::write(&path, "from file\n").unwrap();
+    let secret = server(&format!("file:{}", path.display())).unwrap();
+    assert_eq!(secret.expose(), "from file");
+
+    // the sources posted to the api are taken literally
+    let source: SourceConfig = serde_json::from_str(
+        r#"{"id": 1, "kind": {"type": "rtsp", "uri": "rtsp://camera", "password": "file:/etc/shadow", "username": "${DS_TEST_SECRET}"}}"#,
+    )
+    .unwrap();
+    match source.kind {
+        SourceKind::Rtsp {
+            username, password, ..
+        } => {
+            assert_eq!(username.unwrap().expose(), "${DS_TEST_SECRET}");
+            assert_eq!(password.unwrap().expose(), "file:/etc/shadow");
+        }
+        kind => panic!("unexpected {:?}", kind),
+    }
+
+    // a changed secret changes the source hash
+    let source = |password: &str| SourceConfig {
+        id: 0,
+        kind: SourceKind::Rtsp {
+            uri: "rtsp://camera".to_string(),
+            username: None,
+            password: Some(Secret::new(password)),
+            reconnect: Default::default(),
+        },
+    };
+    assert_ne!(source("a").get_hash(), source("b").get_hash());
+    assert!(!format!("{:?}", source("hunter2")).contains("hunter2"));
+}
+
+#[test]
+fn describe_software_pipeline() {
+    let config = PipelineConfig::from_file("config/pipeline_config_software.yml").unwrap();
+    let description = describe(&config);
+    let lines: Vec<&str> = description.split(" \\\n  ").collect();
+    assert_eq!(
+        lines,
+        vec![
+            "compositor name=streammux background=black ! queue ! identity ! queue ! videoconvert ! identity ! tee name=tee",
+            "tee. ! queue ! identity ! fakesink sync=false",
+            "videotestsrc ! streammux.sink_0",
+        ]
+    );
+}
+
+#[test]
+fn software_pipeline_add_remove_source() {
+    let streammux_config = StreamMuxConfig {
+        batch_size: 1,
+        enable_padding: true,
+        width: 320,
+        height: 240,
+    };
+    let sinks_config = SinksConfig {
+        display: true,
+        rtsp: false,
+        msg_broker: None,
+    };
+    let mut pipeline = Pipeline::new(
+        Backend::Software,
+        streammux_config,
+        vec![],
+        sinks_config.clone(),
+        Default::default(),
+        Default::default(),
+    )
+    .unwrap();
+
+    // a source that can't be linked is rolled back, so it can be added again
+    let unlinkable = TestSource {
+        bin: gst::Bin::new(None),
+    };
+    assert!(pipeline.add_source(Box::new(unlinkable), &0).is_err());
+    assert!(pipeline.sources_status().is_empty());
+
+    let src = TestSource::new().unwrap();
+    pipeline.add_source(Box::new(src), &0).unwrap();
+    pipeline.start().unwrap();
+    assert!(pipeline.is_running());
+    assert_eq!(pipeline.sources_status()[&0].status, SourceStatus::Playing);
+
+    // filters replaced while the source plays
+    let filters = vec![FilterConfig::NvInfer(InferConfig {
+        config_path: "config/filters/config_infer_primary.txt".to_string(),
+        ..Default::default()
+    })];
+    pipeline.update_filters(filters.clone()).unwrap();
+    assert_eq!(pipeline.filters_config(), filters);
+    assert!(pipeline.is_running());
+
+    // display removed then added back while the source plays
+    let sink_bin = pipeline.sink_bin().clone();
+    let tee = sink_bin
+        .children()
+        .into_iter()
+        .find(|child| child.factory().is_some_and(|f| f.name() == "tee"))
+        .unwrap();
+    // the tee keeps flowing while no branch is linked
+    let allow_not_linked = tee.property("allow-not-linked").unwrap();
+    assert!(allow_not_linked.get::<bool>().unwrap());
+    let branches = || (tee.src_pads().len(), sink_bin.children().len());
+    let (tee_pads, children) = branches();
+    let no_display = SinksConfig {
+        display: false,
+        ..sinks_config.clone()
+    };
+    pipeline.update_sinks(no_display.clone()).unwrap();
+    assert_eq!(pipeline.sinks_config(), &no_display);
+    assert!(pipeline.is_running());
+    // the branch is released from the main loop once drained
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while branches() != (tee_pads - 1, children - 1) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(branches(), (tee_pads - 1, children - 1));
+    // the EOS of the only sink doesn't stop the pipeline
+    assert_eq!(pipeline.state(), gst::State::Playing);
+    pipeline.update_sinks(sinks_config.clone()).unwrap();
+    assert_eq!(pipeline.sinks_config(), &sinks_config);
+    assert_eq!(branches(), (tee_pads, children));
+    assert!(pipeline.is_running());
+
+    pipeline.remove_source(&0).unwrap();
+    assert!(pipeline.sources_status().is_empty());
+}
+
+#[test]
+fn failed_source_update_keeps_the_source() {
+    let mut manager = PipelineManager::new("config/pipeline_config_software.yml").unwrap();
+    let update = SourceConfig {
+        id: 0,
+        kind: SourceKind::File {
+            path: "/nonexistent/video.mp4".to_string(),
+            looping: false,
+            start_offset: None,
+            end_offset: None,
+            playback_rate: None,
+        },
+    };
+    assert!(manager.add_or_update_source(&update).is_err());
+
+    let config = &manager.sources_config()[&0];
+    assert!(matches!(config.kind, SourceKind::Test));
+}
+
+#[test]
+fn filters_swap_failure() {
+    gst::init().unwrap();
+    let make = |name| gst::ElementFactory::make(name, None).unwrap();
+    let bin = gst::Bin::new(None);
+    let entry = make("queue");
+    let exit = make("identity");
+    bin.add_many(&[&entry, &exit]).unwrap();
+    let current = vec![make("identity")];
+    prepare_chain(&bin, &current).unwrap();
+    swap_chain(&entry, &exit, &[], &current).unwrap();
+
+    // an element of another bin can't be added to the filters
+    let other = gst::Bin::new(None);
+    let taken = make("identity");
+    other.add(&taken).unwrap();
+    let elements = vec![make("identity"), taken.clone()];
+    assert!(prepare_chain(&bin, &elements).is_err());
+
+    // the current filters stay in place
+    assert_eq!(bin.children().len(), 3);
+    assert_eq!(
+        entry.static_pad("src").unwrap().peer(),
+        current[0].static_pad("sink")
+    );
+    assert_eq!(
+        current[0].static_pad("src").unwrap().peer(),
+        exit.static_pad("sink")
+    );
+    assert_eq!(taken.parent(), Some(other.upcast()));
+
+    // the new filters are linked in place of the current ones
+    let elements = vec![make("identity"), make("identity")];
+    prepare_chain(&bin, &elements).unwrap();
+    swap_chain(&entry, &exit, &current, &elements).unwrap();
+    assert_eq!(
+        entry.static_pad("src").unwrap().peer(),
+        elements[0].static_pad("sink")
+    );
+    assert_eq!(
+        elements[1].static_pad("src").unwrap().peer(),
+        exit.static_pad("sink")
+    );
+    assert_eq!(current[0].static_pad("src").unwrap().peer(), None);
+}
+
+#[test]
+fn sources_diff() {
+    let uri = |id, uri: &str| SourceConfig {
+        id,
+        kind: SourceKind::Uri {
+            uri: uri.to_string(),
+            username: None,
+            password: None,
+        },
+    };
+    let current: HashMap<_, _> = vec![uri(0, "a"), uri(1, "b"), uri(2, "c"), uri(3, "d")]
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+    let new = vec![uri(0, "a"), uri(1, "changed"), uri(4, "e")];
+    let api_sources: HashSet<_> = vec![3].into_iter().collect();
+
+    let diff = SourcesDiff::new(&current, &new, &api_sources);
+    assert_eq!(diff.added, vec![4]);
+    assert_eq!(diff.updated, vec![1]);
+    assert_eq!(diff.removed, vec![2]);
+
+    let same: Vec<_> = current.values().cloned().collect();
+    assert!(SourcesDiff::new(&current, &same, &HashSet::new()).is_empty());
+}
+
+#[test]
+fn reconnect_backoff() {
+    let secs = Duration::from_secs;
+    let policy = ReconnectPolicy {
+        data_timeout: secs(10),
+        connect_timeout: secs(5),
+        initial_backoff: secs(1),
+        max_backoff: secs(4),
+        backoff_multiplier: 2.0,
+        jitter: 0.0,
+        max_attempts: Some(3),
+        ..Default::default()
+    };
+    assert_eq!(policy.backoff(1, 1.0), secs(1));
+    assert_eq!(policy.backoff(2, 1.0), secs(2));
+    assert_eq!(policy.backoff(5, 1.0), secs(4));
+
+    let start = Instant::now();
+    let mut reconnector = Reconnector::new(policy.clone());
+    assert_eq!(reconnector.update(start, start + secs(9)), None);
+    assert_eq!(
+        reconnector.update(start, start + secs(10)),
+        Some(ReconnectEvent::Attempt { attempt: 1 })
+    );
+    // connect timeout, then backoff of 1s
+    assert_eq!(reconnector.update(start, start + secs(15)), None);
+    assert_eq!(
+        reconnector.update(start, start + secs(16)),
+        Some(ReconnectEvent::Attempt { attempt: 2 })
+    );
+    let data = start + secs(17);
+    assert_eq!(
+        reconnector.update(data, start + secs(18)),
+        Some(ReconnectEvent::Reconnected { attempts: 2 })
+    );
+
+    // gives up after max_attempts
+    let mut reconnector = Reconnector::new(policy);
+    let mut now = start + secs(10);
+    let mut events = vec![];
+    while now < start + secs(60) {
+        events.extend(reconnector.update(start, now));
+        now += secs(1);
+    }
+    assert_eq!(
+        events,
+        vec![
+            ReconnectEvent::Attempt { attempt: 1 },
+            ReconnectEvent::Attempt { attempt: 2 },
+            ReconnectEvent::Attempt { attempt: 3 },
+            ReconnectEvent::GaveUp { attempts: 3 },
+        ]
+    );
+}
+
+#[test]
+fn prometheus_metrics() {
+    let mut writer = PrometheusWriter::new();
+    writer
+        .family("deepstream_source_fps", "gauge", "Frames per second")
+        .sample("deepstream_source_fps", &[("source", "0")], 25.5)
+        .sample("deepstream_source_fps", &[("source", "1")], 0.0)
+        .family("deepstream_source_detections_total", "counter", "Objects")
+        .sample(
+            "deepstream_source_detections_total",
+            &[("source", "0"), ("class", "a \"b\"\n")],
+            3.0,
+        )
+        .family("deepstream_overruns_total", "counter", "Overruns")
+        .sample("deepstream_overruns_total", &[], f64::INFINITY);
+
+    assert_eq!(
+        writer.finish(),
+        "# HELP deepstream_source_fps Frames per second\n\
+         # TYPE deepstream_source_fps gauge\n\
+         deepstream_source_fps{source=\"0\"} 25.5\n\
+         deepstream_source_fps{source=\"1\"} 0\n\
+         # HELP deepstream_source_detections_total Objects\n\
+         # TYPE deepstream_source_detections_total counter\n\
+         deepstream_source_detections_total{source=\"0\",class=\"a \\\"b\\\"\\n\"} 3\n\
+         # HELP deepstream_overruns_total Overruns\n\
+         # TYPE deepstream_overruns_total counter\n\
+         deepstream_overruns_total +Inf\n"
+    );
+}
+
+struct ManualClock(Mutex<Instant>);
+
+impl ManualClock {
+    fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[test]
+fn fps_window() {
+    let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+    let mut counter = FpsCounter::new(
+        Duration::from_secs(5),
+        Duration::from_secs(10),
+        clock.clone(),
+    );
+    let frames = |counter: &mut FpsCounter, n, interval| {
+        for _ in 0..n {
+            counter.update();
+            clock.advance(Duration::from_millis(interval));
+        }
+    };
+    assert_eq!(counter.stats(), None);
+
+    // window not filled yet
+    frames(&mut counter, 11, 100);
+    let stats = counter.stats().unwrap();
+    assert!((stats.avg - 10.0).abs() < 1e-6, "{:?}", stats);
+
+    // a jittery stream, 5s at 10 FPS with alternate 50ms and 150ms intervals
+    for _ in 0..25 {
+        frames(&mut counter, 1, 50);
+        frames(&mut counter, 1, 150);
+    }
+    let stats = counter.stats().unwrap();
+    // the 50 frames of the window span 25 intervals of 50ms and 24 of 150ms
+    assert!((stats.avg - 49.0 / 4.85).abs() < 1e-6, "{:?}", stats);
+    assert!((stats.min - 1.0 / 0.15).abs() < 1e-6, "{:?}", stats);
+    assert!((stats.max - 20.0).abs() < 1e-6, "{:?}", stats);
+    assert!((stats.p90 - 20.0).abs() < 1e-6, "{:?}", stats);
+
+    // no frames in the window, not stalled yet
+    clock.advance(Duration::from_secs(6));
+    assert_eq!(counter.stats().unwrap().avg, 0.0);
+
+    // stalled source, once its frames are out of the window
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(counter.stats(), None);
+
+    // restarted at a lower rate, the previous frames are gone
+    frames(&mut counter, 6, 200);
+    let stats = counter.stats().unwrap();
+    assert!((stats.avg - 5.0).abs() < 1e-6, "{:?}", stats);
+    assert!((stats.max - 5.0).abs() < 1e-6, "{:?}", stats);
+}
+
+#[test]
+fn latency_histogram() {
+    let mut histogram = Histogram::default();
+    for latency in [0.004, 0.02, 0.02, 0.3, 12.0] {
+        histogram.observe(latency);
+    }
+    let buckets: Vec<(f64, u64)> = histogram.cumulative().collect();
+    assert_eq!(buckets[0], (0.01, 1));
+    assert_eq!(buckets[1], (0.025, 3));
+    assert_eq!(buckets[4], (0.25, 3));
+    assert_eq!(buckets[5], (0.5, 4));
+    assert_eq!(buckets.last(), Some(&(10.0, 4)));
+    assert_eq!(histogram.count, 5);
+
+    let mut writer = PrometheusWriter::new();
+    writer.histogram("latency_seconds", &[("stage", "osd")], &histogram);
+    let text = writer.finish();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        "latency_seconds_bucket{stage=\"osd\",le=\"0.01\"} 1"
+    );
+    assert_eq!(
+        lines[10],
+        "latency_seconds_bucket{stage=\"osd\",le=\"+Inf\"} 5"
+    );
+    assert_eq!(
+        lines[11],
+        format!("latency_seconds_sum{{stage=\"osd\"}} {}", histogram.sum)
+    );
+    assert_eq!(lines[12], "latency_seconds_count{stage=\"osd\"} 5");
+}
+
+/// Route `request` to a manager handling one command, replying `reply` or
+/// nothing, returning the response and the command the manager got.
+fn api_request(
+    request: TestRequest,
+    reply: Option<CommandResult>,
+) -> (ApiResponse, Option<Command>) {
+    let (events, received) = mpsc::channel();
+    let manager = thread::spawn(move || match received.recv() {
+        Ok(Event::Command(command, reply_sender)) => {
+            if let Some(result) = reply {
+                reply_sender.send(result).unwrap();
+            }
+            Some(command)
+        }
+        _ => None,
+    });
+
+    let response = route(&mut request.into(), &events);
+    drop(events);
+    (response, manager.join().unwrap())
+}
+
+#[test]
+fn api_routes() {
+    let error = |status, message: &str| (status, Body::Json(json!({ "error": message })));
+
+    let (response, command) = api_request(
+        TestRequest::new().with_path("/sources/7"),
+        Some(Err(CommandError::SourceNotFound(7))),
+    );
+    assert!(matches!(command, Some(Command::GetSource(7))));
+    assert_eq!(response, error(404, "Source 7 not found"));
+
+    let (response, command) = api_request(
+        TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/sources")
+            .with_body(r#"{"id": 3, "kind": {"type": "test"}}"#),
+        Some(Ok(Reply::Done)),
+    );
+    match command {
+        Some(Command::AddOrUpdateSource(config)) => {
+            assert_eq!(config.id, 3);
+            assert!(matches!(config.kind, SourceKind::Test));
+        }
+        _ => panic!("Unexpected command {:?}", command),
+    }
+    assert_eq!(response, (200, Body::Json(json!({ "ok": true }))));
+
+    let (response, command) = api_request(
+        TestRequest::new()
+            .with_method(Method::Delete)
+            .with_path("/sources/2?force"),
+        Some(Ok(Reply::Done)),
+    );
+    assert!(matches!(command, Some(Command::RemoveSource(2))));
+    assert_eq!(response.0, 200);
+
+    // rejected before reaching the manager
+    let (response, command) = api_request(TestRequest::new().with_path("/sources/two"), None);
+    assert!(command.is_none());
+    assert_eq!(response, error(400, "Invalid source id two"));
+    let (response, command) = api_request(
+        TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/sources")
+            .with_body(r#"{"id": 3}"#),
+        None,
+    );
+    assert!(command.is_none());
+    assert_eq!(response.0, 400);
+    let (response, command) = api_request(
+        TestRequest::new()
+            .with_method(Method::Put)
+            .with_path("/sources"),
+        None,
+    );
+    assert!(command.is_none());
+    assert_eq!(response, error(404, "No route for PUT /sources"));
+
+    // the manager dropped the command without replying
+    let (response, command) = api_request(TestRequest::new().with_path("/status"), None);
+    assert!(matches!(command, Some(Command::Status)));
+    assert_eq!(response, error(504, "Pipeline manager did not reply"));
+
+    // the manager isn't running
+    let (events, received) = mpsc::channel();
+    drop(received);
+    let response = route(&mut TestRequest::new().with_path("/status").into(), &events);
+    assert_eq!(response, error(503, "Pipeline manager not running"));
+}
+
+#[test]
+fn api_command_responses() {
+    assert_eq!(
+        command_response(Ok(Reply::Metrics("frames 1\n".to_string()))),
+        (200, Body::Text("frames 1\n".to_string()))
+    );
+    assert_eq!(
+        command_response(Err(CommandError::InvalidConfig("Bad uri".to_string()))),
+        (400, Body::Json(json!({ "error": "Bad uri" })))
+    );
+    assert_eq!(
+        command_response(Err(CommandError::Failed("Cant link".to_string()))).0,
+        500
+    );
+}
+
+#[test]
+fn source_registry_owner() {
+    gst::init().unwrap();
+    let mut sources = SourceRegistry::default();
+    sources.insert(1, gst::Bin::new(Some("source-bin-01")), false);
+    let bin = gst::Bin::new(Some("source-bin-02"));
+    sources.insert(2, bin.clone(), false);
+
+    let decoder = gst::Bin::new(Some("decoder"));
+    let parser = gst::Bin::new(Some("parser"));
+    bin.add(&decoder).unwrap();
+    decoder.add(&parser).unwrap();
+    assert_eq!(sources.owner(parser.upcast_ref()), Some(2));
+    assert_eq!(sources.owner(bin.upcast_ref()), Some(2));
+
+    let other = gst::Bin::new(Some("filter_bin"));
+    assert_eq!(sources.owner(other.upcast_ref()), None);
+    // the bins are compared, not their names
+    let same_name = gst::Bin::new(Some("source-bin-01"));
+    assert_eq!(sources.owner(same_name.upcast_ref()), None);
+    sources.remove(&2);
+    assert_eq!(sources.owner(parser.upcast_ref()), None);
+}
+
+#[test]
+fn source_restart_scheduling() {
+    gst::init().unwrap();
+    let mut sources = SourceRegistry::default();
+    let bin = gst::Bin::new(Some("source-bin-01"));
+    sources.insert(1, bin.clone(), false);
+    sources.insert(2, gst::Bin::new(Some("source-bin-02")), true);
+
+    assert_eq!(
+        sources.fail(&1, "Not found".to_string()),
+        Some(Recovery::Restart(bin.clone()))
+    );
+    // the next errors don't schedule another restart
+    assert_eq!(
+        sources.fail(&1, "Internal data stream error".to_string()),
+        Some(Recovery::Scheduled)
+    );
+    assert_eq!(
+        sources.state(&1).unwrap().status,
+        SourceStatus::Failed {
+            error: "Not found".to_string()
+        }
+    );
+
+    assert!(sources.restart(&1, &bin));
+    let state = sources.state(&1).unwrap();
+    assert_eq!(state.status, SourceStatus::Playing);
+    assert_eq!(state.restarts, 1);
+
+    // left stopped once it failed after its last restart
+    for _ in 1..MAX_SOURCE_RESTARTS {
+        sources.fail(&1, "Not found".to_string());
+        assert!(sources.restart(&1, &bin));
+    }
+    assert_eq!(
+        sources.fail(&1, "Not found".to_string()),
+        Some(Recovery::GiveUp(bin.clone()))
+    );
+    assert_eq!(sources.state(&1).unwrap().restarts, MAX_SOURCE_RESTARTS);
+
+    // replaced before its restart
+    let new_bin = gst::Bin::new(Some("source-bin-01"));
+    sources.insert(1, new_bin.clone(), false);
+    sources.fail(&1, "Not found".to_string());
+    assert!(!sources.restart(&1, &bin));
+    assert_eq!(sources.state(&1).unwrap().restarts, 0);
+
+    // removed before its restart
+    sources.remove(&1);
+    assert!(!sources.restart(&1, &new_bin));
+    assert_eq!(sources.fail(&1, "Not found".to_string()), None);
+
+    assert_eq!(
+        sources.fail(&2, "Timeout".to_string()),
+        Some(Recovery::Reconnect)
+    );
+    assert!(matches!(
+        sources.state(&2).unwrap().status,
+        SourceStatus::Failed { .. }
+    ));
+}
+
+#[test]
+fn cli_source_body() {
+    let config = SourceConfig {
+        id: 1,
+        kind: SourceKind::Rtsp {
+            uri: "rtsp://camera/stream".to_string(),
+            username: Some(Secret::new("user")),
+            password: Some(Secret::new("hunter2")),
+            reconnect: Default::default(),
+        },
+    };
+    let body = source_body(&config).unwrap();
+    assert_eq!(body["kind"]["password"], "hunter2");
+    assert_eq!(body["kind"]["username"], "user");
+    assert_eq!(body["kind"]["type"], "rtsp");
+
+    let config = SourceConfig {
+        id: 2,
+        kind: SourceKind::File {
+            path: "/videos/recording.mp4".to_string(),
+            looping: true,
+            start_offset: Some(HashableF64(5.0)),
+            end_offset: None,
+            playback_rate: None,
+        },
+    };
+    assert_eq!(
+        source_body(&config).unwrap(),
+        json!({
+            "id": 2,
+            "kind": {
+                "type": "file",
+                "path": "/videos/recording.mp4",
+                "loop": true,
+                "start_offset": 5.0,
+                "end_offset": null,
+                "playback_rate": null,
+            },
+        })
+    );
+}